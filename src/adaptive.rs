@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How many times slower than usual a response has to be to count as a latency spike.
+const LATENCY_SPIKE_FACTOR: u32 = 3;
+
+/// Tunes how many thumbnails are downloaded at once for `--adaptive-concurrency`, the way
+/// TCP tunes its window: starting at `min`, the limit grows by one after a limit's worth of
+/// healthy requests in a row, and is halved, down to `min`, whenever a request fails or
+/// takes more than three times as long as usual.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+    semaphore: Arc<Semaphore>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    /// Healthy requests since the limit last changed.
+    streak: usize,
+    /// A moving average of how long responses take.
+    usual_latency: Option<Duration>,
+    /// Permits to drop rather than return once they're released, as the limit fell while
+    /// they were in use.
+    excess: usize,
+}
+
+/// Allows one download while it's held.
+#[derive(Debug)]
+pub struct AdaptivePermit {
+    permit: Option<OwnedSemaphorePermit>,
+    controller: Arc<AdaptiveConcurrency>,
+}
+
+impl Drop for AdaptivePermit {
+    fn drop(&mut self) {
+        let mut state = self.controller.state.lock().unwrap();
+        if state.excess > 0 {
+            state.excess -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+impl AdaptiveConcurrency {
+    /// A controller allowing between `min` and `max` downloads at once; `max` is raised to
+    /// `min` if it's lower.
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        AdaptiveConcurrency {
+            min,
+            max: max.max(min),
+            semaphore: Arc::new(Semaphore::new(min)),
+            state: Mutex::new(State {
+                limit: min,
+                streak: 0,
+                usual_latency: None,
+                excess: 0,
+            }),
+        }
+    }
+
+    /// How many downloads may run at once right now.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Waits until another download may start.
+    pub async fn acquire(self: &Arc<Self>) -> AdaptivePermit {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        AdaptivePermit {
+            permit: Some(permit),
+            controller: self.clone(),
+        }
+    }
+
+    /// Records how a request went: whether it `succeeded` and how long it took.
+    pub fn record(&self, succeeded: bool, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let spike = state
+            .usual_latency
+            .is_some_and(|usual| latency > usual * LATENCY_SPIKE_FACTOR);
+        if succeeded {
+            state.usual_latency = Some(match state.usual_latency {
+                Some(usual) => (usual * 7 + latency) / 8,
+                None => latency,
+            });
+        }
+        if succeeded && !spike {
+            state.streak += 1;
+            if state.streak >= state.limit && state.limit < self.max {
+                state.limit += 1;
+                state.streak = 0;
+                if state.excess > 0 {
+                    state.excess -= 1;
+                } else {
+                    self.semaphore.add_permits(1);
+                }
+                tracing::debug!(limit = state.limit, "raised download concurrency");
+            }
+        } else {
+            state.streak = 0;
+            let limit = (state.limit / 2).max(self.min);
+            if limit < state.limit {
+                let removed = state.limit - limit;
+                // Permits in use are dropped as they're released.
+                state.excess += removed - self.semaphore.forget_permits(removed);
+                state.limit = limit;
+                tracing::debug!(limit, "lowered download concurrency");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_limit_rises_with_successes_and_halves_on_failure() {
+        let controller = AdaptiveConcurrency::new(2, 5);
+        let mut limits = Vec::new();
+        for _ in 0..14 {
+            controller.record(true, FAST);
+            limits.push(controller.limit());
+        }
+        assert_eq!(limits, [2, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 5, 5]);
+        assert_eq!(controller.semaphore.available_permits(), 5);
+
+        controller.record(false, FAST);
+        assert_eq!(controller.limit(), 2);
+        assert_eq!(controller.semaphore.available_permits(), 2);
+        controller.record(false, FAST);
+        assert_eq!(controller.limit(), 2);
+    }
+
+    #[test]
+    fn test_latency_spike_lowers_the_limit() {
+        let controller = AdaptiveConcurrency::new(1, 8);
+        for _ in 0..5 {
+            controller.record(true, FAST);
+        }
+        assert_eq!(controller.limit(), 3);
+
+        controller.record(true, FAST * 10);
+        assert_eq!(controller.limit(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_permits_in_use_are_dropped_after_lowering_the_limit() {
+        let controller = Arc::new(AdaptiveConcurrency::new(1, 4));
+        for _ in 0..5 {
+            controller.record(true, FAST);
+        }
+        assert_eq!(controller.limit(), 3);
+        let permits = [
+            controller.acquire().await,
+            controller.acquire().await,
+            controller.acquire().await,
+        ];
+
+        controller.record(false, FAST);
+        assert_eq!(controller.limit(), 1);
+        drop(permits);
+
+        assert_eq!(controller.semaphore.available_permits(), 1);
+    }
+}
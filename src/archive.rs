@@ -0,0 +1,148 @@
+use clap::ValueEnum;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// The format of an `--archive`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The file extension used for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+enum Writer {
+    Tar(tar::Builder<BufWriter<File>>),
+    Zip(Box<ZipWriter<BufWriter<File>>>),
+}
+
+/// An archive the thumbnails are added to one at a time as they're downloaded, so only the
+/// file being added is ever read into memory. It's written next to its final path and
+/// only moved there by `finish`, so an interrupted run doesn't leave a broken archive.
+pub struct Archive {
+    path: PathBuf,
+    temp_path: PathBuf,
+    /// Entries are named by their path relative to this directory.
+    output_dir: PathBuf,
+    writer: Mutex<Option<Writer>>,
+}
+
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Archive")
+            .field("path", &self.path)
+            .field("output_dir", &self.output_dir)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Archive {
+    pub fn create(path: &Path, format: ArchiveFormat, output_dir: &Path) -> io::Result<Self> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".part");
+        let temp_path = PathBuf::from(temp_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(&temp_path)?);
+        let writer = match format {
+            ArchiveFormat::Tar => Writer::Tar(tar::Builder::new(file)),
+            ArchiveFormat::Zip => Writer::Zip(Box::new(ZipWriter::new(file))),
+        };
+        Ok(Archive {
+            path: path.to_path_buf(),
+            temp_path,
+            output_dir: output_dir.to_path_buf(),
+            writer: Mutex::new(Some(writer)),
+        })
+    }
+
+    /// The path the archive is moved to once it's finished.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds the file at `path`, named by its path relative to the output directory.
+    /// Blocks while the file is copied, so call it off the async workers.
+    pub fn add(&self, path: &Path) -> io::Result<()> {
+        let name = entry_name(path.strip_prefix(&self.output_dir).unwrap_or(path));
+        let mut writer = self.writer.lock().unwrap();
+        match writer.as_mut() {
+            Some(Writer::Tar(builder)) => builder.append_path_with_name(path, name),
+            Some(Writer::Zip(zip)) => {
+                // Thumbnails are already compressed, so they're stored as they are.
+                let options =
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+                zip.start_file(name, options).map_err(io::Error::other)?;
+                io::copy(&mut File::open(path)?, zip.as_mut())?;
+                Ok(())
+            }
+            None => Err(io::Error::other("the archive is already finished")),
+        }
+    }
+
+    /// Writes the archive's index and moves it to its final path.
+    pub fn finish(&self) -> io::Result<()> {
+        let file = match self.writer.lock().unwrap().take() {
+            Some(Writer::Tar(builder)) => builder.into_inner()?,
+            Some(Writer::Zip(zip)) => zip.finish().map_err(io::Error::other)?,
+            None => return Ok(()),
+        };
+        file.into_inner().map_err(io::IntoInnerError::into_error)?;
+        fs::rename(&self.temp_path, &self.path)
+    }
+}
+
+/// Joins the components of a relative path with `/`, as archive entry names and object
+/// keys use.
+pub(crate) fn entry_name(relative: &Path) -> String {
+    relative
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tar_archive_names_entries_relative_to_the_output_dir() {
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(output_dir.join("2024")).unwrap();
+        let thumbnail = output_dir.join("2024").join("abc.jpg");
+        std::fs::write(&thumbnail, "image").unwrap();
+        let path = temp_dir.path().join("thumbnails.tar");
+
+        let archive = Archive::create(&path, ArchiveFormat::Tar, &output_dir).unwrap();
+        archive.add(&thumbnail).unwrap();
+        assert!(!path.exists());
+        archive.finish().unwrap();
+
+        let mut tar = tar::Archive::new(File::open(&path).unwrap());
+        let mut entries = tar.entries().unwrap();
+        let mut entry = entries.next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap(), Path::new("2024/abc.jpg"));
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "image");
+        assert!(entries.next().is_none());
+    }
+}
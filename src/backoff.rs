@@ -0,0 +1,101 @@
+use crate::shuffle::SplitMix64;
+use clap::ValueEnum;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How much randomness is added to the delay between retries.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait exactly the exponential backoff.
+    None,
+    /// Wait a random time between zero and the exponential backoff, so tasks that failed
+    /// together don't all retry at the same moment.
+    #[default]
+    Full,
+}
+
+/// Exponential backoff between retries of a failed download: the ceiling doubles with
+/// every attempt, starting at `base` and capped at `max`.
+#[derive(Debug)]
+pub struct Backoff {
+    retries: u32,
+    base: Duration,
+    max: Duration,
+    jitter: Jitter,
+    rng: Mutex<SplitMix64>,
+}
+
+impl Backoff {
+    pub fn new(retries: u32, base: Duration, max: Duration, jitter: Jitter, seed: u64) -> Self {
+        Backoff {
+            retries,
+            base,
+            max,
+            jitter,
+            rng: Mutex::new(SplitMix64::new(seed)),
+        }
+    }
+
+    /// How many times a failed download is retried.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The delay before retry number `attempt`, counting from zero.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max);
+        match self.jitter {
+            Jitter::None => ceiling,
+            Jitter::Full => {
+                let millis = ceiling.as_millis() as u64;
+                Duration::from_millis(self.rng.lock().unwrap().below(millis + 1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_millis(100);
+    const MAX: Duration = Duration::from_millis(1000);
+
+    #[test]
+    fn test_delay_without_jitter_doubles_up_to_max() {
+        let backoff = Backoff::new(6, BASE, MAX, Jitter::None, 0);
+        let delays: Vec<u64> = (0..6)
+            .map(|attempt| backoff.delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+    }
+
+    #[test]
+    fn test_full_jitter_stays_within_backoff() {
+        let backoff = Backoff::new(6, BASE, MAX, Jitter::Full, 42);
+        let again = Backoff::new(6, BASE, MAX, Jitter::Full, 42);
+        let ceilings = [100, 200, 400, 800, 1000, 1000];
+        let mut delays = Vec::new();
+        for round in 0..20 {
+            for (attempt, ceiling) in ceilings.iter().enumerate() {
+                let delay = backoff.delay(attempt as u32);
+                assert!(
+                    delay <= Duration::from_millis(*ceiling),
+                    "attempt {} of round {} waited {:?}",
+                    attempt,
+                    round,
+                    delay
+                );
+                assert_eq!(again.delay(attempt as u32), delay);
+                delays.push(delay);
+            }
+        }
+        // With full jitter the delays vary instead of repeating the same ceilings.
+        delays.sort();
+        delays.dedup();
+        assert!(delays.len() > ceilings.len());
+    }
+}
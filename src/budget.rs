@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A cap on the total number of bytes written during a run, shared between download tasks.
+#[derive(Debug)]
+pub struct ByteBudget {
+    limit: u64,
+    used: AtomicU64,
+}
+
+impl ByteBudget {
+    pub fn new(limit: u64) -> Self {
+        ByteBudget {
+            limit,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `bytes` as written, unless the budget was used up before them. Returns
+    /// whether they were, so a download that finishes after that can be discarded.
+    pub fn spend(&self, bytes: u64) -> bool {
+        self.used.fetch_add(bytes, Ordering::Relaxed) < self.limit
+    }
+
+    /// Whether the budget has been used up, meaning no new downloads should start.
+    pub fn exhausted(&self) -> bool {
+        self.used.load(Ordering::Relaxed) >= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_is_exhausted_once_limit_is_reached() {
+        let budget = ByteBudget::new(10);
+        assert!(!budget.exhausted());
+        assert!(budget.spend(6));
+        assert!(!budget.exhausted());
+        assert!(budget.spend(4));
+        assert!(budget.exhausted());
+        assert!(!budget.spend(1));
+    }
+}
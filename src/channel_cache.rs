@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// Name of the file, stored in the output directory, that maps channel URLs to IDs.
+pub const CHANNEL_CACHE_FILE: &str = "channel_cache.json";
+
+/// A channel ID and when it was resolved, in seconds since the Unix epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChannelCacheEntry {
+    pub channel_id: String,
+    pub resolved_at: u64,
+}
+
+/// Channel IDs previously resolved from channel URLs, so handles and custom names don't
+/// cost a search call on every run. Entries older than the TTL are ignored.
+#[derive(Debug)]
+pub struct ChannelCache {
+    path: PathBuf,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, ChannelCacheEntry>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl ChannelCache {
+    /// Loads the cache stored in `output_dir`, starting empty if it doesn't exist yet.
+    pub async fn load(output_dir: &str, ttl: Duration) -> Result<Self, Box<dyn Error>> {
+        let path = Path::new(output_dir).join(CHANNEL_CACHE_FILE);
+        let entries = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ChannelCache {
+            path,
+            ttl,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Returns the cached channel ID for a URL, unless it has expired.
+    pub fn get(&self, channel_url: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(channel_url)?;
+        (now().saturating_sub(entry.resolved_at) <= self.ttl.as_secs())
+            .then(|| entry.channel_id.clone())
+    }
+
+    pub fn insert(&self, channel_url: &str, channel_id: &str) {
+        self.entries.lock().unwrap().insert(
+            channel_url.to_string(),
+            ChannelCacheEntry {
+                channel_id: channel_id.to_string(),
+                resolved_at: now(),
+            },
+        );
+    }
+
+    /// Writes the cache back to disk.
+    pub async fn save(&self) -> Result<(), Box<dyn Error>> {
+        let contents = serde_json::to_string_pretty(&*self.entries.lock().unwrap())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_channel_cache_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let ttl = Duration::from_secs(3600);
+
+        let cache = ChannelCache::load(output_dir, ttl).await.unwrap();
+        assert_eq!(cache.get("https://www.youtube.com/@handle"), None);
+        cache.insert("https://www.youtube.com/@handle", "UC123");
+        cache.save().await.unwrap();
+
+        let reloaded = ChannelCache::load(output_dir, ttl).await.unwrap();
+        assert_eq!(
+            reloaded.get("https://www.youtube.com/@handle").as_deref(),
+            Some("UC123")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_cache_ignores_expired_entries() {
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let entries = HashMap::from([(
+            "https://www.youtube.com/@handle".to_string(),
+            ChannelCacheEntry {
+                channel_id: "UC123".to_string(),
+                resolved_at: now() - 7200,
+            },
+        )]);
+        std::fs::write(
+            temp_dir.path().join(CHANNEL_CACHE_FILE),
+            serde_json::to_string(&entries).unwrap(),
+        )
+        .unwrap();
+
+        let cache = ChannelCache::load(output_dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(cache.get("https://www.youtube.com/@handle"), None);
+
+        let cache = ChannelCache::load(output_dir, Duration::from_secs(86400))
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get("https://www.youtube.com/@handle").as_deref(),
+            Some("UC123")
+        );
+    }
+}
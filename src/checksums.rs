@@ -0,0 +1,187 @@
+use crate::dedup::ContentHash;
+use crate::paths;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+
+/// Name of the checksum file written to the output directory by `--checksums`.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// The SHA-256 hashes of downloaded thumbnails, keyed by their path relative to the output
+/// directory. Saved in the `<hash>  <path>` format of `sha256sum`, so an archive can be
+/// checked with `sha256sum -c SHA256SUMS`. Entries from earlier runs are kept.
+#[derive(Debug)]
+pub struct Checksums {
+    output_dir: PathBuf,
+    entries: Mutex<BTreeMap<String, String>>,
+}
+
+impl Checksums {
+    /// Loads the checksum file in `output_dir`, starting empty if it doesn't exist yet.
+    /// Lines that aren't in the `sha256sum` format are dropped.
+    pub async fn load(output_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = match fs::read_to_string(output_dir.join(CHECKSUMS_FILE)).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Checksums {
+            output_dir: output_dir.to_path_buf(),
+            entries: Mutex::new(parse_entries(&contents)),
+        })
+    }
+
+    /// Records the hash of the file saved at `path`.
+    pub fn record(&self, path: &Path, hash: &ContentHash) {
+        let relative = path.strip_prefix(&self.output_dir).unwrap_or(path);
+        let name = relative
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let hex = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.entries.lock().unwrap().insert(name, hex);
+    }
+
+    /// Writes the checksum file, via a temporary file so it's never left half-written.
+    /// With `fsync`, it's synced to disk before this returns.
+    pub async fn save(&self, fsync: bool) -> Result<(), Box<dyn Error>> {
+        let contents: String = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, hash)| format!("{}  {}\n", hash, name))
+            .collect();
+        paths::replace_file(&self.output_dir.join(CHECKSUMS_FILE), contents, fsync).await?;
+        Ok(())
+    }
+}
+
+/// Parses the lines of a checksum file into a map from file name to hash.
+fn parse_entries(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            // A '*' marks files hashed in binary mode, which makes no difference here.
+            let name = name.strip_prefix([' ', '*'])?;
+            Some((name.to_string(), hash.to_string()))
+        })
+        .collect()
+}
+
+/// The files of an archive that failed verification against its checksum file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// How many files the checksum file lists.
+    pub checked: usize,
+    /// Files whose contents no longer match their hash.
+    pub mismatched: Vec<String>,
+    /// Files that are listed but can't be read.
+    pub missing: Vec<String>,
+}
+
+impl Verification {
+    pub fn passed(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Re-hashes every file listed in the checksum file in `dir` and reports those that don't
+/// match. Fails if there's no checksum file.
+pub async fn verify(dir: &Path) -> Result<Verification, Box<dyn Error>> {
+    let path = dir.join(CHECKSUMS_FILE);
+    let contents = fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let mut verification = Verification::default();
+    for (name, expected) in parse_entries(&contents) {
+        verification.checked += 1;
+        match fs::read(dir.join(&name)).await {
+            Ok(bytes) => {
+                let actual: String = Sha256::digest(&bytes)
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                if !actual.eq_ignore_ascii_case(&expected) {
+                    verification.mismatched.push(name);
+                }
+            }
+            Err(_) => verification.missing.push(name),
+        }
+    }
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_checksums_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let checksums = Checksums::load(temp_dir.path()).await.unwrap();
+        checksums.record(&temp_dir.path().join("chan").join("b.jpg"), &[0xab; 32]);
+        checksums.record(&temp_dir.path().join("a.jpg"), &[0x01; 32]);
+        checksums.save(false).await.unwrap();
+
+        let contents = std::fs::read_to_string(temp_dir.path().join(CHECKSUMS_FILE)).unwrap();
+        assert_eq!(
+            contents,
+            format!(
+                "{}  a.jpg\n{}  chan/b.jpg\n",
+                "01".repeat(32),
+                "ab".repeat(32)
+            )
+        );
+
+        let reloaded = Checksums::load(temp_dir.path()).await.unwrap();
+        reloaded.record(&temp_dir.path().join("a.jpg"), &[0x02; 32]);
+        reloaded.save(false).await.unwrap();
+        let contents = std::fs::read_to_string(temp_dir.path().join(CHECKSUMS_FILE)).unwrap();
+        assert_eq!(
+            contents,
+            format!(
+                "{}  a.jpg\n{}  chan/b.jpg\n",
+                "02".repeat(32),
+                "ab".repeat(32)
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_flags_corrupted_and_missing_files() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        let checksums = Checksums::load(dir).await.unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg"] {
+            let contents = format!("thumbnail {}", name);
+            std::fs::write(dir.join(name), &contents).unwrap();
+            checksums.record(&dir.join(name), &Sha256::digest(&contents).into());
+        }
+        checksums.save(false).await.unwrap();
+        assert!(verify(dir).await.unwrap().passed());
+
+        std::fs::write(dir.join("b.jpg"), "corrupted").unwrap();
+        std::fs::remove_file(dir.join("d.jpg")).unwrap();
+        assert_eq!(
+            verify(dir).await.unwrap(),
+            Verification {
+                checked: 4,
+                mismatched: vec!["b.jpg".to_string()],
+                missing: vec!["d.jpg".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_needs_a_checksum_file() {
+        let temp_dir = tempdir().unwrap();
+        assert!(verify(temp_dir.path()).await.is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Stops every download task from hammering a thumbnail server that has started failing
+/// most requests. Once more than `threshold` percent of the last `window` requests failed,
+/// the breaker opens and requests wait for `cooldown`. A single probe request then decides:
+/// if it succeeds requests resume, otherwise the breaker waits another cooldown.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u8,
+    window: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+    /// Wakes requests waiting for the probe's result.
+    probed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Whether each of the latest requests succeeded, oldest first.
+    recent: VecDeque<bool>,
+    /// When the breaker is open, the end of its cooldown.
+    open_until: Option<Instant>,
+    /// Whether the probe request after a cooldown is in flight.
+    probing: bool,
+}
+
+/// What a request has to do before it may be sent.
+enum Gate {
+    Pass,
+    WaitUntil(Instant),
+    WaitForProbe,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u8, window: usize, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            window,
+            cooldown,
+            state: Mutex::default(),
+            probed: Notify::new(),
+        }
+    }
+
+    /// How long requests wait once the breaker opens.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Waits until a request may be sent. After a cooldown the first caller is let through
+    /// as the probe; the others wait for its result.
+    pub async fn acquire(&self) {
+        loop {
+            let probed = self.probed.notified();
+            let gate = {
+                let mut state = self.state.lock().unwrap();
+                match state.open_until {
+                    None => Gate::Pass,
+                    Some(until) if Instant::now() < until => Gate::WaitUntil(until),
+                    Some(_) if state.probing => Gate::WaitForProbe,
+                    Some(_) => {
+                        state.probing = true;
+                        Gate::Pass
+                    }
+                }
+            };
+            match gate {
+                Gate::Pass => return,
+                Gate::WaitUntil(until) => tokio::time::sleep_until(until).await,
+                Gate::WaitForProbe => probed.await,
+            }
+        }
+    }
+
+    /// Records the result of a request. Returns `true` if it opened the breaker.
+    pub fn record(&self, success: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            // Requests sent before the breaker opened may still finish; only the probe counts.
+            if !state.probing {
+                return false;
+            }
+            state.probing = false;
+            state.open_until = (!success).then(|| Instant::now() + self.cooldown);
+            self.probed.notify_waiters();
+            return !success;
+        }
+        state.recent.push_back(success);
+        if state.recent.len() > self.window {
+            state.recent.pop_front();
+        }
+        let failures = state.recent.iter().filter(|success| !**success).count();
+        if state.recent.len() == self.window
+            && failures * 100 > self.threshold as usize * self.window
+        {
+            state.recent.clear();
+            state.open_until = Some(Instant::now() + self.cooldown);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    /// How long `acquire` waits, in virtual time.
+    async fn wait(breaker: &CircuitBreaker) -> Duration {
+        let start = Instant::now();
+        breaker.acquire().await;
+        start.elapsed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker_trips_and_recovers_after_successful_probe() {
+        let breaker = CircuitBreaker::new(50, 10, COOLDOWN);
+        for _ in 0..5 {
+            assert!(!breaker.record(true));
+        }
+        // Half of the window failing isn't more than the threshold yet.
+        for _ in 0..5 {
+            assert!(!breaker.record(false));
+        }
+        assert_eq!(wait(&breaker).await, Duration::ZERO);
+        assert!(breaker.record(false));
+
+        assert!(wait(&breaker).await >= COOLDOWN);
+        assert!(!breaker.record(true));
+        assert_eq!(wait(&breaker).await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_probe_waits_another_cooldown() {
+        let breaker = CircuitBreaker::new(50, 4, COOLDOWN);
+        for _ in 0..4 {
+            breaker.record(false);
+        }
+        breaker.acquire().await;
+        assert!(breaker.record(false));
+
+        assert!(wait(&breaker).await >= COOLDOWN);
+        breaker.record(true);
+        assert_eq!(wait(&breaker).await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_wait_for_the_probe() {
+        let breaker = CircuitBreaker::new(50, 2, COOLDOWN);
+        breaker.record(false);
+        breaker.record(false);
+        wait(&breaker).await;
+
+        // The probe is in flight, so a second request waits for its result.
+        let waiting = wait(&breaker);
+        let probe = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            breaker.record(true);
+        };
+        let (waited, ()) = tokio::join!(waiting, probe);
+        assert_eq!(waited, Duration::from_secs(5));
+    }
+}
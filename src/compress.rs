@@ -0,0 +1,74 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Appends `.gz` to a path, for the files `--compress` writes: `abc.jpg` becomes `abc.jpg.gz`.
+pub fn gz_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".gz");
+    PathBuf::from(path)
+}
+
+/// Gzips `bytes` in memory.
+pub fn gzip(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Streams `source` through gzip into `destination`. The compressed data goes to a
+/// temporary file that's renamed into place, so `destination` is never half-written; with
+/// `fsync` it's synced to disk first. Blocks, so run it off the async workers.
+pub fn gzip_file(source: &Path, destination: &Path, fsync: bool) -> io::Result<()> {
+    let mut temp_path = destination.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut encoder = GzEncoder::new(File::create(&temp_path)?, Compression::default());
+    io::copy(&mut File::open(source)?, &mut encoder)?;
+    let file = encoder.finish()?;
+    if fsync {
+        file.sync_all()?;
+    }
+    drop(file);
+    std::fs::rename(&temp_path, destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn gunzip(bytes: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        assert_eq!(
+            gunzip(&gzip(b"{\"title\": \"A title\"}").unwrap()),
+            b"{\"title\": \"A title\"}"
+        );
+    }
+
+    #[test]
+    fn test_gzip_file_replaces_the_destination() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("abc.jpg.part");
+        let destination = gz_path(&temp_dir.path().join("abc.jpg"));
+        std::fs::write(&source, b"image bytes").unwrap();
+        std::fs::write(&destination, b"old").unwrap();
+
+        gzip_file(&source, &destination, true).unwrap();
+
+        assert_eq!(destination, temp_dir.path().join("abc.jpg.gz"));
+        assert_eq!(
+            gunzip(&std::fs::read(&destination).unwrap()),
+            b"image bytes"
+        );
+        assert!(!temp_dir.path().join("abc.jpg.gz.tmp").exists());
+    }
+}
@@ -0,0 +1,174 @@
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+/// Finds the value of `--config` among the raw command-line arguments, before they're parsed.
+pub fn find_config_path(argv: &[OsString]) -> Option<OsString> {
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return args.next().cloned();
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(path.into());
+        }
+    }
+    None
+}
+
+/// Turns the settings of a TOML config file into the command-line arguments they stand for,
+/// e.g. `concurrency = 4` into `--concurrency 4`, so they're checked exactly like flags.
+/// Settings for flags `given` on the command line are left out, so the flags win.
+/// Keys are long flag names, written with `_` or `-`. `true` turns a switch on and `false`
+/// leaves it off; an array gives a repeatable flag once per element.
+pub fn config_args(
+    contents: &str,
+    command: &Command,
+    given: &ArgMatches,
+) -> Result<Vec<OsString>, String> {
+    let settings: BTreeMap<String, toml::Value> =
+        toml::from_str(contents).map_err(|e| e.to_string())?;
+    let mut args = Vec::new();
+    for (key, value) in settings {
+        let name = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()) && name != "config")
+            .ok_or_else(|| format!("unknown setting \"{}\"", key))?;
+        if given.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = OsString::from(format!("--{}", name));
+        let values = match (value, arg.get_action()) {
+            (toml::Value::Boolean(on), action) if !action.takes_values() => {
+                if on {
+                    args.push(flag);
+                }
+                continue;
+            }
+            (toml::Value::Array(values), ArgAction::Append) => values,
+            (value, action) if action.takes_values() => vec![value],
+            (value, _) => return Err(type_error(&key, &value)),
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                value => return Err(type_error(&key, &value)),
+            };
+            args.push(flag.clone());
+            args.push(value.into());
+        }
+    }
+    Ok(args)
+}
+
+fn type_error(key: &str, value: &toml::Value) -> String {
+    let article = match value {
+        toml::Value::Integer(_) | toml::Value::Array(_) => "an",
+        _ => "a",
+    };
+    format!(
+        "setting \"{}\" can't be {} {}",
+        key,
+        article,
+        value.type_str()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn command() -> Command {
+        Command::new("test")
+            .arg(Arg::new("concurrency").long("concurrency"))
+            .arg(Arg::new("output_dir").long("output-dir"))
+            .arg(Arg::new("dedup").long("dedup").action(ArgAction::SetTrue))
+            .arg(
+                Arg::new("placeholder")
+                    .long("placeholder")
+                    .action(ArgAction::Append),
+            )
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue))
+    }
+
+    fn no_flags() -> ArgMatches {
+        command().get_matches_from(["test"])
+    }
+
+    #[test]
+    fn test_find_config_path() {
+        let argv = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            find_config_path(&argv(&["prog", "-c", "url", "--config", "a.toml"])),
+            Some("a.toml".into())
+        );
+        assert_eq!(
+            find_config_path(&argv(&["prog", "--config=b.toml"])),
+            Some("b.toml".into())
+        );
+        assert_eq!(find_config_path(&argv(&["prog", "-c", "url"])), None);
+    }
+
+    #[test]
+    fn test_config_args() {
+        let contents = "concurrency = 4\noutput_dir = \"out\"\ndedup = true\njson = false\n";
+        assert_eq!(
+            config_args(contents, &command(), &no_flags()).unwrap(),
+            ["--concurrency", "4", "--dedup", "--output-dir", "out"]
+        );
+    }
+
+    #[test]
+    fn test_config_args_leaves_out_given_flags() {
+        let given = command().get_matches_from(["test", "--concurrency", "8", "--json"]);
+        assert_eq!(
+            config_args(
+                "concurrency = 4\ndedup = true\njson = false",
+                &command(),
+                &given
+            )
+            .unwrap(),
+            ["--dedup"]
+        );
+    }
+
+    #[test]
+    fn test_config_args_repeats_flags_for_arrays() {
+        assert_eq!(
+            config_args(
+                "placeholder = [\"120x90\", \"1x1\"]",
+                &command(),
+                &no_flags()
+            )
+            .unwrap(),
+            ["--placeholder", "120x90", "--placeholder", "1x1"]
+        );
+    }
+
+    #[test]
+    fn test_config_args_rejects_unknown_and_mistyped_settings() {
+        assert_eq!(
+            config_args("threads = 4", &command(), &no_flags()).unwrap_err(),
+            "unknown setting \"threads\""
+        );
+        assert_eq!(
+            config_args("dedup = 1", &command(), &no_flags()).unwrap_err(),
+            "setting \"dedup\" can't be an integer"
+        );
+        assert_eq!(
+            config_args("concurrency = true", &command(), &no_flags()).unwrap_err(),
+            "setting \"concurrency\" can't be a boolean"
+        );
+        assert_eq!(
+            config_args("concurrency = [1, 2]", &command(), &no_flags()).unwrap_err(),
+            "setting \"concurrency\" can't be an array"
+        );
+    }
+}
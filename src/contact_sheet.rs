@@ -0,0 +1,109 @@
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Colour of cells whose thumbnail is missing or can't be decoded.
+const BLANK_CELL: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// Tiles the given thumbnails into a grid with `columns` cells per row.
+/// Every cell is `cell_width` wide with a 16:9 aspect ratio; images are scaled to fit
+/// and centred. Missing or unreadable files leave a blank cell.
+pub fn build_contact_sheet(paths: &[PathBuf], columns: u32, cell_width: u32) -> RgbImage {
+    let columns = columns.max(1);
+    let cell_height = (cell_width * 9 / 16).max(1);
+    let rows = (paths.len() as u32).div_ceil(columns).max(1);
+    let mut sheet = RgbImage::from_pixel(columns * cell_width, rows * cell_height, BLANK_CELL);
+
+    for (index, path) in paths.iter().enumerate() {
+        let Ok(thumbnail) = image::open(path) else {
+            continue;
+        };
+        let cell = thumbnail
+            .resize(cell_width, cell_height, FilterType::Triangle)
+            .to_rgb8();
+        let index = index as u32;
+        let x = (index % columns) * cell_width + (cell_width - cell.width()) / 2;
+        let y = (index / columns) * cell_height + (cell_height - cell.height()) / 2;
+        imageops::replace(&mut sheet, &cell, i64::from(x), i64::from(y));
+    }
+
+    sheet
+}
+
+/// Builds a contact sheet and writes it to `output` as a PNG.
+pub fn write_contact_sheet(
+    paths: &[PathBuf],
+    columns: u32,
+    cell_width: u32,
+    output: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let sheet = build_contact_sheet(paths, columns, cell_width);
+    sheet.save_with_format(output, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_fixture(dir: &Path, name: &str, color: [u8; 3]) -> PathBuf {
+        let path = dir.join(name);
+        RgbImage::from_pixel(32, 18, Rgb(color))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_build_2x2_contact_sheet() {
+        let temp_dir = tempdir().unwrap();
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let paths: Vec<PathBuf> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| write_fixture(temp_dir.path(), &format!("{}.png", i), *color))
+            .collect();
+
+        let sheet = build_contact_sheet(&paths, 2, 16);
+
+        assert_eq!((sheet.width(), sheet.height()), (32, 18));
+        for (i, color) in colors.iter().enumerate() {
+            let x = (i as u32 % 2) * 16 + 8;
+            let y = (i as u32 / 2) * 9 + 4;
+            assert_eq!(sheet.get_pixel(x, y), &Rgb(*color));
+        }
+    }
+
+    #[test]
+    fn test_missing_thumbnail_leaves_blank_cell() {
+        let temp_dir = tempdir().unwrap();
+        let paths = vec![
+            write_fixture(temp_dir.path(), "present.png", [255, 0, 0]),
+            temp_dir.path().join("missing.jpg"),
+            write_fixture(temp_dir.path(), "also_present.png", [0, 0, 255]),
+        ];
+
+        let sheet = build_contact_sheet(&paths, 2, 16);
+
+        assert_eq!((sheet.width(), sheet.height()), (32, 18));
+        assert_eq!(sheet.get_pixel(8, 4), &Rgb([255, 0, 0]));
+        assert_eq!(sheet.get_pixel(24, 4), &BLANK_CELL);
+        assert_eq!(sheet.get_pixel(8, 13), &Rgb([0, 0, 255]));
+        // The unused last cell is blank too.
+        assert_eq!(sheet.get_pixel(24, 13), &BLANK_CELL);
+    }
+
+    #[test]
+    fn test_write_contact_sheet_as_png() {
+        let temp_dir = tempdir().unwrap();
+        let paths = vec![write_fixture(temp_dir.path(), "a.png", [1, 2, 3])];
+        let output = temp_dir.path().join("sheet.png");
+
+        write_contact_sheet(&paths, 4, 16, &output).unwrap();
+
+        let contents = std::fs::read(&output).unwrap();
+        assert!(contents.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+}
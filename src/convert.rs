@@ -0,0 +1,372 @@
+use clap::ValueEnum;
+#[cfg(feature = "image-processing")]
+use image::ImageFormat;
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "image-processing")]
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Whether this build can decode and encode images, which needs the `image-processing`
+/// feature.
+pub const IMAGE_PROCESSING: bool = cfg!(feature = "image-processing");
+
+/// The image format thumbnails are saved in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Save the JPEG exactly as served by YouTube.
+    #[default]
+    Jpg,
+    /// Re-encode to lossless PNG.
+    Png,
+    /// Re-encode to lossy WebP (see --quality).
+    Webp,
+}
+
+impl OutputFormat {
+    /// The file extension used for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+
+    #[cfg(feature = "image-processing")]
+    fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Jpg => ImageFormat::Jpeg,
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+/// A size given as `WIDTHxHEIGHT`, e.g. `640x360` for `--resize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(size: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("\"{}\" isn't a size like 640x360", size);
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let dimension = |value: &str| {
+            value
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|&value| value > 0)
+                .ok_or_else(invalid)
+        };
+        Ok(Size {
+            width: dimension(width)?,
+            height: dimension(height)?,
+        })
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// How `--resize` fits a thumbnail into the requested size.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale the whole image to fit, keeping its aspect ratio, and pad the rest with black.
+    #[default]
+    Fit,
+    /// Scale the image to cover the size, keeping its aspect ratio, and crop what's left
+    /// over from the middle.
+    Fill,
+    /// Scale the width and height separately, distorting the image if the aspect ratios
+    /// differ.
+    Stretch,
+}
+
+/// Converts a downloaded image in the `source` format, normally a JPEG, into the requested
+/// format. `quality` (0-100) only applies to WebP. Images already in the requested format
+/// are returned unchanged.
+#[cfg(feature = "image-processing")]
+pub fn convert_image(
+    bytes: Vec<u8>,
+    source: OutputFormat,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if format == source {
+        return Ok(bytes);
+    }
+
+    let image = image::load_from_memory_with_format(&bytes, source.image_format())?;
+    encode(&image, format, quality)
+}
+
+/// Resizes an image saved in `format` to `size` the way `mode` asks, re-encoding it in the
+/// same format. Also returns the image's original size.
+#[cfg(feature = "image-processing")]
+pub fn resize_image(
+    bytes: &[u8],
+    format: OutputFormat,
+    size: Size,
+    mode: ResizeMode,
+    quality: u8,
+) -> Result<(Vec<u8>, Size), Box<dyn Error + Send + Sync>> {
+    use image::imageops::FilterType;
+
+    let image = image::load_from_memory_with_format(bytes, format.image_format())?;
+    let original = Size {
+        width: image.width(),
+        height: image.height(),
+    };
+    let resized = match mode {
+        ResizeMode::Fit => {
+            let scaled = image.resize(size.width, size.height, FilterType::Lanczos3);
+            let mut canvas = image::RgbImage::new(size.width, size.height);
+            let x = (size.width - scaled.width()) / 2;
+            let y = (size.height - scaled.height()) / 2;
+            image::imageops::overlay(&mut canvas, &scaled.to_rgb8(), x.into(), y.into());
+            image::DynamicImage::ImageRgb8(canvas)
+        }
+        ResizeMode::Fill => image.resize_to_fill(size.width, size.height, FilterType::Lanczos3),
+        ResizeMode::Stretch => image.resize_exact(size.width, size.height, FilterType::Lanczos3),
+    };
+    Ok((encode(&resized, format, quality)?, original))
+}
+
+/// Encodes an image in the given format. `quality` (0-100) only applies to WebP.
+#[cfg(feature = "image-processing")]
+fn encode(
+    image: &image::DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    match format {
+        OutputFormat::Jpg => {
+            let mut output = Cursor::new(Vec::new());
+            image.to_rgb8().write_to(&mut output, ImageFormat::Jpeg)?;
+            Ok(output.into_inner())
+        }
+        OutputFormat::Png => {
+            let mut output = Cursor::new(Vec::new());
+            image.write_to(&mut output, ImageFormat::Png)?;
+            Ok(output.into_inner())
+        }
+        OutputFormat::Webp => {
+            // WebP only supports 8-bit RGB(A); thumbnails are plain RGB.
+            let rgb = image::DynamicImage::ImageRgb8(image.to_rgb8());
+            let encoder = webp::Encoder::from_image(&rgb).map_err(|e| e.to_string())?;
+            Ok(encoder.encode(f32::from(quality)).to_vec())
+        }
+    }
+}
+
+/// Without image processing, images can't be resized.
+#[cfg(not(feature = "image-processing"))]
+pub fn resize_image(
+    _bytes: &[u8],
+    _format: OutputFormat,
+    _size: Size,
+    _mode: ResizeMode,
+    _quality: u8,
+) -> Result<(Vec<u8>, Size), Box<dyn Error + Send + Sync>> {
+    Err(NO_IMAGE_PROCESSING.into())
+}
+
+/// Without image processing, only images already in the requested format can be saved.
+#[cfg(not(feature = "image-processing"))]
+pub fn convert_image(
+    bytes: Vec<u8>,
+    source: OutputFormat,
+    format: OutputFormat,
+    _quality: u8,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if format == source {
+        Ok(bytes)
+    } else {
+        Err(NO_IMAGE_PROCESSING.into())
+    }
+}
+
+/// Reads the width and height of an image file from its header, whatever its extension.
+#[cfg(feature = "image-processing")]
+pub fn image_dimensions(path: &Path) -> Result<(u32, u32), Box<dyn Error + Send + Sync>> {
+    Ok(image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()?)
+}
+
+/// Without image processing, image sizes can't be read.
+#[cfg(not(feature = "image-processing"))]
+pub fn image_dimensions(_path: &Path) -> Result<(u32, u32), Box<dyn Error + Send + Sync>> {
+    Err(NO_IMAGE_PROCESSING.into())
+}
+
+#[cfg(not(feature = "image-processing"))]
+const NO_IMAGE_PROCESSING: &str = "this build has no image processing support";
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Encodes a tiny valid JPEG to use as a thumbnail fixture.
+    #[cfg(feature = "image-processing")]
+    pub(crate) fn tiny_jpeg() -> Vec<u8> {
+        let image = image::RgbImage::from_pixel(4, 3, image::Rgb([200, 40, 40]));
+        let mut output = Cursor::new(Vec::new());
+        image.write_to(&mut output, ImageFormat::Jpeg).unwrap();
+        output.into_inner()
+    }
+
+    #[cfg(not(feature = "image-processing"))]
+    #[test]
+    fn test_conversion_fails_without_image_processing() {
+        assert!(convert_image(b"jpeg".to_vec(), OutputFormat::Jpg, OutputFormat::Png, 80).is_err());
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        assert!(resize_image(b"jpeg", OutputFormat::Jpg, size, ResizeMode::Fit, 80).is_err());
+        assert!(image_dimensions(Path::new("thumbnail.jpg")).is_err());
+    }
+
+    #[test]
+    fn test_jpg_is_passthrough() {
+        let bytes = b"not even a real jpeg".to_vec();
+        assert_eq!(
+            convert_image(bytes.clone(), OutputFormat::Jpg, OutputFormat::Jpg, 80).unwrap(),
+            bytes
+        );
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_convert_to_png() {
+        let png = convert_image(tiny_jpeg(), OutputFormat::Jpg, OutputFormat::Png, 80).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_convert_to_webp() {
+        let webp = convert_image(tiny_jpeg(), OutputFormat::Jpg, OutputFormat::Webp, 50).unwrap();
+        assert_eq!(&webp[..4], b"RIFF");
+        assert_eq!(&webp[8..12], b"WEBP");
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_convert_webp_source_to_png() {
+        let webp = convert_image(tiny_jpeg(), OutputFormat::Jpg, OutputFormat::Webp, 50).unwrap();
+        assert_eq!(
+            convert_image(webp.clone(), OutputFormat::Webp, OutputFormat::Webp, 50).unwrap(),
+            webp
+        );
+        let png = convert_image(webp, OutputFormat::Webp, OutputFormat::Png, 80).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_convert_invalid_jpeg_fails() {
+        assert!(convert_image(
+            b"garbage".to_vec(),
+            OutputFormat::Jpg,
+            OutputFormat::Png,
+            80
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(
+            "640x360".parse(),
+            Ok(Size {
+                width: 640,
+                height: 360
+            })
+        );
+        assert_eq!("1280X720".parse::<Size>().unwrap().to_string(), "1280x720");
+        assert!("640".parse::<Size>().is_err());
+        assert!("0x360".parse::<Size>().is_err());
+        assert!("640x-1".parse::<Size>().is_err());
+    }
+
+    /// A 40x20 JPEG, white in the left quarter and black elsewhere.
+    #[cfg(feature = "image-processing")]
+    fn wide_jpeg() -> Vec<u8> {
+        let image = image::RgbImage::from_fn(40, 20, |x, _| {
+            if x < 10 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        });
+        let mut output = Cursor::new(Vec::new());
+        image.write_to(&mut output, ImageFormat::Jpeg).unwrap();
+        output.into_inner()
+    }
+
+    #[cfg(feature = "image-processing")]
+    fn resized(mode: ResizeMode) -> image::RgbImage {
+        let size = Size {
+            width: 20,
+            height: 20,
+        };
+        let (bytes, original) =
+            resize_image(&wide_jpeg(), OutputFormat::Jpg, size, mode, 80).unwrap();
+        assert_eq!(original.to_string(), "40x20");
+        image::load_from_memory(&bytes).unwrap().to_rgb8()
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_resize_fit_pads_to_keep_the_aspect_ratio() {
+        let image = resized(ResizeMode::Fit);
+        assert_eq!(image.dimensions(), (20, 20));
+        // The image is scaled to 20x10 and centred, with black bars above and below it.
+        assert!(image.get_pixel(1, 1)[0] < 50);
+        assert!(image.get_pixel(1, 10)[0] > 200);
+        assert!(image.get_pixel(1, 18)[0] < 50);
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_resize_fill_crops_the_middle() {
+        let image = resized(ResizeMode::Fill);
+        assert_eq!(image.dimensions(), (20, 20));
+        // Covering 20x20 keeps the image at 40x20; cropping its middle cuts the white quarter.
+        assert!(image.get_pixel(2, 10)[0] < 50);
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_resize_stretch_fills_the_size() {
+        let image = resized(ResizeMode::Stretch);
+        assert_eq!(image.dimensions(), (20, 20));
+        // The white quarter is squeezed into the left 5 pixels, from top to bottom.
+        assert!(image.get_pixel(1, 1)[0] > 200);
+        assert!(image.get_pixel(1, 18)[0] > 200);
+        assert!(image.get_pixel(15, 10)[0] < 50);
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_image_dimensions_ignores_extension() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("thumbnail.jpg.part");
+        std::fs::write(&path, tiny_jpeg()).unwrap();
+        assert_eq!(image_dimensions(&path).unwrap(), (4, 3));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::middleware::Client;
+use crate::quota;
+use reqwest_middleware::RequestBuilder;
+
+/// How requests to the YouTube Data API are authorized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credentials<'a> {
+    /// An API key, sent as the `key` query parameter. Only reaches public data.
+    ApiKey(&'a str),
+    /// An OAuth 2.0 access token, sent as a bearer token. Can also reach the private
+    /// content of the account it was issued for.
+    AccessToken(&'a str),
+}
+
+impl Credentials<'_> {
+    /// The query parameter to put in a Data API URL: `&key=...` for an API key, and
+    /// nothing for an access token.
+    pub fn key_param(&self) -> String {
+        match self {
+            Credentials::ApiKey(key) => format!("&key={}", key),
+            Credentials::AccessToken(_) => String::new(),
+        }
+    }
+
+    /// Starts a GET request to a Data API URL built with `key_param`, adding the
+    /// `Authorization` header for an access token. The request's quota cost is counted.
+    pub fn get(&self, client: &Client, url: &str) -> RequestBuilder {
+        quota::record(url);
+        match self {
+            Credentials::ApiKey(_) => client.get(url),
+            Credentials::AccessToken(token) => client.get(url).bearer_auth(token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_param() {
+        assert_eq!(Credentials::ApiKey("abc").key_param(), "&key=abc");
+        assert_eq!(Credentials::AccessToken("abc").key_param(), "");
+    }
+}
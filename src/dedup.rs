@@ -0,0 +1,87 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+
+/// A SHA-256 content hash.
+pub type ContentHash = [u8; 32];
+
+/// Tracks the content hash of every thumbnail saved during a run, so identical
+/// images are only stored once.
+#[derive(Debug, Default)]
+pub struct DedupIndex {
+    saved: Mutex<HashMap<ContentHash, PathBuf>>,
+}
+
+impl DedupIndex {
+    /// Records `path` as the file holding `hash`, unless another file already does,
+    /// in which case the path of that original is returned.
+    pub fn claim(&self, hash: ContentHash, path: &Path) -> Option<PathBuf> {
+        match self.saved.lock().unwrap().entry(hash) {
+            Entry::Occupied(entry) => Some(entry.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(path.to_path_buf());
+                None
+            }
+        }
+    }
+}
+
+/// Points `duplicate` at `original` instead of storing a second copy.
+/// Creates a symlink where supported, and otherwise a `.dup` text file holding the
+/// original's path. Returns the path that was created.
+pub async fn link_duplicate(original: &Path, duplicate: &Path) -> io::Result<PathBuf> {
+    // Link relative to the duplicate when both live in the same directory, so the
+    // output directory can be moved around without breaking links.
+    let target = if original.parent() == duplicate.parent() {
+        PathBuf::from(original.file_name().unwrap_or(original.as_os_str()))
+    } else {
+        std::path::absolute(original)?
+    };
+
+    if fs::symlink_metadata(duplicate).await.is_ok() {
+        fs::remove_file(duplicate).await?;
+    }
+
+    #[cfg(unix)]
+    {
+        fs::symlink(&target, duplicate).await?;
+        Ok(duplicate.to_path_buf())
+    }
+    #[cfg(not(unix))]
+    {
+        let mut pointer = duplicate.as_os_str().to_owned();
+        pointer.push(".dup");
+        let pointer = PathBuf::from(pointer);
+        fs::write(&pointer, target.to_string_lossy().as_bytes()).await?;
+        Ok(pointer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn hash_bytes(bytes: &[u8]) -> ContentHash {
+        Sha256::digest(bytes).into()
+    }
+
+    #[test]
+    fn test_claim_returns_first_path_for_same_hash() {
+        let index = DedupIndex::default();
+        let hash = hash_bytes(b"same");
+
+        assert_eq!(index.claim(hash, Path::new("a.jpg")), None);
+        assert_eq!(
+            index.claim(hash, Path::new("b.jpg")),
+            Some(PathBuf::from("a.jpg"))
+        );
+        assert_eq!(
+            index.claim(hash_bytes(b"different"), Path::new("c.jpg")),
+            None
+        );
+    }
+}
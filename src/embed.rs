@@ -0,0 +1,225 @@
+use std::error::Error;
+
+/// JPEG markers this module reads or writes.
+const SOI: [u8; 2] = [0xFF, 0xD8];
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const COM: u8 = 0xFE;
+
+/// EXIF tags and field types written by `embed_metadata`.
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010E;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_USER_COMMENT: u16 = 0x9286;
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+const TYPE_UNDEFINED: u16 = 7;
+
+/// The character code prefixing a `UserComment` written in ASCII.
+const ASCII_CHARACTER_CODE: &[u8; 8] = b"ASCII\0\0\0";
+
+/// What `--embed-metadata` writes into a thumbnail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedMetadata {
+    pub video_id: String,
+    /// The video's title, empty if unknown.
+    pub title: String,
+    /// The URL the thumbnail was downloaded from.
+    pub source_url: String,
+}
+
+/// Returns `jpeg` with an EXIF segment holding the title as `ImageDescription` and the video
+/// ID as `UserComment`, followed by a comment segment holding the source URL. The title is
+/// written as UTF-8, which EXIF readers generally accept; the video ID stands in for a
+/// missing title. The image data itself is left untouched.
+pub fn embed_metadata(
+    jpeg: &[u8],
+    metadata: &EmbeddedMetadata,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut rest = jpeg.strip_prefix(&SOI).ok_or("not a JPEG image")?;
+    let mut output = Vec::with_capacity(jpeg.len() + 512);
+    output.extend_from_slice(&SOI);
+    // A JFIF header has to stay directly after the start of the image.
+    if let [0xFF, APP0, high, low, ..] = *rest {
+        let len = 2 + usize::from(u16::from_be_bytes([high, low]));
+        let (jfif, after) = rest.split_at_checked(len).ok_or("truncated JPEG header")?;
+        output.extend_from_slice(jfif);
+        rest = after;
+    }
+    let description = if metadata.title.is_empty() {
+        &metadata.video_id
+    } else {
+        &metadata.title
+    };
+    let mut exif = b"Exif\0\0".to_vec();
+    exif.extend(tiff(description, &metadata.video_id));
+    write_segment(&mut output, APP1, &exif)?;
+    write_segment(&mut output, COM, metadata.source_url.as_bytes())?;
+    output.extend_from_slice(rest);
+    Ok(output)
+}
+
+/// Appends a marker segment, which can hold at most 65533 bytes.
+fn write_segment(
+    output: &mut Vec<u8>,
+    marker: u8,
+    data: &[u8],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let len = u16::try_from(data.len() + 2).map_err(|_| "metadata too long to embed")?;
+    output.extend_from_slice(&[0xFF, marker]);
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(data);
+    Ok(())
+}
+
+/// Builds a little-endian TIFF structure with `ImageDescription` in the first IFD, which
+/// points to an EXIF IFD holding `UserComment`.
+fn tiff(description: &str, comment: &str) -> Vec<u8> {
+    let mut description = description.as_bytes().to_vec();
+    description.push(0);
+    let mut comment_bytes = ASCII_CHARACTER_CODE.to_vec();
+    comment_bytes.extend_from_slice(comment.as_bytes());
+
+    // The header, then IFD0 with two entries and the EXIF IFD with one, then their values.
+    let ifd0 = 8;
+    let exif_ifd = ifd0 + 2 + 2 * 12 + 4;
+    let description_offset = exif_ifd + 2 + 12 + 4;
+    // Values start on a word boundary.
+    let comment_offset = description_offset + description.len().next_multiple_of(2);
+
+    let mut tiff = Vec::new();
+    tiff.extend_from_slice(b"II");
+    tiff.extend_from_slice(&42u16.to_le_bytes());
+    tiff.extend_from_slice(&(ifd0 as u32).to_le_bytes());
+
+    tiff.extend_from_slice(&2u16.to_le_bytes());
+    entry(
+        &mut tiff,
+        TAG_IMAGE_DESCRIPTION,
+        TYPE_ASCII,
+        &description,
+        description_offset,
+    );
+    entry(
+        &mut tiff,
+        TAG_EXIF_IFD,
+        TYPE_LONG,
+        &(exif_ifd as u32).to_le_bytes(),
+        0,
+    );
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    entry(
+        &mut tiff,
+        TAG_USER_COMMENT,
+        TYPE_UNDEFINED,
+        &comment_bytes,
+        comment_offset,
+    );
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+
+    tiff.extend_from_slice(&description);
+    tiff.resize(comment_offset, 0);
+    tiff.extend_from_slice(&comment_bytes);
+    tiff
+}
+
+/// Appends an IFD entry for a field of one-byte values, or a single LONG. Values of up to
+/// four bytes are stored in the entry itself; longer ones at `offset`.
+fn entry(tiff: &mut Vec<u8>, tag: u16, field_type: u16, value: &[u8], offset: usize) {
+    let count = if field_type == TYPE_LONG {
+        1
+    } else {
+        value.len()
+    };
+    tiff.extend_from_slice(&tag.to_le_bytes());
+    tiff.extend_from_slice(&field_type.to_le_bytes());
+    tiff.extend_from_slice(&(count as u32).to_le_bytes());
+    if value.len() <= 4 {
+        let mut inline = [0; 4];
+        inline[..value.len()].copy_from_slice(value);
+        tiff.extend_from_slice(&inline);
+    } else {
+        tiff.extend_from_slice(&(offset as u32).to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{In, Tag, Value};
+    use std::io::Cursor;
+
+    /// The start of a JPEG with a JFIF header, enough for EXIF readers.
+    const JFIF_JPEG: &[u8] = &[
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x01, 0x00, 0x00,
+        0x01, 0x00, 0x01, 0x00, 0x00, 0xFF, 0xD9,
+    ];
+
+    fn metadata(title: &str) -> EmbeddedMetadata {
+        EmbeddedMetadata {
+            video_id: "dQw4w9WgXcQ".to_string(),
+            title: title.to_string(),
+            source_url: "https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg".to_string(),
+        }
+    }
+
+    /// Reads back the description, the user comment and the comment segment.
+    fn read_back(jpeg: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(jpeg))
+            .unwrap();
+        let description = match &exif
+            .get_field(Tag::ImageDescription, In::PRIMARY)
+            .unwrap()
+            .value
+        {
+            Value::Ascii(values) => values[0].clone(),
+            value => panic!("unexpected ImageDescription {:?}", value),
+        };
+        let comment = match &exif.get_field(Tag::UserComment, In::PRIMARY).unwrap().value {
+            Value::Undefined(bytes, _) => bytes.clone(),
+            value => panic!("unexpected UserComment {:?}", value),
+        };
+        let marker = jpeg
+            .windows(2)
+            .position(|window| window == [0xFF, COM])
+            .unwrap();
+        let len = usize::from(u16::from_be_bytes([jpeg[marker + 2], jpeg[marker + 3]]));
+        let url = jpeg[marker + 4..marker + 2 + len].to_vec();
+        (description, comment, url)
+    }
+
+    #[test]
+    fn test_embedded_fields_can_be_read_back() {
+        let jpeg = embed_metadata(JFIF_JPEG, &metadata("Never Gonna Give You Up")).unwrap();
+
+        assert_eq!(&jpeg[..20], &JFIF_JPEG[..20]);
+        assert!(jpeg.ends_with(&[0xFF, 0xD9]));
+        let (description, comment, url) = read_back(&jpeg);
+        assert_eq!(description, b"Never Gonna Give You Up");
+        assert_eq!(comment, b"ASCII\0\0\0dQw4w9WgXcQ");
+        assert_eq!(url, b"https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg");
+    }
+
+    #[test]
+    fn test_video_id_describes_untitled_videos() {
+        let jpeg = embed_metadata(&[0xFF, 0xD8, 0xFF, 0xD9], &metadata("")).unwrap();
+        assert_eq!(read_back(&jpeg).0, b"dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn test_rejects_other_images() {
+        assert!(embed_metadata(b"\x89PNG\r\n", &metadata("title")).is_err());
+        let too_long = metadata(&"x".repeat(70_000));
+        assert!(embed_metadata(JFIF_JPEG, &too_long).is_err());
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_embedded_jpeg_still_decodes() {
+        let jpeg = embed_metadata(&crate::convert::tests::tiny_jpeg(), &metadata("Title")).unwrap();
+        let image = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (4, 3));
+    }
+}
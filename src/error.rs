@@ -0,0 +1,257 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Exit status when some thumbnails or channels failed.
+pub const EXIT_PARTIAL_FAILURE: i32 = 2;
+/// Exit status when the YouTube Data API quota ran out.
+pub const EXIT_QUOTA_EXCEEDED: i32 = 3;
+/// Exit status when a channel couldn't be found.
+pub const EXIT_CHANNEL_NOT_FOUND: i32 = 4;
+/// Exit status for invalid arguments or an unusable channel URL.
+pub const EXIT_INVALID_INPUT: i32 = 5;
+/// Exit status for any other error.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Why downloading a thumbnail failed.
+#[derive(Debug)]
+pub enum DownloaderError {
+    /// The request couldn't be sent or the response body couldn't be read.
+    Http(reqwest_middleware::Error),
+    /// Writing the thumbnail to disk failed.
+    Io(io::Error),
+    /// The thumbnail couldn't be decoded or converted.
+    Image(Box<dyn Error + Send + Sync>),
+    /// The connection closed before the whole thumbnail arrived.
+    Incomplete {
+        received: u64,
+        expected: Option<u64>,
+    },
+    /// A blocking image task panicked or was cancelled.
+    Task(tokio::task::JoinError),
+    /// Downloading a video's thumbnail, fallbacks included, took longer than
+    /// `--per-download-timeout` and was cancelled. A later run will try again.
+    TimedOut(Duration),
+    /// The YouTube Data API refused the request because the daily quota is used up.
+    QuotaExceeded,
+    /// The YouTube Data API rejected the API key. Holds the API's message.
+    InvalidApiKey(String),
+    /// The YouTube Data API answered with an error status.
+    Api { status: u16, message: String },
+    /// The YouTube Data API answered with a body that doesn't have the expected shape.
+    /// `snippet` holds the start of the body.
+    MalformedResponse { message: String, snippet: String },
+    /// The channel doesn't exist or couldn't be resolved.
+    ChannelNotFound(String),
+    /// An argument or channel URL can't be used.
+    InvalidInput(String),
+    /// Fewer videos were listed than the channel's statistics report, so paging probably
+    /// broke off early. Only an error with `--strict`.
+    IncompleteListing { collected: usize, reported: u64 },
+}
+
+impl DownloaderError {
+    /// The process exit status that reports this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DownloaderError::QuotaExceeded => EXIT_QUOTA_EXCEEDED,
+            DownloaderError::ChannelNotFound(_) => EXIT_CHANNEL_NOT_FOUND,
+            DownloaderError::InvalidInput(_) | DownloaderError::InvalidApiKey(_) => {
+                EXIT_INVALID_INPUT
+            }
+            DownloaderError::Http(_)
+            | DownloaderError::Io(_)
+            | DownloaderError::Image(_)
+            | DownloaderError::Incomplete { .. }
+            | DownloaderError::Task(_)
+            | DownloaderError::TimedOut(_)
+            | DownloaderError::Api { .. }
+            | DownloaderError::MalformedResponse { .. }
+            | DownloaderError::IncompleteListing { .. } => EXIT_FAILURE,
+        }
+    }
+}
+
+impl DownloaderError {
+    /// Whether trying the same download again might succeed, e.g. after the connection
+    /// dropped halfway through, as opposed to failures that would just repeat, like a
+    /// redirect loop. Requests that couldn't be sent or got an error status aren't: the
+    /// client's retry middleware has already retried those.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DownloaderError::Http(e) => e.is_body() || e.is_decode(),
+            DownloaderError::Incomplete { .. } | DownloaderError::TimedOut(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The process exit status for an error that ended the run.
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    error
+        .downcast_ref::<DownloaderError>()
+        .map_or(EXIT_FAILURE, DownloaderError::exit_code)
+}
+
+impl fmt::Display for DownloaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloaderError::Http(e) => write!(f, "HTTP error: {}", e),
+            DownloaderError::Io(e) => write!(f, "I/O error: {}", e),
+            DownloaderError::Image(e) => write!(f, "Image error: {}", e),
+            DownloaderError::Incomplete { received, expected } => write!(
+                f,
+                "Incomplete download: received {} of {} bytes",
+                received,
+                expected.unwrap_or(0)
+            ),
+            DownloaderError::Task(e) => write!(f, "Task error: {}", e),
+            DownloaderError::TimedOut(limit) => {
+                write!(
+                    f,
+                    "Download cancelled after {} seconds",
+                    limit.as_secs_f64()
+                )
+            }
+            DownloaderError::QuotaExceeded => {
+                write!(f, "YouTube Data API quota exceeded; try again tomorrow")
+            }
+            DownloaderError::InvalidApiKey(message) => write!(
+                f,
+                "The YouTube Data API rejected the API key: {}. Check YOUTUBE_API_KEY and that \
+                 the YouTube Data API v3 is enabled in the key's Google Cloud project.",
+                message.trim_end_matches('.')
+            ),
+            DownloaderError::Api { status, message } => {
+                write!(f, "YouTube Data API error ({}): {}", status, message)
+            }
+            DownloaderError::MalformedResponse { message, snippet } => write!(
+                f,
+                "Unexpected response from the YouTube Data API ({}). The response began with: {}",
+                message, snippet
+            ),
+            DownloaderError::ChannelNotFound(message) | DownloaderError::InvalidInput(message) => {
+                write!(f, "{}", message)
+            }
+            DownloaderError::IncompleteListing {
+                collected,
+                reported,
+            } => write!(
+                f,
+                "Only {} videos were listed, but the channel reports {}; the list is probably \
+                 incomplete",
+                collected, reported
+            ),
+        }
+    }
+}
+
+impl Error for DownloaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DownloaderError::Http(e) => Some(e),
+            DownloaderError::Io(e) => Some(e),
+            DownloaderError::Image(e) => Some(e.as_ref()),
+            DownloaderError::Task(e) => Some(e),
+            DownloaderError::Incomplete { .. }
+            | DownloaderError::TimedOut(_)
+            | DownloaderError::QuotaExceeded
+            | DownloaderError::InvalidApiKey(_)
+            | DownloaderError::Api { .. }
+            | DownloaderError::MalformedResponse { .. }
+            | DownloaderError::ChannelNotFound(_)
+            | DownloaderError::InvalidInput(_)
+            | DownloaderError::IncompleteListing { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloaderError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloaderError::Http(e.into())
+    }
+}
+
+impl From<reqwest_middleware::Error> for DownloaderError {
+    fn from(e: reqwest_middleware::Error) -> Self {
+        DownloaderError::Http(e)
+    }
+}
+
+impl From<io::Error> for DownloaderError {
+    fn from(e: io::Error) -> Self {
+        DownloaderError::Io(e)
+    }
+}
+
+impl From<tokio::task::JoinError> for DownloaderError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        DownloaderError::Task(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incomplete_message() {
+        let error = DownloaderError::Incomplete {
+            received: 3,
+            expected: Some(10),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Incomplete download: received 3 of 10 bytes"
+        );
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn test_io_error_is_the_source() {
+        let error = DownloaderError::from(io::Error::other("disk full"));
+        assert_eq!(error.to_string(), "I/O error: disk full");
+        assert_eq!(error.source().unwrap().to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            DownloaderError::QuotaExceeded.exit_code(),
+            EXIT_QUOTA_EXCEEDED
+        );
+        assert_eq!(
+            DownloaderError::ChannelNotFound("gone".to_string()).exit_code(),
+            EXIT_CHANNEL_NOT_FOUND
+        );
+        assert_eq!(
+            DownloaderError::InvalidInput("bad".to_string()).exit_code(),
+            EXIT_INVALID_INPUT
+        );
+        assert_eq!(
+            DownloaderError::InvalidApiKey("API key not valid.".to_string()).exit_code(),
+            EXIT_INVALID_INPUT
+        );
+        assert_eq!(
+            DownloaderError::Api {
+                status: 500,
+                message: "oops".to_string()
+            }
+            .exit_code(),
+            EXIT_FAILURE
+        );
+        assert_eq!(
+            DownloaderError::from(io::Error::other("disk full")).exit_code(),
+            EXIT_FAILURE
+        );
+    }
+
+    #[test]
+    fn test_exit_code_of_boxed_errors() {
+        let quota: Box<dyn Error> = Box::new(DownloaderError::QuotaExceeded);
+        assert_eq!(exit_code(quota.as_ref()), EXIT_QUOTA_EXCEEDED);
+        let other: Box<dyn Error> = "something else".into();
+        assert_eq!(exit_code(other.as_ref()), EXIT_FAILURE);
+    }
+}
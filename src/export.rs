@@ -0,0 +1,210 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The file format of a video list export.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// The file extension used for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// A single row of a video list export. Fields missing from an export written by another
+/// version are read as empty.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VideoRecord {
+    pub video_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub published_at: String,
+    #[serde(default)]
+    pub thumbnail_url: String,
+    /// Absent if the video's details couldn't be fetched.
+    pub duration_seconds: Option<u64>,
+    /// Absent if hidden by the uploader.
+    pub view_count: Option<u64>,
+    /// Absent if hidden by the uploader.
+    pub like_count: Option<u64>,
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Renders the records as CSV with a header row.
+pub fn to_csv(records: &[VideoRecord]) -> String {
+    let mut csv = String::from(
+        "video_id,title,published_at,thumbnail_url,duration_seconds,view_count,like_count\n",
+    );
+    let number = |number: Option<u64>| number.map(|n| n.to_string()).unwrap_or_default();
+    for record in records {
+        let fields = [
+            &record.video_id,
+            &record.title,
+            &record.published_at,
+            &record.thumbnail_url,
+            &number(record.duration_seconds),
+            &number(record.view_count),
+            &number(record.like_count),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| escape_csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Renders the records in the requested format.
+pub fn render(records: &[VideoRecord], format: ExportFormat) -> Result<String, serde_json::Error> {
+    match format {
+        ExportFormat::Csv => Ok(to_csv(records)),
+        ExportFormat::Json => serde_json::to_string_pretty(records),
+    }
+}
+
+/// Reads the records of a JSON export for `--merge-export`. Entries without a video ID,
+/// e.g. from a different kind of file, are left out; returns them as the second element.
+pub fn parse_json(contents: &str) -> Result<(Vec<VideoRecord>, usize), serde_json::Error> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(contents)?;
+    let total = entries.len();
+    let records: Vec<VideoRecord> = entries
+        .into_iter()
+        .filter_map(|entry| serde_json::from_value(entry).ok())
+        .collect();
+    let skipped = total - records.len();
+    Ok((records, skipped))
+}
+
+/// Merges this run's `records` into an `existing` export: they come first, replacing the
+/// existing records of the same videos, followed by the rest of the existing ones.
+pub fn merge(existing: Vec<VideoRecord>, records: &[VideoRecord]) -> Vec<VideoRecord> {
+    let current: HashSet<&str> = records
+        .iter()
+        .map(|record| record.video_id.as_str())
+        .collect();
+    let kept: Vec<VideoRecord> = existing
+        .into_iter()
+        .filter(|record| !current.contains(record.video_id.as_str()))
+        .collect();
+    records.iter().cloned().chain(kept).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<VideoRecord> {
+        vec![
+            VideoRecord {
+                video_id: "video1".to_string(),
+                title: "Plain title".to_string(),
+                published_at: "2024-03-15T10:00:00Z".to_string(),
+                thumbnail_url: "https://i.ytimg.com/vi/video1/maxresdefault.jpg".to_string(),
+                duration_seconds: Some(253),
+                view_count: Some(1500),
+                like_count: Some(42),
+            },
+            VideoRecord {
+                video_id: "video2".to_string(),
+                title: "Tips, tricks and \"quotes\"".to_string(),
+                published_at: "2024-03-16T10:00:00Z".to_string(),
+                thumbnail_url: "https://i.ytimg.com/vi/video2/hqdefault.jpg".to_string(),
+                duration_seconds: Some(61),
+                view_count: None,
+                like_count: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let csv = render(&records(), ExportFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "video_id,title,published_at,thumbnail_url,duration_seconds,view_count,like_count\n\
+             video1,Plain title,2024-03-15T10:00:00Z,https://i.ytimg.com/vi/video1/maxresdefault.jpg,253,1500,42\n\
+             video2,\"Tips, tricks and \"\"quotes\"\"\",2024-03-16T10:00:00Z,https://i.ytimg.com/vi/video2/hqdefault.jpg,61,,\n"
+        );
+    }
+
+    #[test]
+    fn test_json_export() {
+        let json = render(&records(), ExportFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {
+                    "video_id": "video1",
+                    "title": "Plain title",
+                    "published_at": "2024-03-15T10:00:00Z",
+                    "thumbnail_url": "https://i.ytimg.com/vi/video1/maxresdefault.jpg",
+                    "duration_seconds": 253,
+                    "view_count": 1500,
+                    "like_count": 42
+                },
+                {
+                    "video_id": "video2",
+                    "title": "Tips, tricks and \"quotes\"",
+                    "published_at": "2024-03-16T10:00:00Z",
+                    "thumbnail_url": "https://i.ytimg.com/vi/video2/hqdefault.jpg",
+                    "duration_seconds": 61,
+                    "view_count": null,
+                    "like_count": null
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_json_tolerates_other_versions() {
+        let contents = r#"[
+            {"video_id": "video1", "title": "Old title", "published_at": "2024-03-15T10:00:00Z",
+             "thumbnail_url": "https://i.ytimg.com/vi/video1/hqdefault.jpg"},
+            {"video_id": "video2", "title": "Newer", "chapters": []},
+            {"id": "video3"}
+        ]"#;
+
+        let (parsed, skipped) = parse_json(contents).unwrap();
+
+        assert_eq!(skipped, 1);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].duration_seconds, None);
+        assert_eq!(parsed[1].title, "Newer");
+        assert_eq!(parsed[1].published_at, "");
+        assert!(parse_json("{\"video_id\": \"video1\"}").is_err());
+    }
+
+    #[test]
+    fn test_merge_replaces_existing_records() {
+        let [first, second] = <[VideoRecord; 2]>::try_from(records()).unwrap();
+        let updated = VideoRecord {
+            title: "Renamed".to_string(),
+            ..second.clone()
+        };
+        let new = VideoRecord {
+            video_id: "video3".to_string(),
+            ..first.clone()
+        };
+
+        let merged = merge(vec![first.clone(), second], &[new.clone(), updated.clone()]);
+
+        assert_eq!(merged, [new, updated, first]);
+    }
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// Environment variable holding the path of the downloaded thumbnail.
+pub const PATH_ENV: &str = "YOUTUBE_IMAGES_PATH";
+/// Environment variable holding the video ID of the downloaded thumbnail.
+pub const VIDEO_ID_ENV: &str = "YOUTUBE_IMAGES_VIDEO_ID";
+
+/// A command run after each successful download for `--on-download`. At most
+/// `concurrency` copies run at once, however many downloads finish together.
+#[derive(Debug)]
+pub struct DownloadHook {
+    command: String,
+    slots: Semaphore,
+}
+
+impl DownloadHook {
+    pub fn new(command: &str, concurrency: usize) -> Self {
+        DownloadHook {
+            command: command.to_string(),
+            slots: Semaphore::new(concurrency),
+        }
+    }
+
+    /// Runs the command through the shell with the thumbnail's path and video ID as its
+    /// positional parameters, `$1` and `$2`, and also set in `YOUTUBE_IMAGES_PATH` and
+    /// `YOUTUBE_IMAGES_VIDEO_ID`. Waits for it to exit.
+    pub async fn run(&self, path: &Path, video_id: &str) -> io::Result<ExitStatus> {
+        let _slot = self.slots.acquire().await.map_err(io::Error::other)?;
+        shell_command(&self.command)
+            .arg(path)
+            .arg(video_id)
+            .env(PATH_ENV, path)
+            .env(VIDEO_ID_ENV, video_id)
+            .stdin(Stdio::null())
+            .status()
+            .await
+    }
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    // The command is run as written; the arguments after the script name become $1, $2...
+    shell.arg("-c").arg(command).arg("youtube-images");
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_hook_gets_path_and_video_id() {
+        let temp_dir = tempdir().unwrap();
+        let log = temp_dir.path().join("log");
+        let hook = DownloadHook::new(
+            &format!(
+                "printf '%s|%s|%s\\n' \"$YOUTUBE_IMAGES_VIDEO_ID\" \"$1\" \"$2\" >> '{}'",
+                log.display()
+            ),
+            1,
+        );
+
+        let status = hook.run(Path::new("out dir/abc.jpg"), "abc").await.unwrap();
+
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "abc|out dir/abc.jpg|abc\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_runs_the_command_as_written() {
+        let temp_dir = tempdir().unwrap();
+        let log = temp_dir.path().join("log");
+        let hook = DownloadHook::new(
+            &format!(
+                "echo first > '{log}'; echo \"$2\" >> '{log}' # a comment",
+                log = log.display()
+            ),
+            1,
+        );
+
+        let status = hook.run(Path::new("abc.jpg"), "abc").await.unwrap();
+
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "first\nabc\n");
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+
+/// Name of the file, stored in the output directory, that holds the cache validators.
+pub const HTTP_CACHE_FILE: &str = ".thumbnail_cache.json";
+
+/// The validators a thumbnail was last served with.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CacheEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// ETag/Last-Modified validators for downloaded thumbnails, keyed by video ID.
+/// Shared between download tasks and persisted as JSON once all of them finish.
+#[derive(Debug)]
+pub struct HttpCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl HttpCache {
+    /// Loads the cache stored in `output_dir`, starting empty if it doesn't exist yet.
+    pub async fn load(output_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = output_dir.join(HTTP_CACHE_FILE);
+        let entries = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(HttpCache {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn get(&self, video_id: &str) -> Option<CacheEntry> {
+        self.entries.lock().unwrap().get(video_id).cloned()
+    }
+
+    pub fn insert(&self, video_id: &str, entry: CacheEntry) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(video_id.to_string(), entry);
+    }
+
+    pub fn remove(&self, video_id: &str) {
+        self.entries.lock().unwrap().remove(video_id);
+    }
+
+    /// Writes the cache back to disk.
+    pub async fn save(&self) -> Result<(), Box<dyn Error>> {
+        let contents = serde_json::to_string_pretty(&*self.entries.lock().unwrap())?;
+        fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+}
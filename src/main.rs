@@ -1,27 +1,1225 @@
-use clap::Parser;
-use reqwest::Client;
-use serde::Deserialize;
+mod adaptive;
+mod archive;
+mod backoff;
+mod budget;
+mod channel_cache;
+mod checksums;
+mod circuit_breaker;
+mod compress;
+mod config;
+#[cfg(feature = "image-processing")]
+mod contact_sheet;
+mod convert;
+mod credentials;
+mod dedup;
+mod embed;
+mod error;
+mod export;
+mod hook;
+mod http_cache;
+mod metadata;
+mod metrics;
+mod middleware;
+mod paths;
+mod picker;
+mod placeholder;
+#[macro_use]
+mod progress;
+mod prune;
+mod quota;
+mod rate_limit;
+mod resolution_report;
+mod shuffle;
+mod sink;
+mod source;
+mod state;
+mod storyboard;
+mod thumbnail;
+mod title_filter;
+mod video_ids;
+mod video_range;
+
+use adaptive::AdaptiveConcurrency;
+use archive::{Archive, ArchiveFormat};
+use backoff::{Backoff, Jitter};
+use budget::ByteBudget;
+use channel_cache::ChannelCache;
+use checksums::Checksums;
+use circuit_breaker::CircuitBreaker;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use convert::{OutputFormat, ResizeMode, Size};
+use credentials::Credentials;
+use dedup::{ContentHash, DedupIndex};
+use embed::EmbeddedMetadata;
+use error::DownloaderError;
+use export::{ExportFormat, VideoRecord};
+use futures::StreamExt;
+use hook::DownloadHook;
+use http_cache::{CacheEntry, HttpCache};
+use metadata::VideoMetadata;
+use middleware::{Client, RequestLogger};
+use paths::OutputLocation;
+use picker::{ChannelCandidate, ChannelPicker, TerminalPicker};
+use placeholder::PlaceholderSignature;
+use progress::{EventSink, ProgressCallback, ProgressEvent};
+use rate_limit::RateLimiter;
+use regex::Regex;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
+use reqwest_retry::RetryTransientMiddleware;
+use resolution_report::{ResolutionReport, ResolutionRow};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sink::{S3Location, Sink};
+use source::{ChannelSource, ScrapingSource, Uploads};
+use state::DownloadState;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::error::Error;
-use std::path::Path;
-use tokio::fs::{self, File};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thumbnail::Resolution;
+use title_filter::TitleFilter;
+use tokio::fs::{self, File, OpenOptions};
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::Instrument;
+use video_range::VideoRange;
+
+/// Describes the process exit statuses in `--help`.
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  All thumbnails were downloaded or skipped
+  1  Any other error
+  2  Some thumbnails or channels failed, files failed `verify`, or --max-duration ran out
+  3  The YouTube Data API quota is exhausted
+  4  The channel could not be found
+  5  Invalid arguments, channel URL or API key";
 
 /// A tool to download all video cover images from a YouTube channel.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = EXIT_CODES_HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Read settings from this TOML file, with one key per flag, e.g. `concurrency = 8` or
+    /// `skip_shorts = true`. Flags given on the command line take precedence.
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
     /// The URL of the YouTube channel (e.g., https://www.youtube.com/@handle).
+    #[arg(short, long, required_unless_present_any = ["channel_id", "input_file", "video_ids_file"])]
+    channel_url: Option<String>,
+
+    /// The ID of the channel (e.g., UCuAXFkgsw1L7xaCfnd5JJOw), to skip resolving a channel URL.
+    #[arg(long, value_parser = parse_channel_id, conflicts_with_all = ["channel_url", "input_file"])]
+    channel_id: Option<String>,
+
+    /// A file with one channel URL per line. Blank lines and lines starting with '#' are ignored.
+    /// Each channel is downloaded into its own subdirectory of the output directory.
+    #[arg(short, long, conflicts_with = "channel_url")]
+    input_file: Option<String>,
+
+    /// How the subdirectory of each channel in --input-file is named.
+    #[arg(long, value_enum, default_value_t = DirName::Handle, requires = "input_file")]
+    dir_name: DirName,
+
+    /// Download the thumbnails of the video IDs in this file (one per line, or a CSV written by
+    /// --export csv) without calling the Data API, so no API key is needed.
+    #[arg(long, conflicts_with_all = ["channel_url", "channel_id", "input_file", "playlist_name", "export", "skip_shorts", "only_shorts", "with_metadata", "title_contains", "title_regex"])]
+    video_ids_file: Option<String>,
+
+    /// When a handle or custom URL could refer to several channels, list them and ask which
+    /// one to use instead of picking automatically. Only takes effect on a terminal.
+    #[arg(long)]
+    interactive: bool,
+
+    /// Download the channel's playlist with this title (case-insensitive) instead of all uploads.
+    #[arg(long)]
+    playlist_name: Option<String>,
+
+    /// The directory where the images will be saved. Defaults to the YOUTUBE_IMAGE_OUTPUT_DIR
+    /// environment variable, or ./thumbnails if that isn't set.
     #[arg(short, long)]
-    channel_url: String,
+    output_dir: Option<String>,
+
+    /// Only try the largest variant (or the one given by --resolution) at first, and retry
+    /// videos that don't have it yet once more at the end of the run, after waiting this many
+    /// seconds, before falling back to smaller ones. Catches freshly uploaded videos whose
+    /// maxres thumbnail hasn't been generated yet.
+    #[arg(long, value_name = "SECONDS")]
+    retry_404_later: Option<u64>,
+
+    /// Download exactly this thumbnail variant, failing videos that don't have it, instead of
+    /// the largest one available. Useful for getting uniform image sizes.
+    #[arg(long, value_enum)]
+    resolution: Option<Resolution>,
+
+    /// When no JPEG thumbnail exists, try the WebP versions some live streams and premieres
+    /// only have, saving them as .webp (or converting them to --format png).
+    #[arg(long)]
+    try_webp: bool,
+
+    /// Download every thumbnail variant a video has rather than only the largest, saved as
+    /// {id}/maxresdefault.jpg, {id}/hqdefault.jpg and so on (or {id}_maxresdefault.jpg with
+    /// --flatten).
+    #[arg(long, conflicts_with_all = ["resolution", "try_webp", "retry_404_later", "only_missing", "prune", "archive", "output_s3", "on_download", "resolutions_report", "contact_sheet"])]
+    all_resolutions: bool,
+
+    /// Name the variants --all-resolutions saves after this template instead, e.g.
+    /// "{id}_{res}" for abc_maxres.jpg and abc_hq.jpg side by side. {res} is maxres, sd, hq,
+    /// mq or default, as for --resolution.
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_variant_template, requires = "all_resolutions")]
+    variant_template: Option<String>,
+
+    /// Base URL thumbnails are downloaded from, e.g. https://i.ytimg.com or a mirror serving the
+    /// same /vi/{id}/{variant}.jpg paths.
+    #[arg(long, default_value = THUMBNAIL_BASE_URL)]
+    thumbnail_base_url: String,
+
+    /// Base URL of the YouTube Data API, e.g. a mirror or local caching proxy serving the
+    /// same /youtube/v3/... paths.
+    #[arg(long, default_value = API_BASE_URL)]
+    api_base_url: String,
+
+    /// Also download each video's storyboard: the sheets of frames YouTube shows while
+    /// scrubbing, saved at the largest size offered as {id}.storyboard0.jpg,
+    /// {id}.storyboard1.jpg and so on. Experimental and best-effort: storyboards aren't part
+    /// of the Data API, so they're found by reading each video's watch page, which can change
+    /// at any time. Only fetched along with newly downloaded thumbnails.
+    #[arg(long)]
+    storyboards: bool,
+
+    /// Base URL watch pages are read from for --storyboards, and channel pages for --scrape.
+    #[arg(long, default_value = WATCH_BASE_URL)]
+    watch_base_url: String,
+
+    /// List each channel's videos by reading its YouTube pages instead of asking the Data
+    /// API, so no API key is needed. Only finds the latest videos, about 30, and breaks
+    /// whenever YouTube changes its pages. Used when neither an API key nor an access token
+    /// is set.
+    #[arg(long)]
+    scrape: bool,
+
+    /// Skip YouTube Shorts (videos shorter than 60 seconds).
+    #[arg(long, conflicts_with = "only_shorts")]
+    skip_shorts: bool,
+
+    /// Skip age-restricted videos instead of trying to download their thumbnails. Either
+    /// way they're counted separately from other failures.
+    #[arg(long)]
+    skip_restricted: bool,
+
+    /// Only download thumbnails of YouTube Shorts (videos shorter than 60 seconds).
+    #[arg(long)]
+    only_shorts: bool,
+
+    /// Only download videos whose title contains this text, ignoring case.
+    #[arg(long)]
+    title_contains: Option<String>,
+
+    /// Only download videos whose title matches this regular expression.
+    #[arg(long, value_parser = Regex::new)]
+    title_regex: Option<Regex>,
+
+    /// Download at most this many videos: the newest ones, the oldest ones with
+    /// --order oldest, the first ones by --sort-by, or a random sample with --shuffle.
+    /// Videos already in the state file don't count.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Which end of the channel's history to download first, and keep with --limit.
+    #[arg(long, value_enum, default_value_t = Order::Newest, conflicts_with = "shuffle")]
+    order: Order,
+
+    /// Sort the videos before applying --limit and downloading. Videos that tie, or whose
+    /// views are hidden, keep the channel's order.
+    #[arg(long, value_enum, conflicts_with_all = ["order", "shuffle", "video_ids_file"])]
+    sort_by: Option<SortBy>,
+
+    /// Only download the videos at these positions of the list, after --order, --sort-by and
+    /// --shuffle, counting from 0: 100..200 (or 100..=199), 500.. or ..50. Lets several
+    /// machines share a large channel.
+    #[arg(long, value_name = "START..END")]
+    range: Option<VideoRange>,
+
+    /// Cut a --range that reaches past the end of the list down to the videos there are,
+    /// instead of failing.
+    #[arg(long, requires = "range")]
+    clamp: bool,
+
+    /// Compare the number of videos listed with the count in the channel's statistics, and
+    /// warn if the list looks incomplete, e.g. because paging broke off. Costs one more API
+    /// request per channel.
+    #[arg(long, conflicts_with_all = ["playlist_name", "video_ids_file"])]
+    verify_count: bool,
+
+    /// Fail instead of warning when --verify-count finds the list incomplete.
+    #[arg(long, requires = "verify_count")]
+    strict: bool,
+
+    /// Randomly reorder the videos before applying --limit and downloading.
+    #[arg(long)]
+    shuffle: bool,
+
+    /// The seed for --shuffle, to get the same order again. A random seed is printed otherwise.
+    #[arg(long, requires = "shuffle")]
+    seed: Option<u64>,
+
+    /// The image format to save thumbnails in. Anything other than jpg is re-encoded.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Jpg)]
+    format: OutputFormat,
+
+    /// The encoding quality (0-100) used for WebP output.
+    #[arg(long, default_value_t = 80, value_parser = clap::value_parser!(u8).range(0..=100))]
+    quality: u8,
+
+    /// After downloading, tile all thumbnails into a single grid image saved as a PNG at this path.
+    #[arg(long)]
+    contact_sheet: Option<String>,
+
+    /// The number of columns in the contact sheet.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    contact_sheet_columns: u32,
+
+    /// The width in pixels of each contact sheet cell. Cells are 16:9.
+    #[arg(long, default_value_t = 320, value_parser = clap::value_parser!(u32).range(16..))]
+    contact_sheet_cell_width: u32,
+
+    /// Remember each thumbnail's ETag/Last-Modified and send conditional requests on later runs,
+    /// skipping thumbnails that haven't changed.
+    #[arg(long)]
+    conditional: bool,
+
+    /// What to do when a thumbnail already exists in the output directory.
+    #[arg(long, value_enum, default_value_t = Overwrite::Always)]
+    overwrite: Overwrite,
+
+    /// Keep an existing thumbnail that was modified more recently than YouTube's copy,
+    /// going by its Last-Modified header, e.g. one replaced by hand.
+    #[arg(long)]
+    no_overwrite_newer: bool,
+
+    /// Sync each thumbnail, the state file and the checksum file to disk before moving on, so
+    /// they survive a power loss. Slows downloads down.
+    #[arg(long)]
+    fsync: bool,
+
+    /// Run this shell command after each thumbnail is downloaded, with the file's path and
+    /// video ID in $1 and $2 and in YOUTUBE_IMAGES_PATH and YOUTUBE_IMAGES_VIDEO_ID. At most
+    /// --concurrency commands (or one per CPU) run at once.
+    #[arg(long, value_name = "COMMAND")]
+    on_download: Option<String>,
+
+    /// Remove the thumbnails of videos that are no longer in the channel, moving them to a
+    /// .trash subdirectory of the output directory.
+    #[arg(long, conflicts_with_all = ["video_ids_file", "playlist_name", "dir_template", "export"])]
+    prune: bool,
+
+    /// Delete pruned thumbnails instead of moving them to .trash.
+    #[arg(long, requires = "prune")]
+    prune_hard: bool,
+
+    /// Discard thumbnails narrower than this many pixels, trying the next smaller
+    /// resolution instead.
+    #[arg(long)]
+    min_width: Option<u32>,
+
+    /// Discard thumbnails shorter than this many pixels, trying the next smaller
+    /// resolution instead.
+    #[arg(long)]
+    min_height: Option<u32>,
+
+    /// Treat images matching this signature as YouTube's "no thumbnail" placeholder and
+    /// try the next smaller resolution instead: `sha256:<hash>` for an exact image, or
+    /// `<width>x<height>` for any image of that size served for a variant that should be
+    /// another size. Repeat for several; given ones replace the default. Sizes are only
+    /// checked in builds with the image-processing feature.
+    #[arg(long = "placeholder", value_name = "SIGNATURE", default_value = placeholder::DEFAULT_PLACEHOLDER)]
+    placeholders: Vec<PlaceholderSignature>,
+
+    /// Keep placeholder images instead of discarding them.
+    #[arg(long)]
+    keep_placeholders: bool,
+
+    /// How many hours a channel ID resolved from a URL is remembered in channel_cache.json
+    /// in the output directory.
+    #[arg(long, value_name = "HOURS", default_value_t = 168)]
+    channel_cache_ttl: u64,
+
+    /// Don't read or write the channel ID cache.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Limit the combined download throughput of all concurrent downloads, in bytes per second.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_rate: Option<u64>,
+
+    /// Export the video list (ID, title, publish date, thumbnail URL, duration in seconds,
+    /// view and like counts) instead of downloading any thumbnails.
+    #[arg(long, value_enum)]
+    export: Option<ExportFormat>,
+
+    /// How many playlist items to request per page of the API, from 1 to 50. Smaller pages
+    /// cost more quota; mainly useful for testing pagination.
+    #[arg(long, default_value_t = MAX_PAGE_SIZE, value_parser = clap::value_parser!(u8).range(1..=MAX_PAGE_SIZE as i64))]
+    page_size: u8,
+
+    /// Stop listing a playlist after this many pages, warning that the list may be cut
+    /// short. A safety valve against pagination that never ends; unlimited by default.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_pages: Option<u32>,
+
+    /// Only count each channel's uploads and print how many there are and when the oldest
+    /// and newest were published, without creating the output directory or downloading
+    /// anything.
+    #[arg(long, visible_alias = "count", conflicts_with_all = ["video_ids_file", "export", "watch", "contact_sheet"])]
+    stats_only: bool,
+
+    /// Only estimate the Data API quota units a run with these flags would use, per channel
+    /// and in total. Looking up each channel's video count costs 1 unit.
+    #[arg(long, conflicts_with_all = ["video_ids_file", "watch", "stats_only"])]
+    estimate_quota: bool,
+
+    /// The file to write the export to. Defaults to videos.csv/videos.json in the output directory.
+    #[arg(long, requires = "export")]
+    export_file: Option<String>,
+
+    /// Add this run's videos to the export file instead of replacing it, so repeated runs
+    /// build up one list. A video already in the file gets this run's record. Only for
+    /// --export json.
+    #[arg(long, requires = "export")]
+    merge_export: bool,
+
+    /// Also add every thumbnail to a single tar or zip archive, as it's downloaded. Entries
+    /// are named by their path in the output directory.
+    #[arg(long, value_enum, conflicts_with_all = ["export", "watch"])]
+    archive: Option<ArchiveFormat>,
+
+    /// Where to write the --archive. Defaults to thumbnails.tar/thumbnails.zip in the output
+    /// directory.
+    #[arg(long, value_name = "PATH", requires = "archive")]
+    archive_file: Option<String>,
+
+    /// Remove each thumbnail once it's in the --archive, so no loose files are left.
+    #[arg(long, requires = "archive", conflicts_with_all = ["contact_sheet", "checksums", "dedup"])]
+    archive_only: bool,
 
-    /// The directory where the images will be saved.
+    /// Also upload every downloaded thumbnail to an S3-compatible bucket, keyed by the prefix
+    /// followed by its path in the output directory. Credentials and the region come from
+    /// the usual AWS_* environment variables; AWS_ENDPOINT_URL selects a service like MinIO.
+    /// Needs the s3 feature.
+    #[arg(long, value_name = "s3://BUCKET/PREFIX", conflicts_with_all = ["export", "archive_only"])]
+    output_s3: Option<S3Location>,
+
+    /// Write a {video_id}.json sidecar with the video's title, description, publish date,
+    /// channel title and thumbnail URL next to each thumbnail.
+    #[arg(long)]
+    with_metadata: bool,
+
+    /// Write each video's ID and title into its thumbnail's EXIF data, and the URL it was
+    /// downloaded from into a JPEG comment, so they travel with the image. Only applies to
+    /// JPEG output. Needs the image-processing feature.
+    #[arg(long, conflicts_with = "dedup")]
+    embed_metadata: bool,
+
+    /// Resize every thumbnail to this size once it's downloaded, e.g. 640x360, so they all
+    /// have the same dimensions. --resize-mode says how the aspect ratio is dealt with.
+    /// Needs the image-processing feature.
+    #[arg(long, alias = "normalize", value_name = "WIDTHxHEIGHT")]
+    resize: Option<Size>,
+
+    /// How --resize fits thumbnails into the new size.
+    #[arg(long, value_enum, default_value_t = ResizeMode::Fit, requires = "resize")]
+    resize_mode: ResizeMode,
+
+    /// Gzip every thumbnail and metadata sidecar as it's saved, as {id}.jpg.gz and
+    /// {id}.json.gz, for cold storage. JPEGs are compressed already, so they shrink little;
+    /// sidecars shrink a lot. Nothing else reads the files back, so decompress them with
+    /// gunzip or zcat before use.
+    #[arg(long, alias = "compress-output", conflicts_with_all = ["checksums", "contact_sheet"])]
+    compress: bool,
+
+    /// Delete leftover .part files from interrupted downloads before starting, instead of
+    /// resuming them.
+    #[arg(long)]
+    clean_partial: bool,
+
+    /// Check which of the channel's thumbnails are already in the output directory and only
+    /// download the missing ones. Unlike --state-file, this works from the files themselves.
+    #[arg(long)]
+    only_missing: bool,
+
+    /// Organize thumbnails into subdirectories based on each video's upload date, e.g.
+    /// "{year}/{month}". Supports {year}, {month}, {day} and {channel}.
+    #[arg(long)]
+    dir_template: Option<String>,
+
+    /// Keep every file directly in the output directory. The subdirectories that --dir-template
+    /// and --input-file would create become file name prefixes instead, e.g. 2024_03_{id}.jpg.
+    #[arg(long, overrides_with = "no_flatten")]
+    flatten: bool,
+
+    /// Create subdirectories for --dir-template and --input-file (the default). Overrides an
+    /// earlier --flatten.
+    #[arg(long, overrides_with = "flatten")]
+    no_flatten: bool,
+
+    /// Store thumbnails that are byte-for-byte identical to one already saved during this run
+    /// as a symlink to it (or a .dup file pointing at it where symlinks aren't available).
+    #[arg(long)]
+    dedup: bool,
+
+    /// Keep a SHA256SUMS file in the output directory with the hash of every downloaded
+    /// thumbnail, so the archive can be checked later with `sha256sum -c SHA256SUMS`.
+    #[arg(long)]
+    checksums: bool,
+
+    /// When the run finishes, write its downloads, failures, bytes, duration and estimated
+    /// quota use to this file in the Prometheus text format, e.g. for node_exporter's
+    /// textfile collector.
+    #[arg(long, value_name = "PATH", conflicts_with = "watch")]
+    metrics_file: Option<String>,
+
+    /// When the run finishes, write a CSV to this file listing the thumbnail variant each
+    /// video ended up with: video_id, chosen_resolution, width, height and bytes. Widths and
+    /// heights need the image-processing feature.
+    #[arg(long, value_name = "PATH", conflicts_with = "export")]
+    resolutions_report: Option<String>,
+
+    /// A JSON file recording which videos have already been downloaded. Those videos are
+    /// skipped, so later runs only fetch thumbnails of new uploads.
+    #[arg(long)]
+    state_file: Option<String>,
+
+    /// Keep running, checking for new videos every N minutes. Uses the state file (by default
+    /// .download_state.json in the output directory) to only download new thumbnails.
+    #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["export", "contact_sheet"])]
+    watch: Option<u64>,
+
+    /// Print progress as JSON lines, one object per event, instead of human-readable messages.
+    #[arg(long)]
+    json: bool,
+
+    /// Print nothing but errors, which go to stderr; the exit status tells whether the run
+    /// succeeded. With --json, only the summary event is printed.
     #[arg(short, long)]
-    output_dir: String,
+    quiet: bool,
+
+    /// Don't color messages. Colors are only used on a terminal anyway, and not when the
+    /// NO_COLOR environment variable is set.
+    #[arg(long)]
+    no_color: bool,
+
+    /// The User-Agent header sent with every request.
+    #[arg(long, default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+
+    /// Send an extra header with every request, e.g. "Proxy-Authorization: Basic ...".
+    /// Can be given several times. Overrides a default header of the same name.
+    #[arg(long, value_name = "NAME: VALUE", value_parser = parse_header)]
+    header: Vec<(HeaderName, HeaderValue)>,
+
+    /// Authorize Data API requests with this OAuth 2.0 access token instead of the API key,
+    /// e.g. to reach the private uploads of your own channel. Defaults to
+    /// YOUTUBE_ACCESS_TOKEN.
+    #[arg(long, value_name = "TOKEN")]
+    access_token: Option<String>,
+
+    /// Download at most this many thumbnails at once. Unlimited by default.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: Option<u64>,
+
+    /// Tune how many thumbnails are downloaded at once as the run goes: start at
+    /// --min-concurrency and download one more at a time while requests keep succeeding
+    /// quickly, halving the number when they fail or slow down.
+    #[arg(long, conflicts_with = "concurrency")]
+    adaptive_concurrency: bool,
+
+    /// The fewest thumbnails --adaptive-concurrency downloads at once.
+    #[arg(
+        long,
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "adaptive_concurrency"
+    )]
+    min_concurrency: u64,
+
+    /// The most thumbnails --adaptive-concurrency downloads at once.
+    #[arg(
+        long,
+        default_value_t = 32,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "adaptive_concurrency"
+    )]
+    max_concurrency: u64,
+
+    /// Make at most this many YouTube Data API requests at once, e.g. when fetching video
+    /// details in batches. Independent of --concurrency, since API calls cost quota.
+    #[arg(long, default_value_t = DEFAULT_API_CONCURRENCY, value_parser = clap::value_parser!(u64).range(1..))]
+    api_concurrency: u64,
+
+    /// Retry a request that failed because of the network or got a 429 or 5xx answer this
+    /// many times, waiting exponentially longer before each attempt. Applies to API and
+    /// thumbnail requests alike; a thumbnail download cut off halfway is retried as well.
+    #[arg(long, default_value_t = 0)]
+    retries: u32,
+
+    /// Randomize the wait between retries, so downloads that failed together don't all
+    /// retry at once.
+    #[arg(long, value_enum, default_value_t = Jitter::Full)]
+    jitter: Jitter,
+
+    /// Pause all downloads for --cooldown seconds when more than this percentage of the
+    /// last 20 thumbnail requests failed, e.g. because the server started blocking them.
+    /// A single request then checks whether it has recovered.
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    failure_threshold: Option<u8>,
+
+    /// How many seconds to pause downloads for when --failure-threshold is reached.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 60,
+        requires = "failure_threshold"
+    )]
+    cooldown: u64,
+
+    /// How many idle connections per host to keep open for reuse between requests.
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u64).range(1..))]
+    pool_size: u64,
+
+    /// Send TCP keepalive probes on idle connections after this many seconds, so pooled
+    /// connections dropped by a router are noticed. 0 turns keepalive off.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    tcp_keepalive: u64,
+
+    /// Follow at most this many redirects per request. 0 doesn't follow any.
+    #[arg(long, default_value_t = 5)]
+    max_redirects: usize,
+
+    /// Cancel a video's download, including falling back to smaller thumbnails, if it takes
+    /// longer than this many seconds. The video counts as failed and is retried on the next run.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    per_download_timeout: Option<u64>,
+
+    /// Stop starting new downloads once this many bytes have been written. Downloads already
+    /// in progress still finish, so the total may end up slightly higher.
+    #[arg(long, value_name = "BYTES")]
+    max_total_size: Option<u64>,
+
+    /// Stop starting new downloads once the run has taken this many seconds, so it fits a
+    /// cron window. Downloads already in progress still finish, and the state file is saved
+    /// so the next run picks up the rest.
+    #[arg(
+        long,
+        alias = "timeout-overall",
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "watch"
+    )]
+    max_duration: Option<u64>,
+}
+
+/// How existing thumbnails are treated when downloading again.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Overwrite {
+    /// Always download and replace the existing file.
+    #[default]
+    Always,
+    /// Keep existing files without contacting the server.
+    Never,
+    /// Send a HEAD request and only download if the remote Content-Length or ETag differs
+    /// from the existing file. Sizes are only compared for the jpg format.
+    IfDifferent,
+}
+
+/// How a channel's subdirectory is named when downloading several channels.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum DirName {
+    /// The channel ID, e.g. UCxxxx.
+    Id,
+    /// The channel's title followed by its ID, e.g. "My Channel (UCxxxx)". The ID keeps
+    /// channels with the same title apart.
+    Title,
+    /// The handle for /@handle URLs, otherwise the channel ID.
+    #[default]
+    Handle,
+}
+
+/// The order videos are downloaded in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Order {
+    /// Most recent uploads first, the order the uploads playlist lists them in.
+    #[default]
+    Newest,
+    /// Earliest uploads first.
+    Oldest,
+}
+
+/// A key to sort videos by for `--sort-by`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortBy {
+    /// Most recent uploads first.
+    Date,
+    /// Most viewed first.
+    Views,
+    /// Longest first.
+    Duration,
+    /// Alphabetically by title, ignoring case.
+    Title,
+}
+
+/// Settings controlling how each thumbnail is saved.
+#[derive(Debug, Clone, Default)]
+struct DownloadOptions {
+    format: OutputFormat,
+    /// The format the CDN serves the thumbnail in: JPEG, except for the `--try-webp` fallback.
+    source_format: OutputFormat,
+    quality: u8,
+    overwrite: Overwrite,
+    no_overwrite_newer: bool,
+    /// Sync every file to disk before it's renamed into place.
+    fsync: bool,
+    resolution: Option<Resolution>,
+    try_webp: bool,
+    /// Save every variant rather than only the largest, for `--all-resolutions`.
+    all_resolutions: bool,
+    /// How `--all-resolutions` names each variant, for `--variant-template`.
+    variant_template: Option<String>,
+    min_width: Option<u32>,
+    min_height: Option<u32>,
+    /// The size every thumbnail is resized to, for `--resize`.
+    resize: Option<Size>,
+    resize_mode: ResizeMode,
+    /// Gzip thumbnails and sidecars as they're saved, for `--compress`.
+    compress: bool,
+    /// Signatures of placeholder images to discard; empty to keep every image.
+    placeholders: Vec<PlaceholderSignature>,
+    cache: Option<Arc<HttpCache>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    dedup: Option<Arc<DedupIndex>>,
+    checksums: Option<Arc<Checksums>>,
+    state: Option<Arc<DownloadState>>,
+    channel_cache: Option<Arc<ChannelCache>>,
+    /// Receives every resolution, download, skip and failure; `--json` prints them.
+    progress: Option<Arc<ProgressCallback>>,
+    on_download: Option<Arc<DownloadHook>>,
+    archive: Option<Arc<Archive>>,
+    /// Collects the variant each video got, for `--resolutions-report`.
+    resolution_report: Option<Arc<ResolutionReport>>,
+    /// Where thumbnails are uploaded once they're saved, for `--output-s3`.
+    sink: Option<Arc<dyn Sink>>,
+    /// The base URL of the watch pages storyboards are found on, or `None` to skip them.
+    storyboards: Option<String>,
+    /// Remove thumbnails once they're in the archive.
+    archive_only: bool,
+    /// Video titles to embed for `--embed-metadata`, keyed by video ID, or `None` to embed
+    /// nothing.
+    embed_titles: Option<Arc<HashMap<String, String>>>,
+    budget: Option<Arc<ByteBudget>>,
+    /// When `--max-duration` runs out; no new downloads start after it.
+    deadline: Option<Instant>,
+    picker: Option<Arc<dyn ChannelPicker>>,
+    download_timeout: Option<Duration>,
+    backoff: Option<Arc<Backoff>>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    /// Limits concurrent downloads for `--adaptive-concurrency`.
+    adaptive: Option<Arc<AdaptiveConcurrency>>,
+    /// Limits concurrent Data API requests; unlimited if `None`.
+    api_semaphore: Option<Arc<Semaphore>>,
+    /// Bytes received from the thumbnail server by every task, for the throughput report.
+    received_bytes: Arc<AtomicU64>,
+}
+
+impl DownloadOptions {
+    fn from_args(args: &Args) -> Self {
+        DownloadOptions {
+            format: args.format,
+            source_format: OutputFormat::Jpg,
+            quality: args.quality,
+            overwrite: args.overwrite,
+            no_overwrite_newer: args.no_overwrite_newer,
+            fsync: args.fsync,
+            resolution: args.resolution,
+            try_webp: args.try_webp,
+            all_resolutions: args.all_resolutions,
+            variant_template: args.variant_template.clone(),
+            min_width: args.min_width,
+            min_height: args.min_height,
+            resize: args.resize,
+            resize_mode: args.resize_mode,
+            compress: args.compress,
+            placeholders: if args.keep_placeholders {
+                Vec::new()
+            } else {
+                args.placeholders.clone()
+            },
+            cache: None,
+            rate_limiter: args.max_rate.map(|rate| Arc::new(RateLimiter::new(rate))),
+            dedup: args.dedup.then(|| Arc::new(DedupIndex::default())),
+            checksums: None,
+            state: None,
+            channel_cache: None,
+            progress: args.json.then(|| {
+                let events = EventSink::stdout();
+                Arc::new(if args.quiet {
+                    ProgressCallback::new(move |event| {
+                        if matches!(event, ProgressEvent::Finished { .. }) {
+                            events.emit(&event.into());
+                        }
+                    })
+                } else {
+                    events.into_callback()
+                })
+            }),
+            on_download: args.on_download.as_deref().map(|command| {
+                let concurrency = args.concurrency.map_or_else(
+                    || std::thread::available_parallelism().map_or(1, usize::from),
+                    |limit| limit as usize,
+                );
+                Arc::new(DownloadHook::new(command, concurrency))
+            }),
+            archive: None,
+            resolution_report: args
+                .resolutions_report
+                .as_deref()
+                .map(|path| Arc::new(ResolutionReport::new(Path::new(path)))),
+            sink: None,
+            storyboards: args.storyboards.then(|| args.watch_base_url.clone()),
+            archive_only: args.archive_only,
+            embed_titles: args.embed_metadata.then(Arc::default),
+            budget: args
+                .max_total_size
+                .map(|limit| Arc::new(ByteBudget::new(limit))),
+            deadline: args
+                .max_duration
+                .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+            picker: (args.interactive && std::io::stdin().is_terminal())
+                .then(|| Arc::new(TerminalPicker) as Arc<dyn ChannelPicker>),
+            download_timeout: args.per_download_timeout.map(Duration::from_secs),
+            backoff: (args.retries > 0).then(|| {
+                Arc::new(Backoff::new(
+                    args.retries,
+                    RETRY_BASE_DELAY,
+                    RETRY_MAX_DELAY,
+                    args.jitter,
+                    shuffle::random_seed(),
+                ))
+            }),
+            breaker: args.failure_threshold.map(|threshold| {
+                Arc::new(CircuitBreaker::new(
+                    threshold,
+                    BREAKER_WINDOW,
+                    Duration::from_secs(args.cooldown),
+                ))
+            }),
+            adaptive: args.adaptive_concurrency.then(|| {
+                Arc::new(AdaptiveConcurrency::new(
+                    args.min_concurrency as usize,
+                    args.max_concurrency as usize,
+                ))
+            }),
+            api_semaphore: Some(Arc::new(Semaphore::new(args.api_concurrency as usize))),
+            received_bytes: Arc::default(),
+        }
+    }
+
+    /// Whether downloaded images are changed before they're saved, so they can't be streamed
+    /// to disk, resumed or compared with the server's size.
+    fn rewrites_images(&self) -> bool {
+        self.format != self.source_format
+            || self.resize.is_some()
+            || self.embed_titles.is_some()
+            || self.compress
+    }
+
+    /// Where a video's thumbnail is saved: `{id}.{format}`, gzipped as `{id}.{format}.gz`
+    /// with `--compress`.
+    fn thumbnail_path(&self, location: &OutputLocation, video_id: &str) -> PathBuf {
+        let path = thumbnail_path(location, video_id, self.format);
+        if self.compress {
+            compress::gz_path(&path)
+        } else {
+            path
+        }
+    }
+
+    /// Where the `resolution` variant of a video's thumbnail is saved: the video's one
+    /// thumbnail path, or with `--all-resolutions` a path for each variant.
+    fn variant_path(
+        &self,
+        location: &OutputLocation,
+        video_id: &str,
+        resolution: Resolution,
+    ) -> PathBuf {
+        if !self.all_resolutions {
+            return self.thumbnail_path(location, video_id);
+        }
+        let path = paths::variant_path_for(
+            video_id,
+            resolution,
+            self.format.extension(),
+            location,
+            self.variant_template.as_deref(),
+        );
+        if self.compress {
+            compress::gz_path(&path)
+        } else {
+            path
+        }
+    }
+
+    /// Whether `--max-duration` has run out, meaning no new downloads should start.
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Builds the options for a run, loading the state file and channel cache it uses.
+    async fn load(args: &Args) -> Result<Self, Box<dyn Error>> {
+        let mut options = DownloadOptions::from_args(args);
+        if let Some(path) = state_file_path(args) {
+            options.state = Some(Arc::new(DownloadState::load(&path).await?));
+        }
+        if args.checksums {
+            let output_dir = Path::new(args.output_dir());
+            options.checksums = Some(Arc::new(Checksums::load(output_dir).await?));
+        }
+        if let Some(format) = args.archive {
+            let output_dir = Path::new(args.output_dir());
+            let path = match &args.archive_file {
+                Some(path) => PathBuf::from(path),
+                None => output_dir.join(format!("thumbnails.{}", format.extension())),
+            };
+            options.archive = Some(Arc::new(Archive::create(&path, format, output_dir)?));
+        }
+        if let Some(location) = &args.output_s3 {
+            options.sink = Some(s3_sink(location, Path::new(args.output_dir()))?);
+        }
+        if !args.no_cache && args.video_ids_file.is_none() {
+            let ttl = Duration::from_secs(args.channel_cache_ttl * 3600);
+            options.channel_cache =
+                Some(Arc::new(ChannelCache::load(args.output_dir(), ttl).await?));
+        }
+        Ok(options)
+    }
+}
+
+/// Other things the tool can do besides downloading thumbnails.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List a channel's playlists with their IDs and video counts, e.g. to find the name to
+    /// pass to --playlist-name.
+    ListPlaylists {
+        /// The URL of the YouTube channel (e.g., https://www.youtube.com/@handle).
+        #[arg(short, long)]
+        channel_url: String,
+    },
+    /// Resolve a channel URL to its channel ID and uploads playlist ID without downloading
+    /// anything.
+    Resolve {
+        /// The URL of the YouTube channel (e.g., https://www.youtube.com/@handle).
+        #[arg(short, long)]
+        channel_url: String,
+
+        /// Print the IDs as a JSON object instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the files of an archive against the SHA256SUMS written by --checksums, without
+    /// any network access. Exits with status 2 if any file is changed or missing.
+    Verify {
+        /// The directory holding SHA256SUMS, normally the output directory.
+        dir: String,
+    },
+}
+
+impl Args {
+    /// The output directory, once `resolve_output_dir` has filled in the fallbacks.
+    fn output_dir(&self) -> &str {
+        self.output_dir.as_deref().unwrap_or(DEFAULT_OUTPUT_DIR)
+    }
+
+    /// The channel to download without `--input-file`: `--channel-url`, or the `/channel/`
+    /// URL of `--channel-id`, which resolves without an API call.
+    fn channel_url(&self) -> Option<String> {
+        self.channel_url.clone().or_else(|| {
+            self.channel_id
+                .as_ref()
+                .map(|id| format!("{}/channel/{}", WATCH_BASE_URL, id))
+        })
+    }
+}
+
+/// Picks the output directory: `--output-dir`, then the `YOUTUBE_IMAGE_OUTPUT_DIR`
+/// environment variable (`env`), then `DEFAULT_OUTPUT_DIR`. An empty value is an error
+/// rather than a silent fallback, as it's most likely a mistake in a script.
+fn resolve_output_dir(
+    flag: Option<String>,
+    env: Option<String>,
+) -> Result<String, DownloaderError> {
+    match (flag, env) {
+        (Some(flag), _) if flag.is_empty() => Err(DownloaderError::InvalidInput(
+            "--output-dir can't be empty.".to_string(),
+        )),
+        (Some(flag), _) => Ok(flag),
+        (None, Some(env)) if env.is_empty() => Err(DownloaderError::InvalidInput(format!(
+            "{} is set but empty; unset it or pass --output-dir.",
+            OUTPUT_DIR_ENV
+        ))),
+        (None, Some(env)) => Ok(env),
+        (None, None) => Ok(DEFAULT_OUTPUT_DIR.to_string()),
+    }
+}
+
+/// Fails if a flag needs image processing and this build doesn't include it, so it's
+/// reported up front instead of failing every download.
+fn check_image_processing(args: &Args) -> Result<(), DownloaderError> {
+    if convert::IMAGE_PROCESSING {
+        return Ok(());
+    }
+    let flag = if args.format != OutputFormat::Jpg {
+        format!("--format {}", args.format.extension())
+    } else if args.min_width.is_some() {
+        "--min-width".to_string()
+    } else if args.min_height.is_some() {
+        "--min-height".to_string()
+    } else if args.contact_sheet.is_some() {
+        "--contact-sheet".to_string()
+    } else if args.embed_metadata {
+        "--embed-metadata".to_string()
+    } else if args.resize.is_some() {
+        "--resize".to_string()
+    } else {
+        return Ok(());
+    };
+    Err(DownloaderError::InvalidInput(format!(
+        "{} needs image processing, which this build doesn't include. \
+         Rebuild with --features image-processing.",
+        flag
+    )))
+}
+
+/// The first flag given that only works with the Data API, as `--scrape` finds nothing
+/// but the IDs of a channel's latest videos.
+fn api_only_flag(args: &Args) -> Option<&'static str> {
+    let flags = [
+        (
+            matches!(args.command, Some(Command::ListPlaylists { .. })),
+            "list-playlists",
+        ),
+        (
+            matches!(args.command, Some(Command::Resolve { .. })),
+            "resolve",
+        ),
+        (args.stats_only, "--stats-only"),
+        (args.estimate_quota, "--estimate-quota"),
+        (args.export.is_some(), "--export"),
+        (args.with_metadata, "--with-metadata"),
+        (args.playlist_name.is_some(), "--playlist-name"),
+        (args.skip_shorts, "--skip-shorts"),
+        (args.only_shorts, "--only-shorts"),
+        (args.skip_restricted, "--skip-restricted"),
+        (args.title_contains.is_some(), "--title-contains"),
+        (args.title_regex.is_some(), "--title-regex"),
+        (args.sort_by.is_some(), "--sort-by"),
+        (args.verify_count, "--verify-count"),
+        (args.dir_template.is_some(), "--dir-template"),
+        (
+            args.input_file.is_some() && args.dir_name == DirName::Title,
+            "--dir-name title",
+        ),
+    ];
+    flags
+        .into_iter()
+        .find_map(|(given, flag)| given.then_some(flag))
+}
+
+/// Connects to the bucket for `--output-s3`.
+#[cfg(feature = "s3")]
+fn s3_sink(location: &S3Location, output_dir: &Path) -> Result<Arc<dyn Sink>, Box<dyn Error>> {
+    Ok(Arc::new(sink::S3Sink::from_env(location, output_dir)?))
+}
+
+/// Without the s3 feature, there's nothing to upload with.
+#[cfg(not(feature = "s3"))]
+fn s3_sink(_location: &S3Location, _output_dir: &Path) -> Result<Arc<dyn Sink>, Box<dyn Error>> {
+    Err(DownloaderError::InvalidInput(
+        "--output-s3 needs S3 support, which this build doesn't include. \
+         Rebuild with --features s3."
+            .to_string(),
+    )
+    .into())
+}
+
+/// Returns where thumbnails go before any per-channel or `--dir-template` subdirectories.
+fn output_location(args: &Args) -> OutputLocation {
+    if args.flatten {
+        OutputLocation::flat(args.output_dir())
+    } else {
+        OutputLocation::new(args.output_dir())
+    }
 }
 
+/// Returns the state file to use, if any. Watch mode always keeps one.
+fn state_file_path(args: &Args) -> Option<PathBuf> {
+    match (&args.state_file, args.watch) {
+        (Some(path), _) => Some(PathBuf::from(path)),
+        (None, Some(_)) => Some(Path::new(args.output_dir()).join(state::STATE_FILE)),
+        (None, None) => None,
+    }
+}
+
+/// Environment variable holding the output directory to use without `--output-dir`.
+const OUTPUT_DIR_ENV: &str = "YOUTUBE_IMAGE_OUTPUT_DIR";
+
+/// Environment variable holding an OAuth 2.0 access token, used if `--access-token` isn't given.
+const ACCESS_TOKEN_ENV: &str = "YOUTUBE_ACCESS_TOKEN";
+
+/// Output directory used when neither `--output-dir` nor `YOUTUBE_IMAGE_OUTPUT_DIR` is given.
+const DEFAULT_OUTPUT_DIR: &str = "thumbnails";
+
+/// Default base URL of the YouTube Data API.
+const API_BASE_URL: &str = "https://www.googleapis.com";
+
+/// User-Agent sent unless `--user-agent` is given.
+const DEFAULT_USER_AGENT: &str = concat!("youtube-image-downloader/", env!("CARGO_PKG_VERSION"));
+
+/// Default base URL of the YouTube thumbnail CDN.
+const THUMBNAIL_BASE_URL: &str = "https://img.youtube.com";
+
+/// Default base URL of the watch pages `--storyboards` reads storyboard specs from.
+const WATCH_BASE_URL: &str = "https://www.youtube.com";
+
+/// Maximum number of video IDs accepted by a single `videos` API call.
+const VIDEOS_BATCH_SIZE: usize = 50;
+
+/// How many of the latest thumbnail requests `--failure-threshold` looks at.
+const BREAKER_WINDOW: usize = 20;
+
+/// How many Data API requests are made at once unless `--api-concurrency` says otherwise.
+const DEFAULT_API_CONCURRENCY: u64 = 4;
+
+/// How far, in percent, a channel's listing may fall short of its reported video count
+/// before `--verify-count` reports it. Counts drift a little as videos are processed or
+/// made private.
+const LISTING_TOLERANCE_PERCENT: u64 = 10;
+
+/// The most playlist items the API returns per page, and the default `--page-size`.
+const MAX_PAGE_SIZE: u8 = 50;
+
+/// Paging through a playlist stops after this many pages in a row come back empty.
+const MAX_EMPTY_PAGES: usize = 5;
+
+/// How many search results are checked for a channel whose handle actually matches.
+const HANDLE_CANDIDATES: usize = 5;
+
+/// The tabs of a channel page, which browsers append to the channel's URL.
+const CHANNEL_TABS: &[&str] = &[
+    "featured",
+    "videos",
+    "shorts",
+    "streams",
+    "live",
+    "playlists",
+    "podcasts",
+    "releases",
+    "courses",
+    "community",
+    "posts",
+    "store",
+    "channels",
+    "about",
+    "search",
+];
+
+/// Videos shorter than this many seconds are treated as Shorts.
+const SHORTS_MAX_DURATION_SECS: u64 = 60;
+
+/// The longest wait before the first retry of a failed download, doubled for every further one.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// The wait between retries stops growing at this.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
 // --- Structs for YouTube API Deserialization ---
 
+/// Download results, aggregated across all processed channels.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Summary {
+    downloaded: usize,
+    failed: usize,
+    /// Videos skipped because they are private or deleted.
+    unavailable: usize,
+    /// Age-restricted videos that were skipped with `--skip-restricted` or whose thumbnail
+    /// couldn't be downloaded; they aren't counted as failed.
+    restricted: usize,
+    /// Videos not downloaded because `--max-total-size` was reached.
+    over_budget: usize,
+    /// Videos not downloaded because `--max-duration` ran out.
+    out_of_time: usize,
+    /// Channels not processed because `--max-duration` ran out.
+    channels_out_of_time: usize,
+    failed_channels: usize,
+    /// Bytes received from the thumbnail server, including downloads that later failed.
+    bytes: u64,
+    /// Where each video's thumbnail is expected, in playlist order, whether or not it downloaded.
+    thumbnail_paths: Vec<PathBuf>,
+    /// The videos collected for `--export`.
+    exported: Vec<VideoRecord>,
+}
+
+impl Summary {
+    /// Counts how a video's download went, handing the video back if it was deferred.
+    fn count(&mut self, restricted: bool, result: FirstPass) -> Option<Box<DeferredVideo>> {
+        match result {
+            FirstPass::Finished(true) => self.downloaded += 1,
+            FirstPass::Finished(false) if restricted => self.restricted += 1,
+            FirstPass::Finished(false) => self.failed += 1,
+            FirstPass::OverBudget => self.over_budget += 1,
+            FirstPass::OutOfTime => self.out_of_time += 1,
+            FirstPass::Deferred(video) => return Some(video),
+        }
+        None
+    }
+}
+
+impl std::ops::AddAssign for Summary {
+    fn add_assign(&mut self, other: Self) {
+        self.downloaded += other.downloaded;
+        self.failed += other.failed;
+        self.unavailable += other.unavailable;
+        self.restricted += other.restricted;
+        self.over_budget += other.over_budget;
+        self.out_of_time += other.out_of_time;
+        self.channels_out_of_time += other.channels_out_of_time;
+        self.failed_channels += other.failed_channels;
+        self.bytes += other.bytes;
+        self.thumbnail_paths.extend(other.thumbnail_paths);
+        self.exported.extend(other.exported);
+    }
+}
+
 /// Represents the top-level structure of the YouTube API response for search.
 /// Used to find a channel ID from a custom handle.
 #[derive(Deserialize, Debug)]
@@ -33,6 +1231,14 @@ struct SearchListResponse {
 #[derive(Deserialize, Debug)]
 struct SearchResultItem {
     id: SearchResultId,
+    /// Only present when the `snippet` part is requested.
+    snippet: Option<SearchResultSnippet>,
+}
+
+/// Contains the title of the search result.
+#[derive(Deserialize, Debug)]
+struct SearchResultSnippet {
+    title: String,
 }
 
 /// Contains the ID of the search result (e.g., channelId).
@@ -46,6 +1252,8 @@ struct SearchResultId {
 /// Used to get the 'uploads' playlist ID.
 #[derive(Deserialize, Debug)]
 struct ChannelListResponse {
+    // The API omits `items` entirely when nothing matches.
+    #[serde(default)]
     items: Vec<ChannelItem>,
 }
 
@@ -55,6 +1263,29 @@ struct ChannelItem {
     id: Option<String>,
     #[serde(rename = "contentDetails")]
     content_details: Option<ContentDetails>,
+    /// Only present when the `snippet` part is requested.
+    snippet: Option<ChannelSnippet>,
+    /// Only present when the `statistics` part is requested.
+    statistics: Option<ChannelStatistics>,
+}
+
+/// Contains the channel's title and handle, e.g. `@somechannel`.
+#[derive(Deserialize, Debug)]
+struct ChannelSnippet {
+    #[serde(default)]
+    title: String,
+    #[serde(rename = "customUrl")]
+    custom_url: Option<String>,
+}
+
+/// Contains the channel's subscriber and video counts, which the API sends as strings. The
+/// subscriber count is left out when the channel hides it.
+#[derive(Deserialize, Debug)]
+struct ChannelStatistics {
+    #[serde(rename = "subscriberCount")]
+    subscriber_count: Option<String>,
+    #[serde(rename = "videoCount")]
+    video_count: Option<String>,
 }
 
 /// Contains details about the channel's content, including the uploads playlist.
@@ -67,7 +1298,37 @@ struct ContentDetails {
 /// Contains the ID of the uploads playlist.
 #[derive(Deserialize, Debug)]
 struct RelatedPlaylists {
-    uploads: String,
+    uploads: Option<String>,
+}
+
+/// Represents the top-level structure of the YouTube API response for a channel's playlists.
+#[derive(Deserialize, Debug)]
+struct PlaylistListResponse {
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+    #[serde(default)]
+    items: Vec<Playlist>,
+}
+
+/// Represents a single playlist of a channel.
+#[derive(Deserialize, Debug)]
+struct Playlist {
+    id: String,
+    snippet: PlaylistSnippet,
+    /// Only present when the `contentDetails` part is requested.
+    #[serde(rename = "contentDetails")]
+    content_details: Option<PlaylistContentDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PlaylistContentDetails {
+    #[serde(rename = "itemCount")]
+    item_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct PlaylistSnippet {
+    title: String,
 }
 
 /// Represents the top-level structure of the YouTube API response for playlist items.
@@ -83,6 +1344,8 @@ struct PlaylistItemListResponse {
 struct PlaylistItem {
     #[serde(rename = "contentDetails")]
     content_details: VideoContentDetails,
+    /// Only present when the `snippet` part is requested.
+    snippet: Option<PlaylistItemSnippet>,
 }
 
 /// Contains the ID of the video.
@@ -90,359 +1353,10108 @@ struct PlaylistItem {
 struct VideoContentDetails {
     #[serde(rename = "videoId")]
     video_id: String,
+    #[serde(rename = "videoPublishedAt")]
+    video_published_at: Option<String>,
 }
 
-/// Resolves a YouTube channel URL to a channel ID.
-/// Handles formats like /@handle, /channel/ID, and /user/username.
-async fn get_channel_id_from_url(
-    client: &Client,
-    api_key: &str,
-    channel_url: &str,
-) -> Result<String, Box<dyn Error>> {
-    let url_path = reqwest::Url::parse(channel_url)?.path().to_string();
-    let path_parts: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
+/// Contains the title, publish date and thumbnails of a playlist item.
+#[derive(Deserialize, Debug)]
+struct PlaylistItemSnippet {
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(rename = "publishedAt")]
+    published_at: String,
+    #[serde(rename = "channelTitle", default)]
+    channel_title: String,
+    #[serde(default)]
+    thumbnails: HashMap<String, Thumbnail>,
+}
 
-    if path_parts.is_empty() {
-        return Err("Invalid YouTube channel URL path.".into());
-    }
+/// A single thumbnail variant listed in a snippet.
+#[derive(Deserialize, Debug)]
+struct Thumbnail {
+    url: String,
+}
 
-    let first_part = path_parts[0];
+/// Represents the top-level structure of the YouTube API response for videos.
+#[derive(Deserialize, Debug)]
+struct VideoListResponse {
+    #[serde(default)]
+    items: Vec<VideoDetails>,
+}
 
-    // Handle /@handle format by searching for the handle
-    if first_part.starts_with('@') {
-        let handle = &first_part[1..];
-        println!("Found handle: {}. Searching for channel ID...", handle);
-        let search_url = format!(
-            "https://www.googleapis.com/youtube/v3/search?part=id&q={}&type=channel&key={}",
-            handle, api_key
-        );
-        let response = client
-            .get(&search_url)
-            .send()
-            .await?
-            .json::<SearchListResponse>()
-            .await?;
-        return response
-            .items
-            .into_iter()
-            .next()
-            .map(|item| item.id.channel_id)
-            .ok_or_else(|| format!("Could not find a channel ID for handle: {}", handle).into());
-    }
+/// Represents a single video in the API response, as fetched by `fetch_video_details`.
+#[derive(Deserialize, Debug, Clone)]
+struct VideoDetails {
+    id: String,
+    snippet: Option<VideoSnippet>,
+    #[serde(rename = "contentDetails")]
+    content_details: Option<VideoDuration>,
+    statistics: Option<VideoStatistics>,
+    status: Option<VideoStatus>,
+}
 
-    // Handle /channel/ID and /user/username formats
-    if path_parts.len() >= 2 {
-        let type_part = path_parts[0];
-        let identifier = path_parts[1];
+/// Contains the view and like counts of a video. Either is absent when the uploader hides it;
+/// the API sends them as strings.
+#[derive(Deserialize, Debug, Clone)]
+struct VideoStatistics {
+    #[serde(rename = "viewCount")]
+    view_count: Option<String>,
+    #[serde(rename = "likeCount")]
+    like_count: Option<String>,
+}
 
-        // If it's a /channel/ID URL, the ID is right there.
-        if type_part == "channel" {
-            println!("Found channel ID directly in URL: {}", identifier);
-            return Ok(identifier.to_string());
-        }
+/// Contains the privacy and processing status of a video.
+#[derive(Deserialize, Debug, Clone)]
+struct VideoStatus {
+    #[serde(rename = "privacyStatus")]
+    privacy_status: String,
+    #[serde(rename = "uploadStatus")]
+    upload_status: Option<String>,
+}
 
-        // If it's a legacy /user/username URL, we need to look it up.
-        if type_part == "user" {
-            println!(
-                "Found legacy username: {}. Searching for channel ID...",
-                identifier
-            );
-            let channel_list_url = format!(
-                "https://www.googleapis.com/youtube/v3/channels?part=id&forUsername={}&key={}",
-                identifier, api_key
-            );
-            let response = client
-                .get(&channel_list_url)
-                .send()
-                .await?
-                .json::<ChannelListResponse>()
-                .await?;
+/// Contains the title, publish date and channel of a video.
+#[derive(Deserialize, Debug, Clone)]
+struct VideoSnippet {
+    #[serde(default)]
+    title: String,
+    #[serde(rename = "publishedAt")]
+    published_at: String,
+    #[serde(rename = "channelTitle", default)]
+    channel_title: String,
+}
+
+/// Contains the ISO-8601 duration of the video (e.g., PT4M13S) and its content rating.
+#[derive(Deserialize, Debug, Clone)]
+struct VideoDuration {
+    duration: String,
+    #[serde(rename = "contentRating", default)]
+    content_rating: ContentRating,
+}
+
+/// The ratings a video has been given; YouTube's own marks it as age-restricted.
+#[derive(Deserialize, Debug, Clone, Default)]
+struct ContentRating {
+    #[serde(rename = "ytRating")]
+    yt_rating: Option<String>,
+}
+
+/// The body of a failed YouTube Data API request.
+#[derive(Deserialize, Debug)]
+struct ApiErrorResponse {
+    error: ApiErrorBody,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiErrorBody {
+    message: String,
+    #[serde(default)]
+    errors: Vec<ApiErrorReason>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiErrorReason {
+    #[serde(default)]
+    reason: String,
+}
+
+/// How much of an unparseable response body is quoted in the error.
+const MALFORMED_BODY_SNIPPET_CHARS: usize = 200;
+
+/// Decodes a YouTube Data API response, turning error statuses into a `DownloaderError`
+/// so quota exhaustion and a bad API key can be told apart from other failures. A body that doesn't have the
+/// expected shape is reported with the start of its text, to help diagnose API changes.
+async fn parse_json<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, DownloaderError> {
+    let status = response.status();
+    let body = response.text().await?;
+    if status.is_success() {
+        return serde_json::from_str(&body).map_err(|e| DownloaderError::MalformedResponse {
+            message: e.to_string(),
+            snippet: body.chars().take(MALFORMED_BODY_SNIPPET_CHARS).collect(),
+        });
+    }
+    let Ok(ApiErrorResponse { error }) = serde_json::from_str(&body) else {
+        return Err(DownloaderError::Api {
+            status: status.as_u16(),
+            message: body,
+        });
+    };
+    let quota_exceeded = error
+        .errors
+        .iter()
+        .any(|e| e.reason == "quotaExceeded" || e.reason == "dailyLimitExceeded");
+    if status == StatusCode::FORBIDDEN && quota_exceeded {
+        return Err(DownloaderError::QuotaExceeded);
+    }
+    // Invalid keys are reported as `keyInvalid`, or as a generic `badRequest` that only
+    // the message tells apart from other bad requests.
+    let key_invalid = error.errors.iter().any(|e| {
+        e.reason == "keyInvalid" || (e.reason == "badRequest" && error.message.contains("API key"))
+    });
+    if matches!(status, StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN) && key_invalid {
+        return Err(DownloaderError::InvalidApiKey(error.message));
+    }
+    Err(DownloaderError::Api {
+        status: status.as_u16(),
+        message: error.message,
+    })
+}
+
+/// Resolves a YouTube channel URL to a channel ID, consulting and updating `cache` if given.
+/// Handles formats like /@handle, /channel/ID, /c/CustomName, and /user/username.
+/// `picker`, if given, is asked to choose when the URL could refer to several channels.
+async fn get_channel_id_from_url(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    cache: Option<&ChannelCache>,
+    picker: Option<&dyn ChannelPicker>,
+    base_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let channel_url = &normalize_channel_url(channel_url)?;
+    // /channel/ URLs already contain the ID, so there's no lookup worth caching.
+    let is_channel_id_url =
+        reqwest::Url::parse(channel_url).is_ok_and(|url| url.path().starts_with("/channel/"));
+    let cache = cache.filter(|_| !is_channel_id_url);
+    if let Some(channel_id) = cache.and_then(|cache| cache.get(channel_url)) {
+        status!("Using cached channel ID for {}", channel_url);
+        return Ok(channel_id);
+    }
+    let channel_id = resolve_channel_id(client, credentials, channel_url, picker, base_url).await?;
+    if let Some(cache) = cache {
+        cache.insert(channel_url, &channel_id);
+        cache.save().await?;
+    }
+    Ok(channel_id)
+}
+
+/// Reduces a channel URL copied from a browser to the channel itself, e.g.
+/// `youtube.com/@handle/videos?si=abc#top` to `https://youtube.com/@handle`: the query,
+/// fragment and trailing channel tabs are dropped, and a missing scheme is added.
+fn normalize_channel_url(channel_url: &str) -> Result<String, DownloaderError> {
+    let channel_url = channel_url.trim();
+    let with_scheme = if channel_url.contains("://") {
+        channel_url.to_string()
+    } else {
+        format!("https://{}", channel_url)
+    };
+    let mut url = reqwest::Url::parse(&with_scheme).map_err(|e| {
+        DownloaderError::InvalidInput(format!("Invalid channel URL {}: {}", channel_url, e))
+    })?;
+    url.set_query(None);
+    url.set_fragment(None);
+
+    let mut segments: Vec<String> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect();
+    let identifier_len = match segments.first().map(String::as_str) {
+        Some(first) if first.starts_with('@') => 1,
+        Some("channel" | "user" | "c") => 2,
+        _ => segments.len(),
+    };
+    while segments.len() > identifier_len
+        && segments
+            .last()
+            .is_some_and(|segment| CHANNEL_TABS.contains(&segment.to_ascii_lowercase().as_str()))
+    {
+        segments.pop();
+    }
+    url.set_path(&segments.join("/"));
+    Ok(url.to_string())
+}
+
+/// Looks up the channel ID for a channel URL using the API where needed.
+async fn resolve_channel_id(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    picker: Option<&dyn ChannelPicker>,
+    base_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let url_path = reqwest::Url::parse(channel_url)
+        .map_err(|e| {
+            DownloaderError::InvalidInput(format!("Invalid channel URL {}: {}", channel_url, e))
+        })?
+        .path()
+        .to_string();
+    let path_parts: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
+
+    if path_parts.is_empty() {
+        return Err(
+            DownloaderError::InvalidInput("Invalid YouTube channel URL path.".to_string()).into(),
+        );
+    }
+
+    let first_part = path_parts[0];
+
+    // Handle /@handle format by searching for the handle
+    if let Some(handle) = first_part.strip_prefix('@') {
+        status!("Found handle: {}. Searching for channel ID...", handle);
+        let search_url = format!(
+            "{}/youtube/v3/search?part=id&q={}&type=channel{}",
+            base_url,
+            handle,
+            credentials.key_param()
+        );
+        let response: SearchListResponse =
+            parse_json(credentials.get(client, &search_url).send().await?).await?;
+        // Search is fuzzy, so only accept a result whose handle really is the one asked for.
+        let candidates: Vec<String> = response
+            .items
+            .into_iter()
+            .take(HANDLE_CANDIDATES)
+            .map(|item| item.id.channel_id)
+            .collect();
+        if candidates.is_empty() {
+            return Err(DownloaderError::ChannelNotFound(format!(
+                "Could not find a channel ID for handle: {}",
+                handle
+            ))
+            .into());
+        }
+        if let Some(channel_id) =
+            find_channel_with_handle(client, credentials, handle, &candidates, base_url).await?
+        {
+            return Ok(channel_id);
+        }
+        if let Some(picker) = picker {
+            return choose_channel(client, credentials, &candidates, picker, base_url).await;
+        }
+        return Err(DownloaderError::ChannelNotFound(format!(
+            "None of the search results for handle {} have that handle.",
+            handle
+        ))
+        .into());
+    }
+
+    // Handle /channel/ID and /user/username formats
+    if path_parts.len() >= 2 {
+        let type_part = path_parts[0];
+        let identifier = path_parts[1];
+
+        // If it's a /channel/ID URL, the ID is right there.
+        if type_part == "channel" {
+            status!("Found channel ID directly in URL: {}", identifier);
+            return Ok(identifier.to_string());
+        }
+
+        // If it's a legacy /user/username URL, we need to look it up.
+        if type_part == "user" {
+            status!(
+                "Found legacy username: {}. Searching for channel ID...",
+                identifier
+            );
+            let channel_list_url = format!(
+                "{}/youtube/v3/channels?part=id&forUsername={}{}",
+                base_url,
+                identifier,
+                credentials.key_param()
+            );
+            let response: ChannelListResponse =
+                parse_json(credentials.get(client, &channel_list_url).send().await?).await?;
             return response
                 .items
                 .into_iter()
                 .next()
                 .and_then(|item| item.id)
                 .ok_or_else(|| {
-                    format!("Could not find a channel ID for username: {}", identifier).into()
+                    DownloaderError::ChannelNotFound(format!(
+                        "Could not find a channel ID for username: {}",
+                        identifier
+                    ))
+                    .into()
                 });
         }
+
+        // Legacy /c/CustomName URLs have no direct API lookup.
+        if type_part == "c" {
+            status!(
+                "Found custom URL name: {}. Searching for channel ID...",
+                identifier
+            );
+            return get_channel_id_for_custom_name(
+                client,
+                credentials,
+                identifier,
+                picker,
+                base_url,
+            )
+            .await;
+        }
+    }
+
+    Err(DownloaderError::InvalidInput("Unsupported YouTube channel URL format. Please use a URL like https://www.youtube.com/@handle, https://www.youtube.com/channel/ID, https://www.youtube.com/c/CustomName, or https://www.youtube.com/user/username".to_string()).into())
+}
+
+/// Resolves a legacy /c/CustomName to a channel ID.
+/// Most custom names were migrated to an identical handle, so that's tried first;
+/// otherwise the channel search result whose title matches the name best is used, or
+/// `picker` chooses between the search results.
+async fn get_channel_id_for_custom_name(
+    client: &Client,
+    credentials: Credentials<'_>,
+    custom_name: &str,
+    picker: Option<&dyn ChannelPicker>,
+    base_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let handle_url = format!(
+        "{}/youtube/v3/channels?part=id&forHandle={}{}",
+        base_url,
+        custom_name,
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &handle_url).send().await?).await?;
+    if let Some(channel_id) = response.items.into_iter().next().and_then(|item| item.id) {
+        return Ok(channel_id);
+    }
+
+    let search_url = format!(
+        "{}/youtube/v3/search?part=snippet&q={}&type=channel{}",
+        base_url,
+        custom_name,
+        credentials.key_param()
+    );
+    let response: SearchListResponse =
+        parse_json(credentials.get(client, &search_url).send().await?).await?;
+    if let Some(picker) = picker.filter(|_| response.items.len() > 1) {
+        let candidates: Vec<String> = response
+            .items
+            .into_iter()
+            .take(HANDLE_CANDIDATES)
+            .map(|item| item.id.channel_id)
+            .collect();
+        return choose_channel(client, credentials, &candidates, picker, base_url).await;
     }
+    best_custom_name_match(response.items, custom_name).ok_or_else(|| {
+        DownloaderError::ChannelNotFound(format!(
+            "Could not find a channel ID for custom URL: {}",
+            custom_name
+        ))
+        .into()
+    })
+}
+
+/// Picks the search result whose title matches the custom name, ignoring case and
+/// whitespace, falling back to the top result.
+fn best_custom_name_match(items: Vec<SearchResultItem>, custom_name: &str) -> Option<String> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let wanted = normalize(custom_name);
+    let position = items
+        .iter()
+        .position(|item| {
+            item.snippet
+                .as_ref()
+                .is_some_and(|snippet| normalize(&snippet.title) == wanted)
+        })
+        .unwrap_or(0);
+    items
+        .into_iter()
+        .nth(position)
+        .map(|item| item.id.channel_id)
+}
+
+/// Looks up the titles and subscriber counts of the candidate channels and lets `picker`
+/// choose one of them.
+async fn choose_channel(
+    client: &Client,
+    credentials: Credentials<'_>,
+    candidates: &[String],
+    picker: &dyn ChannelPicker,
+    base_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let url = format!(
+        "{}/youtube/v3/channels?part=snippet,statistics&id={}{}",
+        base_url,
+        candidates.join(","),
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+    let mut found: HashMap<String, ChannelItem> = response
+        .items
+        .into_iter()
+        .filter_map(|item| Some((item.id.clone()?, item)))
+        .collect();
+    // Keep the search order, which puts the most relevant channel first.
+    let candidates: Vec<ChannelCandidate> = candidates
+        .iter()
+        .filter_map(|id| found.remove(id))
+        .map(|item| ChannelCandidate {
+            channel_id: item.id.unwrap_or_default(),
+            title: item.snippet.map(|s| s.title).unwrap_or_default(),
+            subscriber_count: item
+                .statistics
+                .and_then(|s| s.subscriber_count)
+                .and_then(|count| count.parse().ok()),
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Err(DownloaderError::ChannelNotFound(
+            "None of the matching channels could be looked up.".to_string(),
+        )
+        .into());
+    }
+    match picker.pick(&candidates)? {
+        Some(index) => Ok(candidates[index].channel_id.clone()),
+        None => Err(DownloaderError::ChannelNotFound("No channel was chosen.".to_string()).into()),
+    }
+}
 
-    Err("Unsupported YouTube channel URL format. Please use a URL like https://www.youtube.com/@handle, https://www.youtube.com/channel/ID, or https://www.youtube.com/user/username".into())
+/// Looks up the handles of the candidate channels and returns the first, in the given order,
+/// whose handle matches `handle` (without the leading `@`, ignoring case).
+async fn find_channel_with_handle(
+    client: &Client,
+    credentials: Credentials<'_>,
+    handle: &str,
+    candidates: &[String],
+    base_url: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let url = format!(
+        "{}/youtube/v3/channels?part=snippet&id={}{}",
+        base_url,
+        candidates.join(","),
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+    let wanted = format!("@{}", handle);
+    let matching: HashSet<String> = response
+        .items
+        .into_iter()
+        .filter(|item| {
+            item.snippet
+                .as_ref()
+                .and_then(|snippet| snippet.custom_url.as_deref())
+                .is_some_and(|custom_url| custom_url.eq_ignore_ascii_case(&wanted))
+        })
+        .filter_map(|item| item.id)
+        .collect();
+    Ok(candidates.iter().find(|id| matching.contains(*id)).cloned())
 }
 
 /// Fetches the uploads playlist ID for a given YouTube channel ID.
+/// Returns `Ok(None)` if the channel exists but has no uploads playlist, and an error if
+/// the channel doesn't exist at all.
 async fn get_uploads_playlist_id(
     client: &Client,
-    api_key: &str,
+    credentials: Credentials<'_>,
+    channel_id: &str,
+    base_url: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let url = format!(
+        "{}/youtube/v3/channels?part=contentDetails&id={}{}",
+        base_url,
+        channel_id,
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+
+    let item = response.items.into_iter().next().ok_or_else(|| {
+        DownloaderError::ChannelNotFound(format!("Channel {} not found.", channel_id))
+    })?;
+    Ok(item
+        .content_details
+        .and_then(|details| details.related_playlists.uploads)
+        .filter(|uploads| !uploads.is_empty()))
+}
+
+/// Derives a channel's uploads playlist ID from its channel ID without an API call:
+/// a `UC...` channel uploads to `UU...`. `None` for IDs that don't follow the pattern.
+fn derive_uploads_playlist_id(channel_id: &str) -> Option<String> {
+    channel_id
+        .strip_prefix("UC")
+        .filter(|rest| !rest.is_empty())
+        .map(|rest| format!("UU{}", rest))
+}
+
+/// Lists a channel's uploads with the given parts, along with the ID of the playlist they
+/// came from, or `None` if the channel has no uploads playlist. The playlist ID is derived
+/// from the channel ID where possible, which saves a channels API call. A derived playlist
+/// that comes back empty may just mean the shortcut doesn't hold for this channel, so the
+/// ID is then looked up and listed as well if it's a different one.
+async fn list_uploads(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_id: &str,
+    parts: &str,
+    page_size: u8,
+    max_pages: Option<u32>,
+    base_url: &str,
+) -> Result<Option<(String, Vec<PlaylistItem>)>, Box<dyn Error>> {
+    let derived = derive_uploads_playlist_id(channel_id);
+    if let Some(playlist_id) = &derived {
+        let items = get_all_playlist_items(
+            client,
+            credentials,
+            playlist_id,
+            parts,
+            page_size,
+            max_pages,
+            base_url,
+        )
+        .await?;
+        if !items.is_empty() {
+            return Ok(Some((playlist_id.clone(), items)));
+        }
+        tracing::debug!(
+            playlist_id,
+            "derived uploads playlist is empty, looking it up"
+        );
+    }
+    let Some(playlist_id) =
+        get_uploads_playlist_id(client, credentials, channel_id, base_url).await?
+    else {
+        return Ok(None);
+    };
+    if derived.as_ref() == Some(&playlist_id) {
+        return Ok(Some((playlist_id, Vec::new())));
+    }
+    let items = get_all_playlist_items(
+        client,
+        credentials,
+        &playlist_id,
+        parts,
+        page_size,
+        max_pages,
+        base_url,
+    )
+    .await?;
+    Ok(Some((playlist_id, items)))
+}
+
+/// Fetches the title of a YouTube channel.
+async fn get_channel_title(
+    client: &Client,
+    credentials: Credentials<'_>,
     channel_id: &str,
+    base_url: &str,
 ) -> Result<String, Box<dyn Error>> {
     let url = format!(
-        "https://www.googleapis.com/youtube/v3/channels?part=contentDetails&id={}&key={}",
-        channel_id, api_key
+        "{}/youtube/v3/channels?part=snippet&id={}{}",
+        base_url,
+        channel_id,
+        credentials.key_param()
     );
-    let response = client
-        .get(&url)
-        .send()
-        .await?
-        .json::<ChannelListResponse>()
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+    response
+        .items
+        .into_iter()
+        .next()
+        .and_then(|item| item.snippet)
+        .map(|snippet| snippet.title)
+        .ok_or_else(|| {
+            DownloaderError::ChannelNotFound(format!("Channel {} not found.", channel_id)).into()
+        })
+}
+
+/// Fetches the number of videos the channel's statistics report, if the API includes it.
+async fn get_channel_video_count(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_id: &str,
+    base_url: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let url = format!(
+        "{}/youtube/v3/channels?part=statistics&id={}{}",
+        base_url,
+        channel_id,
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+    Ok(response
+        .items
+        .into_iter()
+        .next()
+        .and_then(|item| item.statistics)
+        .and_then(|statistics| statistics.video_count)
+        .and_then(|count| count.parse().ok()))
+}
+
+/// Checks a channel's listing against the video count in its statistics, for
+/// `--verify-count`. The counts rarely match exactly, so only a listing more than
+/// `LISTING_TOLERANCE_PERCENT` short is reported: as a warning, or an error with `--strict`.
+fn check_video_count(collected: usize, reported: u64, strict: bool) -> Result<(), DownloaderError> {
+    if (collected as u64) * 100 >= reported * (100 - LISTING_TOLERANCE_PERCENT) {
+        return Ok(());
+    }
+    let error = DownloaderError::IncompleteListing {
+        collected,
+        reported,
+    };
+    if strict {
+        return Err(error);
+    }
+    eprintln!("Warning: {}.", error);
+    Ok(())
+}
+
+/// Finds the ID of the channel's playlist titled `name`, ignoring case.
+async fn find_playlist_by_name(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_id: &str,
+    name: &str,
+    base_url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let wanted = name.to_lowercase();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut url = format!(
+            "{}/youtube/v3/playlists?part=snippet&channelId={}{}&maxResults=50",
+            base_url,
+            channel_id,
+            credentials.key_param()
+        );
+        if let Some(token) = &page_token {
+            url.push_str(&format!("&pageToken={}", token));
+        }
+
+        let response: PlaylistListResponse =
+            parse_json(credentials.get(client, &url).send().await?).await?;
+        if let Some(playlist) = response
+            .items
+            .into_iter()
+            .find(|playlist| playlist.snippet.title.to_lowercase() == wanted)
+        {
+            return Ok(playlist.id);
+        }
+
+        page_token = response.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    Err(DownloaderError::InvalidInput(format!(
+        "Channel {} has no playlist named \"{}\".",
+        channel_id, name
+    ))
+    .into())
+}
+
+/// The IDs printed by the `resolve` subcommand.
+#[derive(Serialize, Debug)]
+struct ResolvedChannel {
+    channel_id: String,
+    uploads_playlist_id: Option<String>,
+}
+
+/// Resolves a channel URL and writes its channel and uploads playlist IDs to `output`,
+/// for the `resolve` subcommand.
+async fn resolve_channel(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    json: bool,
+    output: &mut impl Write,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    let channel_id =
+        get_channel_id_from_url(client, credentials, channel_url, None, None, base_url).await?;
+    let uploads_playlist_id =
+        get_uploads_playlist_id(client, credentials, &channel_id, base_url).await?;
+    let resolved = ResolvedChannel {
+        channel_id,
+        uploads_playlist_id,
+    };
+    if json {
+        writeln!(output, "{}", serde_json::to_string(&resolved)?)?;
+    } else {
+        writeln!(output, "Channel ID: {}", resolved.channel_id)?;
+        writeln!(
+            output,
+            "Uploads playlist ID: {}",
+            resolved.uploads_playlist_id.as_deref().unwrap_or("none")
+        )?;
+    }
+    Ok(())
+}
+
+/// Collects the metrics of a finished run for `--metrics-file`.
+fn run_metrics(summary: &Summary, duration: Duration) -> metrics::RunMetrics {
+    metrics::RunMetrics {
+        downloaded: summary.downloaded,
+        failed: summary.failed,
+        bytes: summary.bytes,
+        duration,
+        quota_units: quota::units_used(),
+    }
+}
+
+/// Checks an archive against its checksum file for the `verify` subcommand, listing the
+/// files that failed on `output`, and returns the exit status.
+async fn verify_archive(dir: &Path, output: &mut impl Write) -> Result<i32, Box<dyn Error>> {
+    let verification = checksums::verify(dir).await?;
+    for name in &verification.mismatched {
+        writeln!(output, "{}: FAILED", name)?;
+    }
+    for name in &verification.missing {
+        writeln!(output, "{}: MISSING", name)?;
+    }
+    writeln!(
+        output,
+        "Verified {} files: {} failed, {} missing.",
+        verification.checked,
+        verification.mismatched.len(),
+        verification.missing.len()
+    )?;
+    Ok(if verification.passed() {
+        0
+    } else {
+        error::EXIT_PARTIAL_FAILURE
+    })
+}
+
+/// Resolves a channel, pages through its uploads and writes a summary of them to `output`,
+/// for `--stats-only`.
+async fn print_channel_stats(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    page_size: u8,
+    max_pages: Option<u32>,
+    output: &mut impl Write,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    let channel_id =
+        get_channel_id_from_url(client, credentials, channel_url, None, None, base_url).await?;
+    let items = list_uploads(
+        client,
+        credentials,
+        &channel_id,
+        "contentDetails",
+        page_size,
+        max_pages,
+        base_url,
+    )
+    .await?
+    .map(|(_, items)| items)
+    .unwrap_or_default();
+    let dates: BTreeSet<&str> = items
+        .iter()
+        .filter_map(|item| item.content_details.video_published_at.as_deref())
+        .collect();
+
+    writeln!(output, "Channel: {} ({})", channel_url, channel_id)?;
+    writeln!(output, "Videos: {}", items.len())?;
+    if let (Some(oldest), Some(newest)) = (dates.first(), dates.last()) {
+        writeln!(output, "Oldest upload: {}", oldest)?;
+        writeln!(output, "Newest upload: {}", newest)?;
+    }
+    writeln!(
+        output,
+        "Estimated thumbnails: {} (one per video)",
+        items.len()
+    )?;
+    Ok(())
+}
+
+/// The quota units resolving `channel_url` to a channel ID costs: handles and custom
+/// names are searched for, then the results' handles are checked in one more request.
+fn resolution_cost(channel_url: &str) -> u64 {
+    let path = reqwest::Url::parse(channel_url)
+        .map(|url| url.path().to_string())
+        .unwrap_or_default();
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    match segments.next() {
+        Some("channel") => 0,
+        Some("user") => quota::LIST_COST,
+        _ => quota::SEARCH_COST + quota::LIST_COST,
+    }
+}
+
+/// The quota units downloading a channel with `video_count` videos is expected to use
+/// with these `args`. `cached` is whether the channel ID is in the channel cache.
+fn estimate_channel_quota(channel_url: &str, video_count: u64, cached: bool, args: &Args) -> u64 {
+    let mut requests = 0;
+    // The first page of playlists for --playlist-name. The uploads playlist ID is derived
+    // from the channel ID without a request.
+    if args.playlist_name.is_some() {
+        requests += 1;
+    }
+    if args.input_file.is_some() && args.dir_name == DirName::Title {
+        requests += 1;
+    }
+    if args.verify_count {
+        requests += 1;
+    }
+    let pages = video_count.div_ceil(args.page_size as u64).max(1);
+    let pages = args.max_pages.map_or(pages, |max| pages.min(max.into()));
+    requests += pages;
+    let listed = pages.saturating_mul(args.page_size.into()).min(video_count);
+    requests += listed.div_ceil(VIDEOS_BATCH_SIZE as u64);
+    let resolution = if cached {
+        0
+    } else {
+        resolution_cost(channel_url)
+    };
+    resolution + requests * quota::LIST_COST
+}
+
+/// Looks up how many videos a channel has without resolving it first, which would cost a
+/// search for handles. Custom names are looked up as handles, as most were migrated to
+/// one. Returns `None` if the channel can't be found that way.
+async fn lookup_video_count(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    channel_id: Option<&str>,
+    base_url: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let path = reqwest::Url::parse(channel_url)?.path().to_string();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let filter = match (channel_id, segments.as_slice()) {
+        (Some(channel_id), _) => format!("id={}", channel_id),
+        (None, [handle, ..]) if handle.starts_with('@') => format!("forHandle={}", handle),
+        (None, ["channel", id, ..]) => format!("id={}", id),
+        (None, ["user", username, ..]) => format!("forUsername={}", username),
+        (None, ["c", name, ..]) => format!("forHandle=@{}", name),
+        _ => return Ok(None),
+    };
+    let url = format!(
+        "{}/youtube/v3/channels?part=statistics&{}{}",
+        base_url,
+        filter,
+        credentials.key_param()
+    );
+    let response: ChannelListResponse =
+        parse_json(credentials.get(client, &url).send().await?).await?;
+    Ok(response
+        .items
+        .into_iter()
+        .next()
+        .and_then(|item| item.statistics)
+        .and_then(|statistics| statistics.video_count)
+        .and_then(|count| count.parse().ok()))
+}
+
+/// Writes the estimated quota use of each channel and the whole run to `output`, for
+/// `--estimate-quota`. Returns the estimated total.
+async fn estimate_quota(
+    client: &Client,
+    credentials: Credentials<'_>,
+    args: &Args,
+    output: &mut impl Write,
+    base_url: &str,
+) -> Result<u64, Box<dyn Error>> {
+    let channel_urls = match &args.input_file {
+        Some(path) => parse_channel_list(&fs::read_to_string(path).await?),
+        None => args.channel_url().into_iter().collect(),
+    };
+    let cache = if args.no_cache {
+        None
+    } else {
+        let ttl = Duration::from_secs(args.channel_cache_ttl * 3600);
+        Some(ChannelCache::load(args.output_dir(), ttl).await?)
+    };
+
+    let mut total = 0;
+    for channel_url in &channel_urls {
+        let normalized = normalize_channel_url(channel_url)?;
+        let cached_id = cache.as_ref().and_then(|cache| cache.get(&normalized));
+        let video_count = lookup_video_count(
+            client,
+            credentials,
+            &normalized,
+            cached_id.as_deref(),
+            base_url,
+        )
         .await?;
+        let units = estimate_channel_quota(
+            &normalized,
+            video_count.unwrap_or(0),
+            cached_id.is_some(),
+            args,
+        );
+        match video_count {
+            Some(count) => writeln!(
+                output,
+                "{}: {} units for {} videos",
+                channel_url, units, count
+            )?,
+            None => writeln!(
+                output,
+                "{}: at least {} units, the number of videos is unknown",
+                channel_url, units
+            )?,
+        }
+        total += units;
+    }
+    writeln!(
+        output,
+        "Estimated total: {} units of the {} a project gets per day. The estimate itself used {}.",
+        total,
+        quota::DAILY_QUOTA,
+        channel_urls.len() as u64 * quota::LIST_COST
+    )?;
+    Ok(total)
+}
+
+/// Resolves a channel and writes each of its playlists to `output`, one per line, for
+/// the `list-playlists` subcommand.
+async fn list_playlists(
+    client: &Client,
+    credentials: Credentials<'_>,
+    channel_url: &str,
+    output: &mut impl Write,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    let channel_id =
+        get_channel_id_from_url(client, credentials, channel_url, None, None, base_url).await?;
+    let mut page_token: Option<String> = None;
+    let mut count = 0;
+
+    loop {
+        let mut url = format!(
+            "{}/youtube/v3/playlists?part=snippet,contentDetails&channelId={}{}&maxResults=50",
+            base_url,
+            channel_id,
+            credentials.key_param()
+        );
+        if let Some(token) = &page_token {
+            url.push_str(&format!("&pageToken={}", token));
+        }
+
+        let response: PlaylistListResponse =
+            parse_json(credentials.get(client, &url).send().await?).await?;
+        for playlist in response.items {
+            let videos = playlist
+                .content_details
+                .map_or_else(|| "?".to_string(), |details| details.item_count.to_string());
+            writeln!(
+                output,
+                "{} ({}, {} videos)",
+                playlist.snippet.title, playlist.id, videos
+            )?;
+            count += 1;
+        }
+
+        page_token = response.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    if count == 0 {
+        writeln!(output, "Channel {} has no public playlists.", channel_id)?;
+    }
+    Ok(())
+}
+
+/// Fetches all items from a given playlist, requesting the given comma-separated parts
+/// `page_size` items at a time, and at most `max_pages` pages.
+async fn get_all_playlist_items(
+    client: &Client,
+    credentials: Credentials<'_>,
+    playlist_id: &str,
+    parts: &str,
+    page_size: u8,
+    max_pages: Option<u32>,
+    base_url: &str,
+) -> Result<Vec<PlaylistItem>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let mut page_token: Option<String> = None;
+    let mut empty_pages = 0;
+    let mut pages = 0;
+
+    loop {
+        let mut url = format!(
+            "{}/youtube/v3/playlistItems?part={}&playlistId={}{}&maxResults={}",
+            base_url,
+            parts,
+            playlist_id,
+            credentials.key_param(),
+            page_size
+        );
+
+        if let Some(token) = &page_token {
+            url.push_str(&format!("&pageToken={}", token));
+        }
+
+        let response = credentials.get(client, &url).send().await?;
+        // A channel that has never uploaded has an uploads playlist ID, but the playlist
+        // itself doesn't exist yet.
+        if response.status() == StatusCode::NOT_FOUND && page_token.is_none() {
+            return Ok(items);
+        }
+        let response: PlaylistItemListResponse = parse_json(response).await?;
+        pages += 1;
+
+        if response.items.is_empty() {
+            empty_pages += 1;
+        } else {
+            empty_pages = 0;
+        }
+        items.extend(response.items);
+
+        if response.next_page_token.is_some() && response.next_page_token == page_token {
+            eprintln!(
+                "Warning: the API returned the same page token twice for playlist {}, stopping.",
+                playlist_id
+            );
+            break;
+        }
+        if empty_pages >= MAX_EMPTY_PAGES {
+            eprintln!(
+                "Warning: {} empty pages in a row for playlist {}, stopping.",
+                empty_pages, playlist_id
+            );
+            break;
+        }
+        page_token = response.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+        if max_pages.is_some_and(|max| pages >= max) {
+            eprintln!(
+                "Warning: stopped after {} pages of playlist {} (--max-pages), the list may be truncated.",
+                pages, playlist_id
+            );
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+/// Builds a video's metadata from a playlist item fetched with the `snippet` part.
+/// The thumbnail URL is the largest variant the API lists.
+fn video_metadata(item: &PlaylistItem) -> VideoMetadata {
+    let video_id = item.content_details.video_id.clone();
+    let snippet = item.snippet.as_ref();
+    let thumbnail_url = snippet
+        .and_then(|snippet| {
+            ["maxres", "standard", "high", "medium", "default"]
+                .iter()
+                .find_map(|name| snippet.thumbnails.get(*name))
+        })
+        .map(|thumbnail| thumbnail.url.clone())
+        .unwrap_or_default();
+    // The snippet's date is when the video was added to the playlist, prefer the upload date.
+    let published_at = item
+        .content_details
+        .video_published_at
+        .clone()
+        .or_else(|| snippet.map(|snippet| snippet.published_at.clone()))
+        .unwrap_or_default();
+
+    VideoMetadata {
+        video_id,
+        title: snippet.map(|s| s.title.clone()).unwrap_or_default(),
+        description: snippet.map(|s| s.description.clone()).unwrap_or_default(),
+        published_at,
+        channel_title: snippet.map(|s| s.channel_title.clone()).unwrap_or_default(),
+        thumbnail_url,
+    }
+}
+
+/// Builds an export row from a playlist item fetched with the `snippet` part.
+fn video_record(item: &PlaylistItem, details: Option<&VideoDetails>) -> VideoRecord {
+    let metadata = video_metadata(item);
+    let statistics = details.and_then(|details| details.statistics.as_ref());
+    let count = |count: Option<&String>| count.and_then(|count| count.parse().ok());
+    VideoRecord {
+        video_id: metadata.video_id,
+        title: metadata.title,
+        published_at: metadata.published_at,
+        thumbnail_url: metadata.thumbnail_url,
+        duration_seconds: details
+            .and_then(|details| details.content_details.as_ref())
+            .and_then(|content_details| parse_iso8601_duration(&content_details.duration)),
+        view_count: count(statistics.and_then(|s| s.view_count.as_ref())),
+        like_count: count(statistics.and_then(|s| s.like_count.as_ref())),
+    }
+}
+
+/// Writes the collected export records, merged into the existing export for
+/// `--merge-export`, and returns the path written to.
+async fn write_export(
+    args: &Args,
+    format: ExportFormat,
+    records: &[VideoRecord],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = match &args.export_file {
+        Some(path) => PathBuf::from(path),
+        None => {
+            fs::create_dir_all(args.output_dir()).await?;
+            Path::new(args.output_dir()).join(format!("videos.{}", format.extension()))
+        }
+    };
+    let merged;
+    let records = if args.merge_export {
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "[]".to_string(),
+            Err(e) => return Err(e.into()),
+        };
+        let (existing, skipped) = export::parse_json(&contents).map_err(|e| {
+            DownloaderError::InvalidInput(format!(
+                "Can't merge into {}, which isn't a JSON export: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        if skipped > 0 {
+            eprintln!(
+                "Warning: dropping {} entries without a video ID from {}",
+                skipped,
+                path.display()
+            );
+        }
+        merged = export::merge(existing, records);
+        &merged
+    } else {
+        records
+    };
+    fs::write(&path, export::render(records, format)?).await?;
+    Ok(path)
+}
+
+/// Parses an ISO-8601 duration as returned by the API (e.g., PT1H2M3S) into seconds.
+/// Returns `None` if the string is not a valid duration.
+fn parse_iso8601_duration(duration: &str) -> Option<u64> {
+    let rest = duration.strip_prefix('P')?;
+    let mut total = 0u64;
+    let mut number = String::new();
+    let mut in_time_part = false;
+    let mut seen_component = false;
+
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if !in_time_part && number.is_empty() => in_time_part = true,
+            _ => {
+                let value: u64 = number.parse().ok()?;
+                number.clear();
+                let multiplier = match (in_time_part, c) {
+                    (false, 'W') => 604_800,
+                    (false, 'D') => 86_400,
+                    (true, 'H') => 3_600,
+                    (true, 'M') => 60,
+                    (true, 'S') => 1,
+                    _ => return None,
+                };
+                total += value * multiplier;
+                seen_component = true;
+            }
+        }
+    }
+
+    (number.is_empty() && seen_component).then_some(total)
+}
+
+/// Returns true if a video of the given duration (in seconds) counts as a Short.
+fn is_short(duration_secs: u64) -> bool {
+    duration_secs < SHORTS_MAX_DURATION_SECS
+}
+
+/// Fetches the snippet and content details of the given videos. IDs are sent in batches
+/// of 50 (the API maximum) and the batches are requested concurrently, as many at a time
+/// as `api_semaphore` allows.
+/// Videos the API doesn't return (e.g., deleted ones) are absent from the map.
+async fn fetch_video_details(
+    client: &Client,
+    credentials: Credentials<'_>,
+    video_ids: &[String],
+    api_semaphore: Option<&Semaphore>,
+    base_url: &str,
+) -> Result<HashMap<String, VideoDetails>, Box<dyn Error>> {
+    let requests = video_ids.chunks(VIDEOS_BATCH_SIZE).map(|batch| {
+        let url = format!(
+            "{}/youtube/v3/videos?part=snippet,contentDetails,statistics,status&id={}{}",
+            base_url,
+            batch.join(","),
+            credentials.key_param()
+        );
+        async move {
+            let _permit = match api_semaphore {
+                Some(semaphore) => Some(semaphore.acquire().await?),
+                None => None,
+            };
+            let response = credentials.get(client, &url).send().await?;
+            Ok::<_, Box<dyn Error>>(parse_json::<VideoListResponse>(response).await?)
+        }
+    });
+    let responses = futures::future::try_join_all(requests).await?;
+
+    Ok(responses
+        .into_iter()
+        .flat_map(|response| response.items)
+        .map(|item| (item.id.clone(), item))
+        .collect())
+}
+
+/// Extracts the durations (in seconds) from fetched video details.
+/// Videos that are missing or have an unparseable duration are absent from the map.
+fn video_durations(details: &HashMap<String, VideoDetails>) -> HashMap<String, u64> {
+    let mut durations = HashMap::new();
+
+    for (video_id, item) in details {
+        let Some(content_details) = &item.content_details else {
+            continue;
+        };
+        match parse_iso8601_duration(&content_details.duration) {
+            Some(secs) => {
+                durations.insert(video_id.clone(), secs);
+            }
+            None => eprintln!(
+                "Could not parse duration '{}' for video ID {}",
+                content_details.duration, video_id
+            ),
+        }
+    }
+
+    durations
+}
+
+/// Returns why a video's thumbnail can't be downloaded, or `None` if it's available.
+/// Videos missing from the API response have been deleted or made private.
+fn unavailable_reason(details: Option<&VideoDetails>) -> Option<&'static str> {
+    let Some(details) = details else {
+        return Some("deleted or unavailable");
+    };
+    let status = details.status.as_ref()?;
+    if status.privacy_status == "private" {
+        return Some("private");
+    }
+    match status.upload_status.as_deref() {
+        Some("deleted" | "failed" | "rejected") => Some("deleted or unavailable"),
+        _ => None,
+    }
+}
+
+/// Whether a video is age-restricted, so its thumbnail may be refused or a placeholder.
+/// Members-only videos would belong here too, but the Data API doesn't tell them apart.
+fn is_restricted(details: Option<&VideoDetails>) -> bool {
+    details
+        .and_then(|details| details.content_details.as_ref())
+        .and_then(|content_details| content_details.content_rating.yt_rating.as_deref())
+        == Some("ytAgeRestricted")
+}
+
+/// Splits off age-restricted videos for `--skip-restricted`, logging each one. Returns
+/// the other videos and the number that were skipped.
+fn filter_restricted(
+    video_ids: Vec<String>,
+    details: &HashMap<String, VideoDetails>,
+) -> (Vec<String>, usize) {
+    let (restricted, kept): (Vec<String>, Vec<String>) = video_ids
+        .into_iter()
+        .partition(|video_id| is_restricted(details.get(video_id)));
+    for video_id in &restricted {
+        status!(
+            "{}",
+            progress::skipped(format_args!("Skipping age-restricted video: {}", video_id))
+        );
+    }
+    (kept, restricted.len())
+}
+
+/// Splits off videos that are private or no longer available, logging each one.
+/// Returns the available videos and the number that were skipped.
+fn filter_unavailable(
+    video_ids: Vec<String>,
+    details: &HashMap<String, VideoDetails>,
+) -> (Vec<String>, usize) {
+    let mut skipped = 0;
+    let available = video_ids
+        .into_iter()
+        .filter(|video_id| match unavailable_reason(details.get(video_id)) {
+            Some(reason) => {
+                status!(
+                    "{}",
+                    progress::skipped(format_args!("Skipping {} video: {}", reason, video_id))
+                );
+                skipped += 1;
+                false
+            }
+            None => true,
+        })
+        .collect();
+    (available, skipped)
+}
+
+/// Keeps only the Shorts (`only_shorts == true`) or only the regular videos
+/// (`only_shorts == false`). Videos with an unknown duration are treated as regular videos.
+fn filter_shorts(
+    video_ids: Vec<String>,
+    durations: &HashMap<String, u64>,
+    only_shorts: bool,
+) -> Vec<String> {
+    video_ids
+        .into_iter()
+        .filter(|id| durations.get(id).is_some_and(|&secs| is_short(secs)) == only_shorts)
+        .collect()
+}
+
+/// Returns where a video's files go: `location`, or the `--dir-template` directory below it.
+fn video_location(
+    args: &Args,
+    location: &OutputLocation,
+    details: &HashMap<String, VideoDetails>,
+    video_id: &str,
+) -> OutputLocation {
+    match &args.dir_template {
+        Some(template) => {
+            let snippet = details.get(video_id).and_then(|d| d.snippet.as_ref());
+            location.join(paths::render_dir_template(
+                template,
+                snippet.map(|s| s.published_at.as_str()),
+                snippet
+                    .map(|s| s.channel_title.as_str())
+                    .unwrap_or_default(),
+            ))
+        }
+        None => location.clone(),
+    }
+}
+
+/// Sorts videos by `key`, keeping the order of ties. Videos without the value, such as
+/// those with hidden views or missing details, go last.
+fn sort_videos(video_ids: &mut [String], details: &HashMap<String, VideoDetails>, key: SortBy) {
+    let snippet = |id: &String| details.get(id).and_then(|d| d.snippet.as_ref());
+    match key {
+        SortBy::Date => {
+            // RFC 3339 timestamps in UTC sort chronologically as strings.
+            video_ids.sort_by_cached_key(|id| {
+                Reverse(snippet(id).map(|snippet| snippet.published_at.clone()))
+            })
+        }
+        SortBy::Views => video_ids.sort_by_cached_key(|id| {
+            Reverse(
+                details
+                    .get(id)
+                    .and_then(|d| d.statistics.as_ref())
+                    .and_then(|statistics| statistics.view_count.as_ref())
+                    .and_then(|count| count.parse::<u64>().ok()),
+            )
+        }),
+        SortBy::Duration => video_ids.sort_by_cached_key(|id| {
+            Reverse(
+                details
+                    .get(id)
+                    .and_then(|d| d.content_details.as_ref())
+                    .and_then(|content_details| parse_iso8601_duration(&content_details.duration)),
+            )
+        }),
+        SortBy::Title => video_ids.sort_by_cached_key(|id| match snippet(id) {
+            Some(snippet) => (false, snippet.title.to_lowercase()),
+            None => (true, String::new()),
+        }),
+    }
+}
+
+/// Applies `--order`, `--sort-by`, `--shuffle`, `--range` and `--limit` to a channel's
+/// video list, which is newest first.
+fn select_videos(
+    mut video_ids: Vec<String>,
+    details: &HashMap<String, VideoDetails>,
+    args: &Args,
+) -> Result<Vec<String>, DownloaderError> {
+    if args.order == Order::Oldest {
+        video_ids.reverse();
+    }
+    if let Some(key) = args.sort_by {
+        sort_videos(&mut video_ids, details, key);
+    }
+    if args.shuffle {
+        let seed = args.seed.unwrap_or_else(|| {
+            let seed = shuffle::random_seed();
+            status!("Shuffling videos with --seed {}", seed);
+            seed
+        });
+        shuffle::shuffle(&mut video_ids, seed);
+    }
+    if let Some(range) = args.range {
+        let positions = range
+            .positions(video_ids.len(), args.clamp)
+            .map_err(DownloaderError::InvalidInput)?;
+        video_ids.truncate(positions.end);
+        video_ids.drain(..positions.start);
+    }
+    if let Some(limit) = args.limit {
+        video_ids.truncate(limit);
+    }
+    Ok(video_ids)
+}
+
+/// Keeps the videos whose title matches `filter`. Videos without a known title are dropped.
+fn filter_by_title(
+    video_ids: Vec<String>,
+    details: &HashMap<String, VideoDetails>,
+    filter: &TitleFilter,
+) -> Vec<String> {
+    video_ids
+        .into_iter()
+        .filter(|id| {
+            details
+                .get(id)
+                .and_then(|d| d.snippet.as_ref())
+                .is_some_and(|snippet| filter.matches(&snippet.title))
+        })
+        .collect()
+}
+
+/// Parses the contents of an input file into a list of channel URLs.
+/// Blank lines and lines starting with '#' are ignored.
+fn parse_channel_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Derives the name of a channel's subdirectory for `--dir-name`. `title` is only used,
+/// and only needs to be fetched, for `DirName::Title`.
+fn channel_dir_name(
+    dir_name: DirName,
+    channel_url: &str,
+    channel_id: &str,
+    title: Option<&str>,
+) -> String {
+    let name = match dir_name {
+        DirName::Id => channel_id.to_string(),
+        DirName::Title => format!("{} ({})", title.unwrap_or_default(), channel_id),
+        DirName::Handle => reqwest::Url::parse(channel_url)
+            .ok()
+            .and_then(|url| {
+                url.path_segments()?
+                    .find(|segment| !segment.is_empty())?
+                    .strip_prefix('@')
+                    .map(String::from)
+            })
+            .unwrap_or_else(|| channel_id.to_string()),
+    };
+    paths::sanitize_path_component(&name)
+}
+
+/// Removes the thumbnails at `location` of videos missing from the channel's current list,
+/// for `--prune`. An empty list is more likely a hiccup than a channel that deleted
+/// everything, so nothing is pruned then.
+async fn prune_channel(
+    args: &Args,
+    location: &OutputLocation,
+    channel_video_ids: &HashSet<String>,
+) -> Result<(), Box<dyn Error>> {
+    if channel_video_ids.is_empty() {
+        status!("Not pruning, as the channel lists no videos.");
+        return Ok(());
+    }
+    let current = channel_video_ids.iter().map(String::as_str).collect();
+    let extension = if args.compress {
+        format!("{}.gz", args.format.extension())
+    } else {
+        args.format.extension().to_string()
+    };
+    let pruned = prune::prune(location, &extension, &current, args.prune_hard).await?;
+    if args.prune_hard {
+        status!("Pruned {} thumbnails of removed videos.", pruned);
+    } else {
+        status!(
+            "Pruned {} thumbnails of removed videos into {}.",
+            pruned,
+            location.dir().join(prune::TRASH_DIR).display()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the path a video's thumbnail is saved to.
+fn thumbnail_path(location: &OutputLocation, video_id: &str, format: OutputFormat) -> PathBuf {
+    paths::output_path_for(video_id, format.extension(), location)
+}
+
+/// Returns the temporary path a thumbnail is written to before being renamed into place.
+fn partial_path(file_path: &Path) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+/// Returns the path next to a `.part` file that records which thumbnail it holds.
+fn resume_info_path(part_path: &Path) -> PathBuf {
+    let mut path = part_path.as_os_str().to_owned();
+    path.push(".resume");
+    PathBuf::from(path)
+}
+
+/// The validator a response can be resumed with: its ETag if it's a strong one, otherwise
+/// its Last-Modified date.
+fn resume_validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+        .map(String::from)
+}
+
+/// Records that the `.part` file being written holds the thumbnail at `url` as of
+/// `validator`. Without a validator there's no telling whether a later answer is the same
+/// thumbnail, so the file won't be resumed.
+async fn save_resume_info(
+    resume_path: &Path,
+    url: &str,
+    validator: Option<&str>,
+) -> std::io::Result<()> {
+    match validator {
+        Some(validator) => fs::write(resume_path, format!("{}\n{}\n", url, validator)).await,
+        None => match fs::remove_file(resume_path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// The validator a `.part` file was saved with, if it holds the thumbnail at `url`.
+async fn saved_resume_validator(resume_path: &Path, url: &str) -> Option<String> {
+    let info = fs::read_to_string(resume_path).await.ok()?;
+    let (saved_url, validator) = info.trim_end().split_once('\n')?;
+    (saved_url == url).then(|| validator.to_string())
+}
+
+/// Whether a 206 answer to a resumed download continues the `.part` file: its range starts
+/// where the file ends, and it's the same version of the thumbnail the file came from.
+fn continues_partial(headers: &HeaderMap, resume_from: u64, validator: &str) -> bool {
+    let start = headers
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes "))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.parse::<u64>().ok());
+    start == Some(resume_from)
+        && resume_validator(headers).is_none_or(|current| current == validator)
+}
+
+/// Deletes leftover `.part` files in the output directory and the directories under it,
+/// like those of `--dir-template`, and returns how many were removed. Symlinks aren't
+/// followed, so nothing outside the output directory is touched.
+async fn remove_partial_files(output_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut removed = 0;
+    let mut dirs = vec![output_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let extension = path.extension().and_then(|ext| ext.to_str());
+            if matches!(extension, Some("part" | "resume")) && file_type.is_file() {
+                fs::remove_file(&path).await?;
+                removed += usize::from(extension == Some("part"));
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Sends a HEAD request for a thumbnail and reports whether it matches the file on disk:
+/// either its ETag equals `etag`, the one the file was saved with, or (if `compare_size`)
+/// its `Content-Length` equals the file's size. A failed request, or nothing to compare,
+/// counts as a mismatch.
+async fn remote_matches_local(
+    client: &Client,
+    thumbnail_url: &str,
+    file_path: &Path,
+    etag: Option<&str>,
+    compare_size: bool,
+) -> bool {
+    let Ok(metadata) = fs::metadata(file_path).await else {
+        return false;
+    };
+    let response = match client.head(thumbnail_url).send().await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return false,
+    };
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    if let (Some(local), Some(remote)) = (etag, header(ETAG)) {
+        return local == remote;
+    }
+    // `Response::content_length` reflects the (empty) body of a HEAD response, so read
+    // the header itself.
+    compare_size
+        && header(CONTENT_LENGTH)
+            .and_then(|value| value.parse::<u64>().ok())
+            .is_some_and(|len| len == metadata.len())
+}
+
+/// Checks whether the file at `file_path` was modified after `last_modified`, an HTTP date.
+/// Returns false if there's no file or either time is unknown.
+async fn local_is_newer(file_path: &Path, last_modified: Option<&str>) -> bool {
+    let Some(remote) = last_modified.and_then(|date| httpdate::parse_http_date(date).ok()) else {
+        return false;
+    };
+    fs::metadata(file_path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|local| local > remote)
+}
+
+/// What happened to a video's thumbnail.
+#[derive(Debug, Clone, PartialEq)]
+enum DownloadOutcome {
+    /// The thumbnail was saved to `path`. `bytes` is how much was written there, which is
+    /// nothing when `--dedup` linked it to an identical thumbnail instead.
+    Downloaded {
+        resolution: Resolution,
+        bytes: u64,
+        path: PathBuf,
+    },
+    /// The thumbnail at `path` was already up to date. The resolution is unknown when
+    /// `--overwrite never` kept the file without checking it.
+    Skipped {
+        resolution: Option<Resolution>,
+        path: PathBuf,
+    },
+    /// No usable variant of the thumbnail exists.
+    NotFound,
+    /// `--max-total-size` was used up before the thumbnail was requested or saved.
+    OverBudget,
+    /// `--max-duration` ran out before the thumbnail was requested.
+    OutOfTime,
+}
+
+impl DownloadOutcome {
+    /// The resolution that was saved or kept, if any.
+    fn resolution(&self) -> Option<Resolution> {
+        match self {
+            DownloadOutcome::Downloaded { resolution, .. } => Some(*resolution),
+            DownloadOutcome::Skipped { resolution, .. } => *resolution,
+            DownloadOutcome::NotFound
+            | DownloadOutcome::OverBudget
+            | DownloadOutcome::OutOfTime => None,
+        }
+    }
+
+    /// Where the thumbnail was saved or kept, if anywhere.
+    fn path(&self) -> Option<&Path> {
+        match self {
+            DownloadOutcome::Downloaded { path, .. } | DownloadOutcome::Skipped { path, .. } => {
+                Some(path)
+            }
+            DownloadOutcome::NotFound
+            | DownloadOutcome::OverBudget
+            | DownloadOutcome::OutOfTime => None,
+        }
+    }
+}
+
+/// Whether a thumbnail server's answer means it's refusing or failing requests in general,
+/// unlike a 404 for a variant that doesn't exist.
+fn is_blocking_status(status: StatusCode) -> bool {
+    status == StatusCode::FORBIDDEN
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// Removes a `.part` file and its `.resume` record if dropped before `disarm`, which
+/// happens to the download writing them when `--per-download-timeout` cancels it.
+struct PartialFileGuard {
+    part_path: Option<PathBuf>,
+}
+
+impl PartialFileGuard {
+    fn new(part_path: PathBuf) -> Self {
+        PartialFileGuard {
+            part_path: Some(part_path),
+        }
+    }
+
+    /// Keeps the files, once the download has finished or failed on its own.
+    fn disarm(&mut self) {
+        self.part_path = None;
+    }
+}
+
+impl Drop for PartialFileGuard {
+    fn drop(&mut self) {
+        if let Some(part_path) = self.part_path.take() {
+            let _ = std::fs::remove_file(resume_info_path(&part_path));
+            let _ = std::fs::remove_file(part_path);
+        }
+    }
+}
+
+/// Downloads one variant of a video thumbnail from the given URL into the output directory,
+/// converting it to the requested format. Returns `NotFound` if the variant doesn't exist,
+/// is a placeholder image or is smaller than `--min-width`/`--min-height`.
+async fn download_variant(
+    client: &Client,
+    video_id: &str,
+    resolution: Resolution,
+    thumbnail_url: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+) -> Result<DownloadOutcome, DownloaderError> {
+    // A cancelled download stops wherever it was, so its temporary file is removed here,
+    // at whichever path it was writing. One that fails is kept to be resumed.
+    let mut guard = PartialFileGuard::new(partial_path(
+        &options.variant_path(location, video_id, resolution),
+    ));
+    let result = fetch_variant(
+        client,
+        video_id,
+        resolution,
+        thumbnail_url,
+        location,
+        options,
+    )
+    .await;
+    guard.disarm();
+    result
+}
+
+/// Does the work of `download_variant`.
+async fn fetch_variant(
+    client: &Client,
+    video_id: &str,
+    resolution: Resolution,
+    thumbnail_url: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+) -> Result<DownloadOutcome, DownloaderError> {
+    let file_path = options.variant_path(location, video_id, resolution);
+    // Each variant saved by `--all-resolutions` has its own validators.
+    let cache_key = if options.all_resolutions {
+        format!("{}/{}", video_id, resolution.short_name())
+    } else {
+        video_id.to_string()
+    };
+
+    if options.overwrite == Overwrite::IfDifferent {
+        let etag = options
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(&cache_key))
+            .and_then(|entry| entry.etag);
+        // Converted thumbnails never match the remote size, so only compare unconverted ones.
+        let compare_size = !options.rewrites_images();
+        if remote_matches_local(
+            client,
+            thumbnail_url,
+            &file_path,
+            etag.as_deref(),
+            compare_size,
+        )
+        .await
+        {
+            status!(
+                "{}",
+                progress::skipped(format_args!(
+                    "Thumbnail already up to date for video ID: {}",
+                    video_id
+                ))
+            );
+            return Ok(DownloadOutcome::Skipped {
+                resolution: Some(resolution),
+                path: file_path,
+            });
+        }
+    }
+
+    let mut request = client.get(thumbnail_url);
+    // Only revalidate if we still have the file the validators belong to.
+    if let Some(cache) = &options.cache {
+        if let Some(entry) = cache.get(&cache_key).filter(|_| file_path.exists()) {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+    }
+    // A thumbnail left half-written by a dropped connection is continued where it stopped
+    // instead of being fetched again from the start, unless it's rewritten, or the file
+    // holds another variant or a version of the thumbnail the server no longer has.
+    let part_path = partial_path(&file_path);
+    let resume_path = resume_info_path(&part_path);
+    let validator = if !options.rewrites_images() {
+        saved_resume_validator(&resume_path, thumbnail_url).await
+    } else {
+        None
+    };
+    let mut resume_from = match &validator {
+        Some(_) => fs::metadata(&part_path)
+            .await
+            .map_or(0, |metadata| metadata.len()),
+        None => 0,
+    };
+    tracing::debug!(url = thumbnail_url, resume_from, "requesting thumbnail");
+    if let Some(breaker) = &options.breaker {
+        breaker.acquire().await;
+    }
+    // Without --concurrency every download task starts at once, so the limits are checked
+    // here, right before each request, rather than when the tasks are started.
+    if options.out_of_time() {
+        return Ok(DownloadOutcome::OutOfTime);
+    }
+    if options
+        .budget
+        .as_ref()
+        .is_some_and(|budget| budget.exhausted())
+    {
+        return Ok(DownloadOutcome::OverBudget);
+    }
+    let request_started = Instant::now();
+    let sent = async {
+        Ok::<_, DownloaderError>(match (request.try_clone(), &validator) {
+            (Some(ranged), Some(validator)) if resume_from > 0 => {
+                // If-Range makes the server send the whole thumbnail if it changed since.
+                let response = ranged
+                    .header(RANGE, format!("bytes={}-", resume_from))
+                    .header(IF_RANGE, validator)
+                    .send()
+                    .await?;
+                let status = response.status();
+                if status == StatusCode::RANGE_NOT_SATISFIABLE
+                    || (status == StatusCode::PARTIAL_CONTENT
+                        && !continues_partial(response.headers(), resume_from, validator))
+                {
+                    // The temporary file is as long as the thumbnail or longer, or the server
+                    // sent some other part or version of it, so it can't be trusted: start
+                    // over.
+                    fs::remove_file(&part_path).await?;
+                    resume_from = 0;
+                    request.send().await?
+                } else {
+                    response
+                }
+            }
+            _ => request.send().await?,
+        })
+    }
+    .await;
+    let failed = sent
+        .as_ref()
+        .map_or(true, |response| is_blocking_status(response.status()));
+    if let Some(adaptive) = &options.adaptive {
+        adaptive.record(!failed, request_started.elapsed());
+    }
+    if let Some(breaker) = &options.breaker {
+        if breaker.record(!failed) {
+            notice!(
+                "Too many thumbnail requests are failing, pausing downloads for {} seconds.",
+                breaker.cooldown().as_secs()
+            );
+        }
+    }
+    let mut response = sent?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        status!("Thumbnail unchanged for video ID: {}", video_id);
+        return Ok(DownloadOutcome::Skipped {
+            resolution: Some(resolution),
+            path: file_path,
+        });
+    }
+    if !response.status().is_success() {
+        // Variants that haven't been generated for a video return a 404.
+        tracing::debug!(
+            url = thumbnail_url,
+            status = %response.status(),
+            "thumbnail variant not available"
+        );
+        return Ok(DownloadOutcome::NotFound);
+    }
+    // Whatever a redirect away from the thumbnail servers leads to, it isn't the thumbnail.
+    let requested_host = reqwest::Url::parse(thumbnail_url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from));
+    if let Some(host) = response.url().host_str() {
+        if requested_host.as_deref() != Some(host) && !thumbnail::is_image_host(host) {
+            eprintln!(
+                "Thumbnail at {} for video ID {} redirected to {}, which isn't a thumbnail server.",
+                thumbnail_url,
+                video_id,
+                response.url()
+            );
+            return Ok(DownloadOutcome::NotFound);
+        }
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+    let cache_entry = CacheEntry {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    if options.no_overwrite_newer
+        && local_is_newer(&file_path, cache_entry.last_modified.as_deref()).await
+    {
+        status!(
+            "{}",
+            progress::skipped(format_args!(
+                "Keeping thumbnail for video ID {}, which was modified after YouTube's copy",
+                video_id
+            ))
+        );
+        return Ok(DownloadOutcome::Skipped {
+            resolution: Some(resolution),
+            path: file_path,
+        });
+    }
+    // Servers that ignore the range send the whole thumbnail with a 200 instead.
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        resume_from = 0;
+    }
+    // Write to a temporary file and only move it into place once it's complete,
+    // so an interrupted download never leaves a truncated thumbnail behind.
+    let expected_len = response.content_length();
+    let mut received = 0u64;
+    let written;
+    let mut hasher = Sha256::new();
+    // The hash of the image as served, which placeholder signatures are matched against.
+    let served_hash: ContentHash;
+    // The image's size as served, if `--resize` changed it; otherwise it's read from the file.
+    let mut served_dimensions = None;
+    if !options.rewrites_images() {
+        // Thumbnails that aren't converted are saved as-is, so stream them straight to disk.
+        let mut file = if resume_from > 0 {
+            status!(
+                "Resuming thumbnail for video ID {} from byte {}",
+                video_id,
+                resume_from
+            );
+            // Hash what's already there so --dedup sees the whole thumbnail.
+            hasher.update(fs::read(&part_path).await?);
+            OpenOptions::new().append(true).open(&part_path).await?
+        } else {
+            let validator = resume_validator(response.headers());
+            save_resume_info(&resume_path, thumbnail_url, validator.as_deref()).await?;
+            File::create(&part_path).await?
+        };
+        while let Some(chunk) = response.chunk().await? {
+            if let Some(limiter) = &options.rate_limiter {
+                limiter.acquire(chunk.len()).await;
+            }
+            options
+                .received_bytes
+                .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            received += chunk.len() as u64;
+        }
+        file.flush().await?;
+        if options.fsync {
+            file.sync_all().await?;
+        }
+        written = resume_from + received;
+        served_hash = hasher.clone().finalize().into();
+    } else {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if let Some(limiter) = &options.rate_limiter {
+                limiter.acquire(chunk.len()).await;
+            }
+            options
+                .received_bytes
+                .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            bytes.extend_from_slice(&chunk);
+        }
+        received = bytes.len() as u64;
+        served_hash = Sha256::digest(&bytes).into();
+        let embedded = options
+            .embed_titles
+            .as_ref()
+            .filter(|_| options.format == OutputFormat::Jpg)
+            .map(|titles| EmbeddedMetadata {
+                video_id: video_id.to_string(),
+                title: titles.get(video_id).cloned().unwrap_or_default(),
+                source_url: thumbnail_url.to_string(),
+            });
+        // Decoding and encoding is CPU-bound, keep it off the async workers.
+        let (source, format, quality) = (options.source_format, options.format, options.quality);
+        let (resize, resize_mode) = (options.resize, options.resize_mode);
+        let (bytes, dimensions) = tokio::task::spawn_blocking(move || {
+            let mut bytes = convert::convert_image(bytes, source, format, quality)?;
+            let mut dimensions = None;
+            if let Some(size) = resize {
+                let (resized, original) =
+                    convert::resize_image(&bytes, format, size, resize_mode, quality)?;
+                (bytes, dimensions) = (resized, Some((original.width, original.height)));
+            }
+            let bytes = match embedded {
+                Some(metadata) => embed::embed_metadata(&bytes, &metadata)?,
+                None => bytes,
+            };
+            Ok((bytes, dimensions))
+        })
+        .await?
+        .map_err(DownloaderError::Image)?;
+        served_dimensions = dimensions;
+        hasher.update(&bytes);
+        let mut file = File::create(&part_path).await?;
+        file.write_all(&bytes).await?;
+        if options.fsync {
+            file.sync_all().await?;
+        }
+        written = bytes.len() as u64;
+    }
+    if received == 0 || expected_len.is_some_and(|len| len != received) {
+        return Err(DownloaderError::Incomplete {
+            received,
+            expected: expected_len,
+        });
+    }
+    // The temporary file is complete, so there's nothing left to resume.
+    save_resume_info(&resume_path, thumbnail_url, None).await?;
+    if !options.placeholders.is_empty() {
+        let path = part_path.clone();
+        // Images that can't be read can still be saved, they just aren't placeholders.
+        let dimensions = match served_dimensions {
+            Some(dimensions) => Some(dimensions),
+            None => tokio::task::spawn_blocking(move || convert::image_dimensions(&path))
+                .await?
+                .ok(),
+        };
+        if options
+            .placeholders
+            .iter()
+            .any(|signature| signature.matches(resolution, &served_hash, dimensions))
+        {
+            fs::remove_file(&part_path).await?;
+            tracing::debug!(url = thumbnail_url, "thumbnail is a placeholder");
+            notice!(
+                "Thumbnail at {} for video ID {} is a placeholder image, discarding it.",
+                thumbnail_url,
+                video_id
+            );
+            return Ok(DownloadOutcome::NotFound);
+        }
+    }
+    if options.min_width.is_some() || options.min_height.is_some() {
+        let path = part_path.clone();
+        let (width, height) = match served_dimensions {
+            Some(dimensions) => dimensions,
+            None => tokio::task::spawn_blocking(move || convert::image_dimensions(&path))
+                .await?
+                .map_err(DownloaderError::Image)?,
+        };
+        if options.min_width.is_some_and(|min| width < min)
+            || options.min_height.is_some_and(|min| height < min)
+        {
+            fs::remove_file(&part_path).await?;
+            notice!(
+                "Thumbnail at {} for video ID {} is only {}x{}, discarding it.",
+                thumbnail_url,
+                video_id,
+                width,
+                height
+            );
+            return Ok(DownloadOutcome::NotFound);
+        }
+    }
+    let hash = hasher.finalize().into();
+    let duplicate_of = options
+        .dedup
+        .as_ref()
+        .and_then(|dedup| dedup.claim(hash, &file_path));
+    let outcome = if let Some(original) = duplicate_of {
+        fs::remove_file(&part_path).await?;
+        let link = dedup::link_duplicate(&original, &file_path).await?;
+        status!(
+            "Thumbnail for video ID {} is identical to {}, linked at {}",
+            video_id,
+            original.display(),
+            link.display()
+        );
+        DownloadOutcome::Downloaded {
+            resolution,
+            bytes: 0,
+            path: link,
+        }
+    } else {
+        // Downloads running at once may all have started before the budget ran out; only
+        // those that finish before it did are kept.
+        if options
+            .budget
+            .as_ref()
+            .is_some_and(|budget| !budget.spend(written))
+        {
+            fs::remove_file(&part_path).await?;
+            return Ok(DownloadOutcome::OverBudget);
+        }
+        if options.compress {
+            let (part, file, fsync) = (part_path.clone(), file_path.clone(), options.fsync);
+            tokio::task::spawn_blocking(move || compress::gzip_file(&part, &file, fsync)).await??;
+            fs::remove_file(&part_path).await?;
+        } else {
+            fs::rename(&part_path, &file_path).await?;
+        }
+        if options.fsync {
+            paths::sync_dir(location.dir()).await?;
+        }
+        if let Some(checksums) = &options.checksums {
+            checksums.record(&file_path, &hash);
+        }
+        DownloadOutcome::Downloaded {
+            resolution,
+            bytes: written,
+            path: file_path,
+        }
+    };
+    if let Some(cache) = &options.cache {
+        if cache_entry == CacheEntry::default() {
+            cache.remove(&cache_key);
+        } else {
+            cache.insert(&cache_key, cache_entry);
+        }
+    }
+    tracing::debug!(url = thumbnail_url, bytes = written, "saved thumbnail");
+    status!(
+        "{}",
+        progress::success(format_args!(
+            "Downloaded thumbnail for video ID: {}",
+            video_id
+        ))
+    );
+    Ok(outcome)
+}
+
+/// A video whose preferred thumbnail wasn't there on the first pass of `--retry-404-later`.
+struct DeferredVideo {
+    video_id: String,
+    location: OutputLocation,
+    metadata: Option<VideoMetadata>,
+}
+
+/// The result of a download task: whether the video got a thumbnail, that it is retried
+/// at the end of the run, or that `--max-total-size` or `--max-duration` stopped it.
+enum FirstPass {
+    Finished(bool),
+    Deferred(Box<DeferredVideo>),
+    OverBudget,
+    OutOfTime,
+}
+
+impl FirstPass {
+    /// The result of a download that `--max-total-size` or `--max-duration` stopped, if
+    /// they did.
+    fn stopped(outcome: &DownloadOutcome) -> Option<Self> {
+        match outcome {
+            DownloadOutcome::OverBudget => Some(FirstPass::OverBudget),
+            DownloadOutcome::OutOfTime => Some(FirstPass::OutOfTime),
+            _ => None,
+        }
+    }
+}
+
+/// Like `download_thumbnail`, but cancelled after `options.download_timeout`, retried with
+/// backoff after network errors when `--retries` is given, and logging errors and reporting
+/// them as `NotFound`.
+async fn try_download_thumbnail(
+    client: &Client,
+    video_id: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    thumbnail_base_url: &str,
+) -> DownloadOutcome {
+    let mut attempt = 0;
+    loop {
+        let result = download_thumbnail_with_timeout(
+            client,
+            video_id,
+            location,
+            options,
+            thumbnail_base_url,
+        )
+        .await;
+        let e = match result {
+            Ok(outcome) => return outcome,
+            Err(e) => e,
+        };
+        eprintln!(
+            "{}",
+            progress::failure(format_args!(
+                "Error downloading thumbnail for {}: {}",
+                video_id, e
+            ))
+        );
+        tracing::warn!(error = %e, attempt, "thumbnail download failed");
+        match &options.backoff {
+            Some(backoff) if attempt < backoff.retries() && e.is_transient() => {
+                let delay = backoff.delay(attempt);
+                tracing::debug!(?delay, "retrying thumbnail download");
+                notice!(
+                    "Retrying video ID {} in {:.1}s ({} of {})",
+                    video_id,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    backoff.retries()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            _ => return DownloadOutcome::NotFound,
+        }
+    }
+}
+
+/// Runs `download_thumbnail`, cancelling it after `options.download_timeout`.
+async fn download_thumbnail_with_timeout(
+    client: &Client,
+    video_id: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    thumbnail_base_url: &str,
+) -> Result<DownloadOutcome, DownloaderError> {
+    let download = download_thumbnail(client, video_id, location, options, thumbnail_base_url);
+    match options.download_timeout {
+        Some(limit) => match tokio::time::timeout(limit, download).await {
+            Ok(result) => result,
+            // The cancelled download removed its temporary file as it was dropped.
+            Err(_) => Err(DownloaderError::TimedOut(limit)),
+        },
+        None => download.await,
+    }
+}
+
+/// Records the final outcome of a video: reports it, marks it in the state file, writes
+/// its metadata sidecar and fetches its storyboard.
+/// Returns whether the video has a thumbnail now.
+async fn record_outcome(
+    client: &Client,
+    video_id: &str,
+    outcome: &DownloadOutcome,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    metadata: Option<VideoMetadata>,
+    thumbnail_base_url: &str,
+) -> bool {
+    if *outcome == DownloadOutcome::NotFound {
+        eprintln!(
+            "{}",
+            progress::failure(format_args!(
+                "No thumbnail available for video ID {}",
+                video_id
+            ))
+        );
+    }
+    if let Some(progress) = &options.progress {
+        let video_id = video_id.to_string();
+        progress.report(match outcome {
+            DownloadOutcome::Downloaded { bytes, .. } => ProgressEvent::Downloaded {
+                video_id,
+                bytes: *bytes,
+            },
+            DownloadOutcome::Skipped { .. } => ProgressEvent::Skipped { video_id },
+            DownloadOutcome::NotFound
+            | DownloadOutcome::OverBudget
+            | DownloadOutcome::OutOfTime => ProgressEvent::Failed { video_id },
+        });
+    }
+    let downloaded = *outcome != DownloadOutcome::NotFound;
+    if let Some(state) = options.state.as_ref().filter(|_| downloaded) {
+        state.mark_downloaded(video_id);
+    }
+    if let (Some(mut metadata), Some(resolution)) = (metadata, outcome.resolution()) {
+        metadata.thumbnail_url = thumbnail::thumbnail_url(video_id, resolution, thumbnail_base_url);
+        if let Err(e) = metadata::write_sidecar(location, &metadata, options.compress).await {
+            eprintln!("Error writing metadata for {}: {}", video_id, e);
+        }
+    }
+    if let (Some(watch_base_url), DownloadOutcome::Downloaded { .. }) =
+        (&options.storyboards, outcome)
+    {
+        // Storyboards are best-effort, so missing one doesn't fail the video.
+        match storyboard::download(client, video_id, watch_base_url, location).await {
+            Ok(sheets) => tracing::debug!(sheets = sheets.len(), "saved storyboard"),
+            Err(e) => eprintln!(
+                "Warning: couldn't download the storyboard for {}: {}",
+                video_id, e
+            ),
+        }
+    }
+    if let (Some(hook), DownloadOutcome::Downloaded { path, .. }) = (&options.on_download, outcome)
+    {
+        // The thumbnail is saved either way, so a failing hook only gets reported.
+        match hook.run(path, video_id).await {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("--on-download command for {} failed: {}", video_id, status),
+            Err(e) => eprintln!(
+                "Error running --on-download command for {}: {}",
+                video_id, e
+            ),
+        }
+    }
+    if let Some(report) = &options.resolution_report {
+        let path = outcome.path().map(Path::to_path_buf);
+        let (dimensions, bytes) = match &path {
+            Some(path) => {
+                let image = path.clone();
+                let dimensions =
+                    tokio::task::spawn_blocking(move || convert::image_dimensions(&image).ok())
+                        .await
+                        .ok()
+                        .flatten();
+                let bytes = fs::metadata(path).await.ok().map(|metadata| metadata.len());
+                (dimensions, bytes)
+            }
+            None => (None, None),
+        };
+        report.record(ResolutionRow {
+            video_id: video_id.to_string(),
+            resolution: outcome.resolution(),
+            dimensions,
+            bytes,
+        });
+    }
+    if let (Some(sink), DownloadOutcome::Downloaded { path, .. }) = (&options.sink, outcome) {
+        if let Err(e) = sink.put(path).await {
+            eprintln!(
+                "{}",
+                progress::failure(format_args!(
+                    "Error uploading the thumbnail for {}: {}",
+                    video_id, e
+                ))
+            );
+            return false;
+        }
+    }
+    if let (Some(archive), Some(path)) = (&options.archive, outcome.path()) {
+        let (archive, path) = (archive.clone(), path.to_path_buf());
+        let archive_only = options.archive_only;
+        let archived = tokio::task::spawn_blocking(move || {
+            archive.add(&path)?;
+            if archive_only {
+                std::fs::remove_file(&path)?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = archived {
+            eprintln!(
+                "Error adding the thumbnail for {} to the archive: {}",
+                video_id, e
+            );
+            return false;
+        }
+    }
+    downloaded
+}
+
+/// Downloads the largest thumbnail available for a video, falling back through smaller
+/// resolutions when a variant doesn't exist. With `options.resolution` set, only that
+/// variant is tried.
+async fn download_thumbnail(
+    client: &Client,
+    video_id: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    thumbnail_base_url: &str,
+) -> Result<DownloadOutcome, DownloaderError> {
+    if options.all_resolutions {
+        return download_all_variants(client, video_id, location, options, thumbnail_base_url)
+            .await;
+    }
+    if options.overwrite == Overwrite::Never {
+        let path = options.thumbnail_path(location, video_id);
+        if fs::symlink_metadata(&path).await.is_ok() {
+            status!(
+                "{}",
+                progress::skipped(format_args!(
+                    "Keeping existing thumbnail for video ID: {}",
+                    video_id
+                ))
+            );
+            return Ok(DownloadOutcome::Skipped {
+                resolution: None,
+                path,
+            });
+        }
+    }
+    let resolutions = match &options.resolution {
+        Some(resolution) => std::slice::from_ref(resolution),
+        None => &Resolution::ALL[..],
+    };
+    for &resolution in resolutions {
+        let url = thumbnail::thumbnail_url(video_id, resolution, thumbnail_base_url);
+        match download_variant(client, video_id, resolution, &url, location, options).await? {
+            DownloadOutcome::NotFound => continue,
+            outcome => return Ok(outcome),
+        }
+    }
+    if options.try_webp {
+        tracing::debug!("no JPEG thumbnail, trying WebP");
+        let webp_options = DownloadOptions {
+            source_format: OutputFormat::Webp,
+            // Keep the WebP as it is rather than re-encoding it as a JPEG.
+            format: match options.format {
+                OutputFormat::Jpg => OutputFormat::Webp,
+                format => format,
+            },
+            ..options.clone()
+        };
+        for &resolution in resolutions {
+            let url = thumbnail::webp_thumbnail_url(video_id, resolution, thumbnail_base_url);
+            match download_variant(client, video_id, resolution, &url, location, &webp_options)
+                .await?
+            {
+                DownloadOutcome::NotFound => continue,
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+    Ok(DownloadOutcome::NotFound)
+}
+
+/// Downloads every variant of a video's thumbnail that exists, for `--all-resolutions`.
+/// Returns the largest one's outcome with the bytes of all of them, or `NotFound` if there
+/// were none.
+async fn download_all_variants(
+    client: &Client,
+    video_id: &str,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    thumbnail_base_url: &str,
+) -> Result<DownloadOutcome, DownloaderError> {
+    let mut largest = None;
+    let mut total_bytes = 0;
+    for resolution in Resolution::ALL {
+        let path = options.variant_path(location, video_id, resolution);
+        if options.overwrite == Overwrite::Never && fs::symlink_metadata(&path).await.is_ok() {
+            largest.get_or_insert(DownloadOutcome::Skipped {
+                resolution: Some(resolution),
+                path,
+            });
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let url = thumbnail::thumbnail_url(video_id, resolution, thumbnail_base_url);
+        let outcome =
+            download_variant(client, video_id, resolution, &url, location, options).await?;
+        match outcome {
+            DownloadOutcome::NotFound => {}
+            DownloadOutcome::Downloaded { bytes, .. } => {
+                total_bytes += bytes;
+                if !matches!(largest, Some(DownloadOutcome::Downloaded { .. })) {
+                    largest = Some(outcome);
+                }
+            }
+            DownloadOutcome::Skipped { .. } => {
+                largest.get_or_insert(outcome);
+            }
+            DownloadOutcome::OverBudget | DownloadOutcome::OutOfTime => {
+                // Keep what's saved so far, but say why nothing was if nothing was.
+                largest.get_or_insert(outcome);
+                break;
+            }
+        }
+    }
+    Ok(match largest {
+        Some(DownloadOutcome::Downloaded {
+            resolution, path, ..
+        }) => DownloadOutcome::Downloaded {
+            resolution,
+            bytes: total_bytes,
+            path,
+        },
+        Some(outcome) => outcome,
+        None => DownloadOutcome::NotFound,
+    })
+}
+
+/// Resolves a channel, collects its videos and downloads their thumbnails.
+/// When processing an input file, thumbnails go into a subdirectory named after the channel.
+async fn process_channel(
+    client: &Client,
+    credentials: Credentials<'_>,
+    args: &Args,
+    channel_url: &str,
+    options: &DownloadOptions,
+    base_url: &str,
+) -> Result<Summary, Box<dyn Error>> {
+    let parts = if args.export.is_some() || args.with_metadata {
+        "snippet,contentDetails"
+    } else {
+        "contentDetails"
+    };
+    let api_source = DataApiSource {
+        client,
+        credentials,
+        args,
+        options,
+        parts,
+        base_url,
+    };
+    let scraping_source = ScrapingSource::new(client, &args.watch_base_url);
+    let source: &dyn ChannelSource = if args.scrape {
+        &scraping_source
+    } else {
+        &api_source
+    };
+
+    status!("Resolving channel URL: {}", channel_url);
+    let channel_id = source.resolve(channel_url).await?;
+    status!("Resolved to channel ID: {}", channel_id);
+    if let Some(progress) = &options.progress {
+        progress.report(ProgressEvent::Resolved {
+            channel_url: channel_url.to_string(),
+            channel_id: channel_id.clone(),
+        });
+    }
+
+    let items = if let Some(name) = &args.playlist_name {
+        status!("Looking up playlist \"{}\"...", name);
+        let playlist_id =
+            find_playlist_by_name(client, credentials, &channel_id, name, base_url).await?;
+        status!("Found playlist ID: {}", playlist_id);
+        status!("Fetching all video IDs from the playlist...");
+        get_all_playlist_items(
+            client,
+            credentials,
+            &playlist_id,
+            parts,
+            args.page_size,
+            args.max_pages,
+            base_url,
+        )
+        .await?
+    } else {
+        status!("Fetching all video IDs from the channel's uploads...");
+        let Some(items) = source.list_uploads(&channel_id).await? else {
+            status!("Channel {} has no uploads.", channel_id);
+            return Ok(Summary::default());
+        };
+        items
+    };
+    let mut video_ids: Vec<String> = items
+        .iter()
+        .map(|item| item.content_details.video_id.clone())
+        .collect();
+    status!("Found {} videos in the channel.", video_ids.len());
+    if args.verify_count {
+        if let Some(reported) =
+            get_channel_video_count(client, credentials, &channel_id, base_url).await?
+        {
+            check_video_count(video_ids.len(), reported, args.strict)?;
+        }
+    }
+    let channel_video_ids: HashSet<String> = if args.prune {
+        video_ids.iter().cloned().collect()
+    } else {
+        HashSet::new()
+    };
+
+    let title_filter = TitleFilter::new(args.title_contains.as_deref(), args.title_regex.as_ref());
+    // Downloads need the details to weed out private and deleted videos, exports for the
+    // durations and statistics. Scraped channels only list public videos anyway.
+    let details = if args.scrape {
+        HashMap::new()
+    } else {
+        status!("Fetching video details...");
+        fetch_video_details(
+            client,
+            credentials,
+            &video_ids,
+            options.api_semaphore.as_deref(),
+            base_url,
+        )
+        .await?
+    };
+
+    if args.skip_shorts || args.only_shorts {
+        let durations = video_durations(&details);
+        video_ids = filter_shorts(video_ids, &durations, args.only_shorts);
+        if args.only_shorts {
+            status!("Keeping {} Shorts.", video_ids.len());
+        } else {
+            status!("Keeping {} videos after skipping Shorts.", video_ids.len());
+        }
+    }
+
+    if let Some(title_filter) = &title_filter {
+        video_ids = filter_by_title(video_ids, &details, title_filter);
+        status!("Keeping {} videos with matching titles.", video_ids.len());
+    }
+
+    if args.export.is_some() {
+        let video_ids = select_videos(video_ids, &details, args)?;
+        let kept: HashSet<&String> = video_ids.iter().collect();
+        let exported = items
+            .iter()
+            .filter(|item| kept.contains(&item.content_details.video_id))
+            .map(|item| video_record(item, details.get(&item.content_details.video_id)))
+            .collect();
+        return Ok(Summary {
+            exported,
+            ..Default::default()
+        });
+    }
+
+    let (video_ids, unavailable) = if args.scrape {
+        (video_ids, 0)
+    } else {
+        filter_unavailable(video_ids, &details)
+    };
+    let (video_ids, restricted) = if args.skip_restricted {
+        filter_restricted(video_ids, &details)
+    } else {
+        (video_ids, 0)
+    };
+
+    let location = if args.input_file.is_some() {
+        let title = if args.dir_name == DirName::Title {
+            Some(get_channel_title(client, credentials, &channel_id, base_url).await?)
+        } else {
+            None
+        };
+        output_location(args).join(channel_dir_name(
+            args.dir_name,
+            channel_url,
+            &channel_id,
+            title.as_deref(),
+        ))
+    } else {
+        output_location(args)
+    };
+    if args.prune {
+        prune_channel(args, &location, &channel_video_ids).await?;
+    }
+    let metadata: HashMap<String, VideoMetadata> = if args.with_metadata {
+        items
+            .iter()
+            .map(|item| (item.content_details.video_id.clone(), video_metadata(item)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let summary = download_videos(
+        client, args, video_ids, &location, options, &details, metadata,
+    )
+    .await?;
+    Ok(Summary {
+        unavailable,
+        restricted: summary.restricted + restricted,
+        ..summary
+    })
+}
+
+/// Lists channels through the Data API, as runs with an API key or access token do.
+struct DataApiSource<'a> {
+    client: &'a Client,
+    credentials: Credentials<'a>,
+    args: &'a Args,
+    options: &'a DownloadOptions,
+    /// The playlist item parts to request.
+    parts: &'a str,
+    base_url: &'a str,
+}
+
+#[async_trait::async_trait]
+impl ChannelSource for DataApiSource<'_> {
+    async fn resolve(&self, channel_url: &str) -> Result<String, Box<dyn Error>> {
+        get_channel_id_from_url(
+            self.client,
+            self.credentials,
+            channel_url,
+            self.options.channel_cache.as_deref(),
+            self.options.picker.as_deref(),
+            self.base_url,
+        )
+        .await
+    }
+
+    async fn list_uploads(&self, channel_id: &str) -> Result<Uploads, Box<dyn Error>> {
+        let Some((uploads_playlist_id, items)) = list_uploads(
+            self.client,
+            self.credentials,
+            channel_id,
+            self.parts,
+            self.args.page_size,
+            self.args.max_pages,
+            self.base_url,
+        )
+        .await?
+        else {
+            return Ok(None);
+        };
+        status!("Listed uploads playlist {}", uploads_playlist_id);
+        Ok(Some(items))
+    }
+}
+
+/// Downloads the thumbnails of `video_ids` into `location`. `details` supplies the
+/// upload dates for `--dir-template` and `metadata` the sidecars for `--with-metadata`;
+/// videos missing from them are still downloaded.
+async fn download_videos(
+    client: &Client,
+    args: &Args,
+    mut video_ids: Vec<String>,
+    location: &OutputLocation,
+    options: &DownloadOptions,
+    details: &HashMap<String, VideoDetails>,
+    mut metadata: HashMap<String, VideoMetadata>,
+) -> Result<Summary, Box<dyn Error>> {
+    let received_before = options.received_bytes.load(Ordering::Relaxed);
+    if let Some(state) = &options.state {
+        let total = video_ids.len();
+        video_ids.retain(|video_id| !state.contains(video_id));
+        if video_ids.len() < total {
+            status!(
+                "Skipping {} already downloaded videos.",
+                total - video_ids.len()
+            );
+        }
+    }
+    if args.only_missing {
+        let total = video_ids.len();
+        let mut missing = Vec::new();
+        for video_id in video_ids {
+            let video_location = video_location(args, location, details, &video_id);
+            let path = options.thumbnail_path(&video_location, &video_id);
+            if fs::symlink_metadata(&path).await.is_err() {
+                missing.push(video_id);
+            }
+        }
+        status!(
+            "{} of {} thumbnails are already in the output directory, {} missing.",
+            total - missing.len(),
+            total,
+            missing.len()
+        );
+        video_ids = missing;
+    }
+    let video_ids = select_videos(video_ids, details, args)?;
+
+    // Create the output directory if it doesn't exist
+    fs::create_dir_all(location.dir()).await?;
+
+    if args.clean_partial {
+        let removed = remove_partial_files(location.dir()).await?;
+        if removed > 0 {
+            status!("Removed {} partial downloads.", removed);
+        }
+    }
+
+    let mut options = options.clone();
+    if options.embed_titles.is_some() {
+        let titles = details
+            .iter()
+            .filter_map(|(video_id, details)| {
+                let title = &details.snippet.as_ref()?.title;
+                Some((video_id.clone(), title.clone()))
+            })
+            .collect();
+        options.embed_titles = Some(Arc::new(titles));
+    }
+    // Comparing against the remote ETag needs the one each thumbnail was saved with.
+    if args.conditional || args.overwrite == Overwrite::IfDifferent {
+        options.cache = Some(Arc::new(HttpCache::load(location.dir()).await?));
+    }
+    // Work out every video's directory up front and create them one by one,
+    // rather than having concurrent tasks race to create the same directories.
+    let video_locations: Vec<(String, OutputLocation)> = video_ids
+        .into_iter()
+        .map(|video_id| {
+            let video_location = video_location(args, location, details, &video_id);
+            (video_id, video_location)
+        })
+        .collect();
+    let unique_dirs: BTreeSet<&Path> = video_locations
+        .iter()
+        .map(|(_, location)| location.dir())
+        .collect();
+    for dir in unique_dirs {
+        fs::create_dir_all(dir).await?;
+    }
+
+    let mut download_tasks = Vec::new();
+    let mut summary = Summary::default();
+
+    let semaphore = args
+        .concurrency
+        .map(|permits| Arc::new(Semaphore::new(permits as usize)));
+    for (video_id, location) in video_locations {
+        let permit = match &semaphore {
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await?),
+            None => None,
+        };
+        let adaptive_permit = match &options.adaptive {
+            Some(adaptive) => Some(adaptive.acquire().await),
+            None => None,
+        };
+        let metadata = metadata.remove(&video_id);
+        summary
+            .thumbnail_paths
+            .push(options.thumbnail_path(&location, &video_id));
+        let client = client.clone();
+        let options = options.clone();
+        let thumbnail_base_url = args.thumbnail_base_url.clone();
+
+        let retry_later = args.retry_404_later.is_some();
+        let restricted = is_restricted(details.get(&video_id));
+
+        let span = tracing::info_span!("download", video_id = %video_id);
+        let task = tokio::spawn(
+            async move {
+                let _permits = (permit, adaptive_permit);
+                // A fresh upload's maxres thumbnail may not be generated yet, so with
+                // --retry-404-later only the preferred variant is tried for now.
+                let first_choice = DownloadOptions {
+                    resolution: Some(options.resolution.unwrap_or(Resolution::MaxRes)),
+                    ..options.clone()
+                };
+                let attempt_options = if retry_later { &first_choice } else { &options };
+                let outcome = try_download_thumbnail(
+                    &client,
+                    &video_id,
+                    &location,
+                    attempt_options,
+                    &thumbnail_base_url,
+                )
+                .await;
+                if let Some(stopped) = FirstPass::stopped(&outcome) {
+                    return stopped;
+                }
+                if retry_later && outcome == DownloadOutcome::NotFound {
+                    return FirstPass::Deferred(Box::new(DeferredVideo {
+                        video_id,
+                        location,
+                        metadata,
+                    }));
+                }
+                FirstPass::Finished(
+                    record_outcome(
+                        &client,
+                        &video_id,
+                        &outcome,
+                        &location,
+                        &options,
+                        metadata,
+                        &thumbnail_base_url,
+                    )
+                    .await,
+                )
+            }
+            .instrument(span),
+        );
+        download_tasks.push((restricted, task));
+    }
+
+    // Wait for all the download tasks to complete.
+    let mut deferred = Vec::new();
+    for (restricted, task) in download_tasks {
+        if let Some(video) = summary.count(restricted, task.await?) {
+            deferred.push(video);
+        }
+    }
+
+    if let (Some(delay), false) = (args.retry_404_later, deferred.is_empty()) {
+        let delay_ends = Instant::now() + Duration::from_secs(delay);
+        if options
+            .deadline
+            .is_some_and(|deadline| delay_ends >= deadline)
+        {
+            summary.out_of_time += deferred.len();
+            deferred.clear();
+        }
+    }
+    if let (Some(delay), false) = (args.retry_404_later, deferred.is_empty()) {
+        status!(
+            "Retrying {} videos without a thumbnail yet in {} seconds...",
+            deferred.len(),
+            delay
+        );
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+        let options = &options;
+        let thumbnail_base_url = args.thumbnail_base_url.as_str();
+        let retries = deferred.into_iter().map(|video| {
+            let span = tracing::info_span!("download", video_id = %video.video_id);
+            let restricted = is_restricted(details.get(&video.video_id));
+            async move {
+                let outcome = try_download_thumbnail(
+                    client,
+                    &video.video_id,
+                    &video.location,
+                    options,
+                    thumbnail_base_url,
+                )
+                .await;
+                if let Some(stopped) = FirstPass::stopped(&outcome) {
+                    return (restricted, stopped);
+                }
+                let recorded = record_outcome(
+                    client,
+                    &video.video_id,
+                    &outcome,
+                    &video.location,
+                    options,
+                    video.metadata,
+                    thumbnail_base_url,
+                )
+                .await;
+                (restricted, FirstPass::Finished(recorded))
+            }
+            .instrument(span)
+        });
+        let limit = match &options.adaptive {
+            Some(adaptive) => adaptive.limit(),
+            None => args.concurrency.map_or(usize::MAX, |limit| limit as usize),
+        };
+        let results: Vec<(bool, FirstPass)> = futures::stream::iter(retries)
+            .buffer_unordered(limit)
+            .collect()
+            .await;
+        for (restricted, result) in results {
+            summary.count(restricted, result);
+        }
+    }
+
+    if let Some(cache) = &options.cache {
+        cache.save().await?;
+    }
+    if let Some(state) = &options.state {
+        state.save(options.fsync).await?;
+    }
+    if let Some(checksums) = &options.checksums {
+        checksums.save(options.fsync).await?;
+    }
+
+    summary.bytes = options.received_bytes.load(Ordering::Relaxed) - received_before;
+    Ok(summary)
+}
+
+/// Builds the HTTP client used for all API and thumbnail requests. Every task shares its
+/// connection pool; HTTPS connections use HTTP/2 where the server offers it, so many
+/// downloads from the same host share a few connections. Compressed responses are
+/// accepted, which shrinks the API's JSON considerably. Nagle's algorithm is turned off,
+/// as it only delays the many small requests a run makes. With `--retries`, requests that
+/// fail to connect, time out or get a 429 or 5xx answer are retried with backoff before
+/// the caller sees them, and every request is logged at debug level.
+fn build_client(args: &Args) -> Result<Client, reqwest::Error> {
+    let keepalive = (args.tcp_keepalive > 0).then(|| Duration::from_secs(args.tcp_keepalive));
+    let mut headers = HeaderMap::new();
+    for (name, value) in &args.header {
+        headers.append(name.clone(), value.clone());
+    }
+    let client = reqwest::Client::builder()
+        .user_agent(&args.user_agent)
+        .default_headers(headers)
+        .gzip(true)
+        .deflate(true)
+        .pool_max_idle_per_host(args.pool_size as usize)
+        .tcp_keepalive(keepalive)
+        .tcp_nodelay(true)
+        .redirect(reqwest::redirect::Policy::limited(args.max_redirects))
+        .build()?;
+    let mut builder = reqwest_middleware::ClientBuilder::new(client);
+    if args.retries > 0 {
+        builder = builder.with(RetryTransientMiddleware::new_with_policy(Backoff::new(
+            args.retries,
+            RETRY_BASE_DELAY,
+            RETRY_MAX_DELAY,
+            args.jitter,
+            shuffle::random_seed(),
+        )));
+    }
+    // Logged after the retry middleware, so every attempt is logged.
+    Ok(builder.with(RequestLogger).build())
+}
+
+/// Checks a `--channel-id`: `UC` followed by 22 letters, digits, `-` or `_`.
+fn parse_channel_id(channel_id: &str) -> Result<String, String> {
+    let valid = channel_id.len() == 24
+        && channel_id.starts_with("UC")
+        && channel_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(channel_id.to_string())
+    } else {
+        Err(format!(
+            "\"{}\" isn't a channel ID; they're 24 characters starting with UC",
+            channel_id
+        ))
+    }
+}
+
+/// Checks a `--variant-template` names every variant of every video apart.
+fn parse_variant_template(template: &str) -> Result<String, String> {
+    if template.contains("{id}") && template.contains("{res}") {
+        Ok(template.to_string())
+    } else {
+        Err(format!(
+            "\"{}\" needs both {{id}} and {{res}}, or variants would overwrite each other",
+            template
+        ))
+    }
+}
+
+/// Parses a `--header` given as `Name: Value`.
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("\"{}\" isn't in the form \"Name: Value\"", header))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("\"{}\" isn't a valid header name", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("the value of {} isn't a valid header value", name))?;
+    Ok((name, value))
+}
+
+/// Processes every requested channel and aggregates the download totals.
+async fn run(
+    client: &Client,
+    credentials: Credentials<'_>,
+    args: &Args,
+    base_url: &str,
+) -> Result<Summary, Box<dyn Error>> {
+    let options = DownloadOptions::load(args).await?;
+    let summary = run_with_options(client, credentials, args, &options, base_url).await?;
+    if let Some(archive) = options.archive {
+        let path = archive.path().to_path_buf();
+        tokio::task::spawn_blocking(move || archive.finish()).await??;
+        status!("Wrote archive {}", path.display());
+    }
+    if let Some(report) = &options.resolution_report {
+        report.save().await?;
+        status!("Wrote resolutions report {}", report.path().display());
+    }
+    Ok(summary)
+}
+
+/// Like `run`, with the download options already built.
+async fn run_with_options(
+    client: &Client,
+    credentials: Credentials<'_>,
+    args: &Args,
+    options: &DownloadOptions,
+    base_url: &str,
+) -> Result<Summary, Box<dyn Error>> {
+    let channel_urls = match &args.input_file {
+        Some(path) => parse_channel_list(&fs::read_to_string(path).await?),
+        None => args.channel_url().into_iter().collect(),
+    };
+    let per_channel_dir = args.input_file.is_some();
+
+    let mut summary = Summary::default();
+    if let Some(path) = &args.video_ids_file {
+        let video_ids = video_ids::parse_video_ids(&fs::read_to_string(path).await?)?;
+        status!("Read {} video IDs from {}", video_ids.len(), path);
+        summary = download_videos(
+            client,
+            args,
+            video_ids,
+            &output_location(args),
+            options,
+            &HashMap::new(),
+            HashMap::new(),
+        )
+        .await?;
+    }
+    for (index, channel_url) in channel_urls.iter().enumerate() {
+        if options.out_of_time() {
+            summary.channels_out_of_time = channel_urls.len() - index;
+            break;
+        }
+        let result =
+            process_channel(client, credentials, args, channel_url, options, base_url).await;
+        match result {
+            Ok(channel_summary) => summary += channel_summary,
+            // A single channel failing shouldn't abort the rest of a batch, unless the
+            // quota ran out and every later channel would fail too.
+            Err(e) if per_channel_dir && !is_quota_exceeded(e.as_ref()) => {
+                eprintln!("Error processing channel {}: {}", channel_url, e);
+                summary.failed_channels += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(format) = args.export {
+        let path = write_export(args, format, &summary.exported).await?;
+        status!(
+            "Exported {} videos to {}",
+            summary.exported.len(),
+            path.display()
+        );
+    } else if let Some(progress) = &options.progress {
+        progress.report(ProgressEvent::Finished {
+            ok: summary.downloaded,
+            failed: summary.failed,
+        });
+    }
+
+    Ok(summary)
+}
+
+fn is_quota_exceeded(error: &(dyn Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<DownloaderError>(),
+        Some(DownloaderError::QuotaExceeded)
+    )
+}
+
+/// Checks the channels for new videos every `--watch` minutes, forever unless `max_cycles`
+/// is given. A failed check is logged and retried at the next interval.
+async fn watch(
+    client: &Client,
+    credentials: Credentials<'_>,
+    args: &Args,
+    base_url: &str,
+    max_cycles: Option<usize>,
+) {
+    let minutes = args.watch.unwrap_or(1);
+    let mut cycles = 0;
+    loop {
+        match run(client, credentials, args, base_url).await {
+            Ok(summary) => status!(
+                "Check finished: downloaded {} new thumbnails, {} failed.",
+                summary.downloaded,
+                summary.failed
+            ),
+            Err(e) => eprintln!("Error checking for new videos: {}", e),
+        }
+        cycles += 1;
+        if max_cycles.is_some_and(|max| cycles >= max) {
+            return;
+        }
+        status!("Checking again in {} minutes...", minutes);
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = parse_args(env::args_os().collect()).unwrap_or_else(|e| {
+        let _ = e.print();
+        // --help and --version also arrive here, and aren't failures.
+        let code = if e.use_stderr() {
+            error::EXIT_INVALID_INPUT
+        } else {
+            0
+        };
+        std::process::exit(code);
+    });
+    progress::set_human_output(!args.json && !args.quiet);
+    progress::set_quiet(args.quiet);
+    progress::init_color(args.no_color);
+    // Detailed logs of every request, tagged with the video they belong to, for debugging
+    // a run with e.g. RUST_LOG=youtube_images=debug.
+    if env::var_os("RUST_LOG").is_some() {
+        tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    let code = match run_main(args).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            error::exit_code(e.as_ref())
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Parses the command line, adding the settings of the `--config` file, if any, for the
+/// flags it doesn't give.
+fn parse_args(argv: Vec<OsString>) -> Result<Args, clap::Error> {
+    let Some(path) = config::find_config_path(&argv) else {
+        return Args::try_parse_from(argv);
+    };
+    let mut command = Args::command();
+    let path = path.to_string_lossy();
+    let contents = std::fs::read_to_string(&*path).map_err(|e| {
+        command.error(
+            clap::error::ErrorKind::Io,
+            format!("can't read config file {}: {}", path, e),
+        )
+    })?;
+    // Nothing is required yet, so this only fails on errors the full parse reports anyway.
+    let given = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)?;
+    let config_args = config::config_args(&contents, &command, &given).map_err(|message| {
+        command.error(
+            clap::error::ErrorKind::InvalidValue,
+            format!("invalid config file {}: {}", path, message),
+        )
+    })?;
+    let mut argv = argv.into_iter();
+    let program = argv.next();
+    Args::try_parse_from(program.into_iter().chain(config_args).chain(argv))
+}
+
+/// Runs the tool for the parsed arguments and returns the exit status.
+async fn run_main(mut args: Args) -> Result<i32, Box<dyn Error>> {
+    args.output_dir = Some(resolve_output_dir(
+        args.output_dir.take(),
+        env::var(OUTPUT_DIR_ENV).ok(),
+    )?);
+    check_image_processing(&args)?;
+    if args.merge_export && args.export != Some(ExportFormat::Json) {
+        return Err(DownloaderError::InvalidInput(
+            "--merge-export only works with --export json.".to_string(),
+        )
+        .into());
+    }
+    if let Some(Command::Verify { dir }) = &args.command {
+        return verify_archive(Path::new(dir), &mut std::io::stdout()).await;
+    }
+    if let Some(flag) = api_only_flag(&args).filter(|_| args.scrape) {
+        return Err(DownloaderError::InvalidInput(format!(
+            "{} needs the YouTube Data API, which --scrape doesn't use.",
+            flag
+        ))
+        .into());
+    }
+    let access_token = args
+        .access_token
+        .clone()
+        .or_else(|| env::var(ACCESS_TOKEN_ENV).ok())
+        .filter(|token| !token.is_empty());
+    let api_key = env::var("YOUTUBE_API_KEY").ok();
+    if api_key.is_none() && access_token.is_none() && !args.scrape && args.video_ids_file.is_none()
+    {
+        if let Some(flag) = api_only_flag(&args) {
+            return Err(DownloaderError::InvalidInput(format!(
+                "YOUTUBE_API_KEY environment variable not set, and {} needs the YouTube Data \
+                 API. Set it, or pass an OAuth access token with --access-token or \
+                 YOUTUBE_ACCESS_TOKEN.",
+                flag
+            ))
+            .into());
+        }
+        eprintln!(
+            "Warning: YOUTUBE_API_KEY isn't set, so channel videos are listed by reading \
+             YouTube's pages instead. Only the latest videos are found, and this breaks \
+             whenever YouTube changes its pages."
+        );
+        args.scrape = true;
+    }
+    let args = &args;
+    // Downloading a saved list of video IDs and scraping don't touch the Data API.
+    let api_key = api_key.unwrap_or_default();
+    let credentials = match &access_token {
+        Some(token) => Credentials::AccessToken(token),
+        None => Credentials::ApiKey(&api_key),
+    };
+
+    let client = build_client(args)?;
+    match &args.command {
+        Some(Command::ListPlaylists { channel_url }) => {
+            let mut stdout = std::io::stdout();
+            list_playlists(
+                &client,
+                credentials,
+                channel_url,
+                &mut stdout,
+                &args.api_base_url,
+            )
+            .await?;
+            return Ok(0);
+        }
+        Some(Command::Resolve { channel_url, json }) => {
+            let mut stdout = std::io::stdout();
+            resolve_channel(
+                &client,
+                credentials,
+                channel_url,
+                *json,
+                &mut stdout,
+                &args.api_base_url,
+            )
+            .await?;
+            return Ok(0);
+        }
+        Some(Command::Verify { .. }) | None => {}
+    }
+    if args.estimate_quota {
+        let mut stdout = std::io::stdout();
+        estimate_quota(&client, credentials, args, &mut stdout, &args.api_base_url).await?;
+        return Ok(0);
+    }
+    if args.stats_only {
+        let channel_urls = match &args.input_file {
+            Some(path) => parse_channel_list(&fs::read_to_string(path).await?),
+            None => args.channel_url().into_iter().collect(),
+        };
+        let mut stdout = std::io::stdout();
+        for channel_url in &channel_urls {
+            print_channel_stats(
+                &client,
+                credentials,
+                channel_url,
+                args.page_size,
+                args.max_pages,
+                &mut stdout,
+                &args.api_base_url,
+            )
+            .await?;
+        }
+        return Ok(0);
+    }
+    // Fail before spending any quota if the thumbnails couldn't be saved anyway.
+    paths::ensure_writable_dir(Path::new(args.output_dir())).await?;
+
+    if args.watch.is_some() {
+        watch(&client, credentials, args, &args.api_base_url, None).await;
+        return Ok(0);
+    }
+
+    let started = std::time::Instant::now();
+    let summary = run(&client, credentials, args, &args.api_base_url).await?;
+    if let Some(path) = &args.metrics_file {
+        run_metrics(&summary, started.elapsed())
+            .write(Path::new(path))
+            .await?;
+    }
+    if args.export.is_some() {
+        return Ok(0);
+    }
+
+    status!("\nDownload process finished!");
+    status!(
+        "Downloaded {}, {} failed.",
+        progress::transfer_report(summary.downloaded, summary.bytes, started.elapsed()),
+        summary.failed
+    );
+    if summary.unavailable > 0 {
+        status!("Skipped {} private or deleted videos.", summary.unavailable);
+    }
+    if summary.restricted > 0 {
+        if args.skip_restricted {
+            status!("Skipped {} age-restricted videos.", summary.restricted);
+        } else {
+            status!(
+                "No thumbnail for {} age-restricted videos (restricted, not counted as failed).",
+                summary.restricted
+            );
+        }
+    }
+    if summary.over_budget > 0 {
+        status!(
+            "Skipped {} videos after reaching the --max-total-size budget.",
+            summary.over_budget
+        );
+    }
+    if summary.failed_channels > 0 {
+        status!(
+            "{} channels could not be processed.",
+            summary.failed_channels
+        );
+    }
+    let out_of_time = summary.out_of_time > 0 || summary.channels_out_of_time > 0;
+    if out_of_time {
+        status!(
+            "Stopped after --max-duration ran out: {} videos and {} channels were left for the next run.",
+            summary.out_of_time,
+            summary.channels_out_of_time
+        );
+    }
+    let code = if summary.failed > 0 || summary.failed_channels > 0 || out_of_time {
+        error::EXIT_PARTIAL_FAILURE
+    } else {
+        0
+    };
+
+    #[cfg(feature = "image-processing")]
+    if let Some(contact_sheet) = &args.contact_sheet {
+        status!("Building contact sheet...");
+        let (columns, cell_width) = (args.contact_sheet_columns, args.contact_sheet_cell_width);
+        let output = PathBuf::from(contact_sheet);
+        tokio::task::spawn_blocking(move || {
+            contact_sheet::write_contact_sheet(
+                &summary.thumbnail_paths,
+                columns,
+                cell_width,
+                &output,
+            )
+        })
+        .await?
+        .map_err(|e| e as Box<dyn Error>)?;
+        status!("Saved contact sheet to {}", contact_sheet);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    const MOCK_API_KEY: &str = "test_api_key";
+    const MOCK_CREDENTIALS: Credentials = Credentials::ApiKey(MOCK_API_KEY);
+    const MOCK_CHANNEL_ID: &str = "UC_test_channel_id";
+    const MOCK_USERNAME: &str = "testuser";
+    const MOCK_HANDLE: &str = "testhandle";
+    const MOCK_UPLOADS_ID: &str = "UU_test_channel_id";
+    const MOCK_VIDEO_ID_1: &str = "video1";
+    const MOCK_VIDEO_ID_2: &str = "video2";
+
+    /// A client without the retry and logging middleware `build_client` adds.
+    fn test_client() -> Client {
+        reqwest::Client::new().into()
+    }
+
+    /// Mocks the `channels?part=snippet` lookup, giving each channel ID the paired handle.
+    async fn mock_channel_handles(
+        server: &mut mockito::Server,
+        channels: &[(&str, &str)],
+    ) -> mockito::Mock {
+        let items: Vec<_> = channels
+            .iter()
+            .map(|(id, handle)| json!({"id": id, "snippet": {"customUrl": format!("@{}", handle)}}))
+            .collect();
+        server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "part".to_string(),
+                "snippet".to_string(),
+            ))
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await
+    }
+
+    /// Fetches all video IDs from a given playlist, `page_size` at a time and at most
+    /// `max_pages` pages.
+    async fn get_all_video_ids(
+        client: &Client,
+        credentials: Credentials<'_>,
+        playlist_id: &str,
+        page_size: u8,
+        max_pages: Option<u32>,
+        base_url: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let items = get_all_playlist_items(
+            client,
+            credentials,
+            playlist_id,
+            "contentDetails",
+            page_size,
+            max_pages,
+            base_url,
+        )
+        .await?;
+        Ok(items
+            .into_iter()
+            .map(|item| item.content_details.video_id)
+            .collect())
+    }
+
+    /// Mocks the `videos` endpoint, reporting every given video as public.
+    async fn mock_public_videos(server: &mut mockito::Server, video_ids: &[&str]) -> mockito::Mock {
+        let items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"id": id, "status": {"privacyStatus": "public", "uploadStatus": "processed"}}))
+            .collect();
+        server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_handle_url() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+            .create_async()
+            .await;
+
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[(MOCK_CHANNEL_ID, MOCK_HANDLE)]).await;
+
+        // Pass the mock server's URL to the function
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    #[test]
+    fn test_normalize_channel_url() {
+        for (url, expected) in [
+            (
+                "https://www.youtube.com/@handle?si=abc#videos",
+                "https://www.youtube.com/@handle",
+            ),
+            (
+                "https://www.youtube.com/@handle/streams",
+                "https://www.youtube.com/@handle",
+            ),
+            (
+                "https://www.youtube.com/channel/UC123/videos/",
+                "https://www.youtube.com/channel/UC123",
+            ),
+            (
+                "https://www.youtube.com/c/Name/Featured?view=0",
+                "https://www.youtube.com/c/Name",
+            ),
+            ("youtube.com/@handle", "https://youtube.com/@handle"),
+            // A handle that happens to be a tab name is left alone.
+            (
+                "https://www.youtube.com/@videos",
+                "https://www.youtube.com/@videos",
+            ),
+        ] {
+            assert_eq!(normalize_channel_url(url).unwrap(), expected, "{}", url);
+        }
+        assert!(matches!(
+            normalize_channel_url("not a url"),
+            Err(DownloaderError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_urls_copied_from_a_browser() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let search_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+            .expect(2)
+            .create_async()
+            .await;
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[(MOCK_CHANNEL_ID, MOCK_HANDLE)]).await;
+
+        for channel_url in [
+            format!("http://any.url/@{}?si=abc123#videos", MOCK_HANDLE),
+            format!("http://any.url/@{}/streams", MOCK_HANDLE),
+            format!("http://any.url/channel/{}/videos", MOCK_CHANNEL_ID),
+        ] {
+            let channel_id = get_channel_id_from_url(
+                &client,
+                MOCK_CREDENTIALS,
+                &channel_url,
+                None,
+                None,
+                &server.url(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(channel_id, MOCK_CHANNEL_ID, "{}", channel_url);
+        }
+        search_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_estimate_quota_for_handle() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let statistics_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=statistics&forHandle=@{}&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"statistics": {"videoCount": "120"}}]}).to_string())
+            .create_async()
+            .await;
+        let search_mock = server
+            .mock("GET", "/youtube/v3/search")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("https://www.youtube.com/@{}", MOCK_HANDLE);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--page-size",
+            "20",
+            "--estimate-quota",
+        ]);
+        let mut output = Vec::new();
+        let total = estimate_quota(&client, MOCK_CREDENTIALS, &args, &mut output, &server.url())
+            .await
+            .unwrap();
+
+        statistics_mock.assert_async().await;
+        search_mock.assert_async().await;
+        // A search plus a handle check, 6 pages of 20 playlist items and 3 batches of 50
+        // video details. The uploads playlist ID is derived for free.
+        assert_eq!(total, 100 + 1 + 6 + 3);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(&format!("{}: 110 units for 120 videos", channel_url)));
+        assert!(output.contains("Estimated total: 110 units of the 10000"));
+    }
+
+    #[test]
+    fn test_estimate_channel_quota_by_url_and_flags() {
+        let args = Args::parse_from(["youtube-images", "-c", "url", "--verify-count"]);
+        // No search for a channel ID URL, one page and one batch, plus the statistics.
+        assert_eq!(
+            estimate_channel_quota("https://www.youtube.com/channel/UC1", 50, false, &args),
+            1 + 1 + 1
+        );
+        // An empty channel still needs a page to find out.
+        assert_eq!(
+            estimate_channel_quota("https://www.youtube.com/user/name", 0, false, &args),
+            1 + 1 + 1
+        );
+        // A cached handle needs no search.
+        assert_eq!(
+            estimate_channel_quota("https://www.youtube.com/@handle", 51, true, &args),
+            1 + 2 + 2
+        );
+        // --max-pages caps the pages, and with them the videos whose details are fetched.
+        let args = Args::parse_from(["youtube-images", "-c", "url", "--max-pages", "2"]);
+        assert_eq!(
+            estimate_channel_quota("https://www.youtube.com/channel/UC1", 500, false, &args),
+            2 + 2
+        );
+        // Looking up a playlist by name costs a page of the channel's playlists.
+        let args = Args::parse_from(["youtube-images", "-c", "url", "--playlist-name", "Clips"]);
+        assert_eq!(
+            estimate_channel_quota("https://www.youtube.com/channel/UC1", 50, false, &args),
+            1 + 1 + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_prints_channel_and_uploads_ids() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _search_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+            .create_async()
+            .await;
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[(MOCK_CHANNEL_ID, MOCK_HANDLE)]).await;
+        let _uploads_mock = server.mock("GET", &*format!("/youtube/v3/channels?part=contentDetails&id={}&key={}", MOCK_CHANNEL_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{"contentDetails": {"relatedPlaylists": {"uploads": MOCK_UPLOADS_ID}}}]}).to_string())
+            .create_async().await;
+
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        let mut text = Vec::new();
+        resolve_channel(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            false,
+            &mut text,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+        let mut json_output = Vec::new();
+        resolve_channel(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            true,
+            &mut json_output,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            format!(
+                "Channel ID: {}\nUploads playlist ID: {}\n",
+                MOCK_CHANNEL_ID, MOCK_UPLOADS_ID
+            )
+        );
+        let json_output: serde_json::Value = serde_json::from_slice(&json_output).unwrap();
+        assert_eq!(
+            json_output,
+            json!({"channel_id": MOCK_CHANNEL_ID, "uploads_playlist_id": MOCK_UPLOADS_ID})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_user_url() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=id&forUsername={}&key={}",
+                    MOCK_USERNAME, MOCK_API_KEY
+                ),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"items": [{"id": MOCK_CHANNEL_ID}]}).to_string())
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/user/{}", MOCK_USERNAME);
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_custom_url() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let handle_mock = server
+            .mock(
+                "GET",
+                "/youtube/v3/channels?part=id&forHandle=SomeName&key=test_api_key",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"pageInfo": {"totalResults": 0}}).to_string())
+            .create_async()
+            .await;
+        let search_mock = server
+            .mock(
+                "GET",
+                "/youtube/v3/search?part=snippet&q=SomeName&type=channel&key=test_api_key",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({"items": [
+                    {"id": {"channelId": "UC_other"}, "snippet": {"title": "Some Name Fan Club"}},
+                    {"id": {"channelId": MOCK_CHANNEL_ID}, "snippet": {"title": "Some Name"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            "http://any.url/c/SomeName",
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        handle_mock.assert_async().await;
+        search_mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    /// Answers the channel prompt with canned input.
+    #[derive(Debug)]
+    struct StubPicker(&'static str);
+
+    impl ChannelPicker for StubPicker {
+        fn pick(&self, candidates: &[ChannelCandidate]) -> std::io::Result<Option<usize>> {
+            picker::prompt(candidates, &mut self.0.as_bytes(), &mut std::io::sink())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interactive_custom_url_resolves_to_chosen_candidate() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _handle_mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "forHandle".to_string(),
+                "SomeName".to_string(),
+            ))
+            .with_body(json!({"pageInfo": {"totalResults": 0}}).to_string())
+            .create_async()
+            .await;
+        let _search_mock = server
+            .mock(
+                "GET",
+                "/youtube/v3/search?part=snippet&q=SomeName&type=channel&key=test_api_key",
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": {"channelId": MOCK_CHANNEL_ID}, "snippet": {"title": "Some Name"}},
+                    {"id": {"channelId": "UC_other"}, "snippet": {"title": "Some Name Fan Club"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let details_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=snippet,statistics&id={},UC_other&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": "UC_other", "snippet": {"title": "Some Name Fan Club"}, "statistics": {"subscriberCount": "12"}},
+                    {"id": MOCK_CHANNEL_ID, "snippet": {"title": "Some Name"}, "statistics": {"subscriberCount": "34000"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            "http://any.url/c/SomeName",
+            None,
+            Some(&StubPicker("2\n")),
+            &server.url(),
+        )
+        .await;
+
+        details_mock.assert_async().await;
+        assert_eq!(result.unwrap(), "UC_other");
+    }
+
+    #[tokio::test]
+    async fn test_interactive_handle_without_exact_match_asks() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _search_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"id": {"channelId": "UC_other"}}]}).to_string())
+            .create_async()
+            .await;
+        let _handles_mock = mock_channel_handles(&mut server, &[("UC_other", "otherhandle")]).await;
+        let _details_mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "part".to_string(),
+                "snippet,statistics".to_string(),
+            ))
+            .with_body(
+                json!({"items": [{"id": "UC_other", "snippet": {"title": "Other"}}]}).to_string(),
+            )
+            .create_async()
+            .await;
+        let channel_url = format!("https://www.youtube.com/@{}", MOCK_HANDLE);
+
+        let chosen = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            Some(&StubPicker("\n")),
+            &server.url(),
+        )
+        .await;
+        let abandoned = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            Some(&StubPicker("q\n")),
+            &server.url(),
+        )
+        .await;
+
+        assert_eq!(chosen.unwrap(), "UC_other");
+        let error = abandoned.unwrap_err();
+        assert_eq!(
+            error::exit_code(error.as_ref()),
+            error::EXIT_CHANNEL_NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_custom_url_via_handle() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let handle_mock = server
+            .mock(
+                "GET",
+                "/youtube/v3/channels?part=id&forHandle=SomeName&key=test_api_key",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"items": [{"id": MOCK_CHANNEL_ID}]}).to_string())
+            .create_async()
+            .await;
+        let search_mock = server
+            .mock("GET", "/youtube/v3/search")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            "http://any.url/c/SomeName",
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        handle_mock.assert_async().await;
+        search_mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    #[tokio::test]
+    async fn test_get_uploads_playlist_id() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", &*format!("/youtube/v3/channels?part=contentDetails&id={}&key={}", MOCK_CHANNEL_ID, MOCK_API_KEY))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"items": [{"contentDetails": {"relatedPlaylists": {"uploads": MOCK_UPLOADS_ID}}}]}).to_string())
+            .create_async().await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap().as_deref(), Some(MOCK_UPLOADS_ID));
+    }
+
+    #[tokio::test]
+    async fn test_page_size_one_fetches_one_item_per_request() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = ["video1", "video2", "video3"];
+        let mut mocks = Vec::new();
+        for (page, video_id) in video_ids.iter().enumerate() {
+            let mut url = format!(
+                "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=1",
+                MOCK_UPLOADS_ID, MOCK_API_KEY
+            );
+            if page > 0 {
+                url.push_str(&format!("&pageToken=page{}", page));
+            }
+            let mut body = json!({"items": [{"contentDetails": {"videoId": video_id}}]});
+            if page + 1 < video_ids.len() {
+                body["nextPageToken"] = json!(format!("page{}", page + 1));
+            }
+            mocks.push(
+                server
+                    .mock("GET", &*url)
+                    .with_body(body.to_string())
+                    .expect(1)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let page_size = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--page-size",
+            "1",
+        ])
+        .page_size;
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            page_size,
+            None,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(result, video_ids);
+        for invalid in ["0", "51"] {
+            assert!(Args::try_parse_from([
+                "youtube-images",
+                "-c",
+                "url",
+                "-o",
+                "out",
+                "--page-size",
+                invalid
+            ])
+            .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_all_video_ids_with_pagination() {
+        let client = test_client();
+        let next_page_token = "nextPageToken123";
+        let mut server = mockito::Server::new_async().await;
+
+        let mock1 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"nextPageToken": next_page_token, "items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
+            .create_async().await;
+
+        let mock2 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50&pageToken={}", MOCK_UPLOADS_ID, MOCK_API_KEY, next_page_token))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string())
+            .create_async().await;
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        mock1.assert_async().await;
+        mock2.assert_async().await;
+        assert_eq!(result.unwrap(), vec![MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]);
+    }
+
+    #[tokio::test]
+    async fn test_max_pages_stops_pagination() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let page_path = |token: Option<&str>| {
+            let mut path = format!(
+                "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                MOCK_UPLOADS_ID, MOCK_API_KEY
+            );
+            if let Some(token) = token {
+                path.push_str(&format!("&pageToken={}", token));
+            }
+            path
+        };
+        let mut mocks = Vec::new();
+        for (token, next, video_id, hits) in [
+            (None, "page2", "video1", 1),
+            (Some("page2"), "page3", "video2", 1),
+            (Some("page3"), "page4", "video3", 0),
+        ] {
+            mocks.push(
+                server
+                    .mock("GET", &*page_path(token))
+                    .with_body(
+                        json!({"nextPageToken": next, "items": [{"contentDetails": {"videoId": video_id}}]})
+                            .to_string(),
+                    )
+                    .expect(hits)
+                    .create_async()
+                    .await,
+            );
+        }
+        let args = Args::parse_from(["youtube-images", "-c", "url", "--max-pages", "2"]);
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            args.max_pages,
+            &server.url(),
+        )
+        .await;
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(result.unwrap(), ["video1", "video2"]);
+    }
+
+    fn gzip(body: &str) -> Vec<u8> {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_all_video_ids_with_gzip_responses() {
+        let args = Args::parse_from(["youtube-images", "-c", "url", "-o", "out"]);
+        let client = build_client(&args).unwrap();
+        let next_page_token = "nextPageToken123";
+        let mut server = mockito::Server::new_async().await;
+
+        let mock1 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .match_header("accept-encoding", mockito::Matcher::Regex("gzip".to_string()))
+            .with_header("content-type", "application/json")
+            .with_header("content-encoding", "gzip")
+            .with_body(gzip(&json!({"nextPageToken": next_page_token, "items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string()))
+            .create_async().await;
+        let mock2 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50&pageToken={}", MOCK_UPLOADS_ID, MOCK_API_KEY, next_page_token))
+            .with_header("content-type", "application/json")
+            .with_header("content-encoding", "gzip")
+            .with_body(gzip(&json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string()))
+            .create_async().await;
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        mock1.assert_async().await;
+        mock2.assert_async().await;
+        assert_eq!(result.unwrap(), vec![MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]);
+    }
+
+    #[tokio::test]
+    async fn test_download_thumbnail_success() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let image_bytes = b"fake_image_data";
+        let mut server = mockito::Server::new_async().await;
+
+        // Mock a simple path on the server
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(200)
+            .with_body(image_bytes)
+            .create_async()
+            .await;
+
+        // Construct the full URL to the mock server's path
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await;
+
+        mock.assert_async().await; // This will now pass!
+        assert!(result.is_ok());
+
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert!(file_path.exists());
+        let contents = fs::read(file_path).await.unwrap();
+        assert_eq!(contents, image_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_failing_server_trips_breaker_until_a_probe_succeeds() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let cooldown = Duration::from_millis(300);
+        let options = DownloadOptions {
+            breaker: Some(Arc::new(CircuitBreaker::new(50, 4, cooldown))),
+            ..Default::default()
+        };
+        let download = || {
+            download_variant(
+                &client,
+                MOCK_VIDEO_ID_1,
+                Resolution::MaxRes,
+                &url,
+                &location,
+                &options,
+            )
+        };
+
+        let failing = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(503)
+            .expect(4)
+            .create_async()
+            .await;
+        for _ in 0..4 {
+            assert_eq!(download().await.unwrap(), DownloadOutcome::NotFound);
+        }
+        failing.assert_async().await;
+        failing.remove_async().await;
+        let _recovered = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        // The breaker tripped, so the probe waits out the cooldown...
+        let start = std::time::Instant::now();
+        assert!(matches!(
+            download().await.unwrap(),
+            DownloadOutcome::Downloaded { .. }
+        ));
+        assert!(start.elapsed() >= cooldown);
+        // ...and once it succeeded, requests go through straight away again.
+        let start = std::time::Instant::now();
+        download().await.unwrap();
+        assert!(start.elapsed() < cooldown);
+    }
+
+    #[tokio::test]
+    async fn test_download_with_fsync() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            fsync: true,
+            ..Default::default()
+        };
+
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &location,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { bytes: 15, .. }
+        ));
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"fake_image_data");
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_thumbnail_failure() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await;
+
+        mock.assert_async().await; // This will now pass!
+        assert!(result.is_ok());
+
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert!(!file_path.exists());
+    }
+
+    #[test]
+    fn test_parse_iso8601_duration() {
+        assert_eq!(parse_iso8601_duration("PT45S"), Some(45));
+        assert_eq!(parse_iso8601_duration("PT4M13S"), Some(253));
+        assert_eq!(parse_iso8601_duration("PT1H2M3S"), Some(3723));
+        assert_eq!(parse_iso8601_duration("PT10M"), Some(600));
+        assert_eq!(parse_iso8601_duration("P1DT1S"), Some(86_401));
+        assert_eq!(parse_iso8601_duration("P0D"), Some(0));
+    }
+
+    #[test]
+    fn test_parse_iso8601_duration_invalid() {
+        assert_eq!(parse_iso8601_duration(""), None);
+        assert_eq!(parse_iso8601_duration("45S"), None);
+        assert_eq!(parse_iso8601_duration("PT"), None);
+        assert_eq!(parse_iso8601_duration("PT45"), None);
+        assert_eq!(parse_iso8601_duration("PTS"), None);
+        assert_eq!(parse_iso8601_duration("P5H"), None);
+    }
+
+    #[tokio::test]
+    async fn test_45_second_video_is_classified_as_short() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/videos?part=snippet,contentDetails,statistics,status&id={},{}&key={}",
+                    MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, MOCK_API_KEY
+                ),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({"items": [
+                    {"id": MOCK_VIDEO_ID_1, "contentDetails": {"duration": "PT45S"}},
+                    {"id": MOCK_VIDEO_ID_2, "contentDetails": {"duration": "PT12M5S"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let video_ids = vec![MOCK_VIDEO_ID_1.to_string(), MOCK_VIDEO_ID_2.to_string()];
+        let details =
+            fetch_video_details(&client, MOCK_CREDENTIALS, &video_ids, None, &server.url())
+                .await
+                .unwrap();
+        let durations = video_durations(&details);
+
+        mock.assert_async().await;
+        assert!(is_short(durations[MOCK_VIDEO_ID_1]));
+        assert!(!is_short(durations[MOCK_VIDEO_ID_2]));
+        assert_eq!(
+            filter_shorts(video_ids.clone(), &durations, true),
+            vec![MOCK_VIDEO_ID_1]
+        );
+        assert_eq!(
+            filter_shorts(video_ids, &durations, false),
+            vec![MOCK_VIDEO_ID_2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_video_details_batches_by_50() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let video_ids: Vec<String> = (0..120).map(|i| format!("video{}", i)).collect();
+        let mut mocks = Vec::new();
+        for batch in video_ids.chunks(50) {
+            let items: Vec<_> = batch
+                .iter()
+                .map(|id| {
+                    json!({
+                        "id": id,
+                        "snippet": {"publishedAt": "2024-03-15T10:00:00Z", "channelTitle": format!("Channel of {}", id)},
+                        "contentDetails": {"duration": "PT1M"}
+                    })
+                })
+                .collect();
+            mocks.push(
+                server
+                    .mock(
+                        "GET",
+                        &*format!(
+                            "/youtube/v3/videos?part=snippet,contentDetails,statistics,status&id={}&key={}",
+                            batch.join(","),
+                            MOCK_API_KEY
+                        ),
+                    )
+                    .with_status(200)
+                    .with_header("content-type", "application/json")
+                    .with_body(json!({ "items": items }).to_string())
+                    .expect(1)
+                    .create_async()
+                    .await,
+            );
+        }
+        let any_request = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let details =
+            fetch_video_details(&client, MOCK_CREDENTIALS, &video_ids, None, &server.url())
+                .await
+                .unwrap();
+
+        assert_eq!(mocks.len(), 3);
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        any_request.assert_async().await;
+        assert_eq!(details.len(), 120);
+        let snippet = details["video119"].snippet.as_ref().unwrap();
+        assert_eq!(snippet.published_at, "2024-03-15T10:00:00Z");
+        assert_eq!(snippet.channel_title, "Channel of video119");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_video_details_respects_api_concurrency() {
+        use std::sync::atomic::AtomicUsize;
+        use tokio::io::AsyncReadExt;
+
+        // A server that answers every request slowly, counting how many are in flight.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let (in_flight, max_in_flight, requests) =
+                (in_flight.clone(), max_in_flight.clone(), requests.clone());
+            tokio::spawn(async move {
+                loop {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let (in_flight, max_in_flight, requests) =
+                        (in_flight.clone(), max_in_flight.clone(), requests.clone());
+                    tokio::spawn(async move {
+                        let mut request = Vec::new();
+                        let mut buffer = [0; 1024];
+                        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            let read = socket.read(&mut buffer).await.unwrap();
+                            if read == 0 {
+                                return;
+                            }
+                            request.extend_from_slice(&buffer[..read]);
+                        }
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        let body = r#"{"items": []}"#;
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                             content-length: {}\r\nconnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        socket.write_all(response.as_bytes()).await.unwrap();
+                    });
+                }
+            })
+        };
+        let video_ids: Vec<String> = (0..300).map(|i| format!("video{}", i)).collect();
+        let semaphore = Semaphore::new(2);
+
+        fetch_video_details(
+            &test_client(),
+            MOCK_CREDENTIALS,
+            &video_ids,
+            Some(&semaphore),
+            &url,
+        )
+        .await
+        .unwrap();
+        server.abort();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_parse_channel_list() {
+        let contents = "# my channels\nhttps://www.youtube.com/@one\n\n  \n  https://www.youtube.com/channel/UC123  \n#https://www.youtube.com/@skipped\n";
+        assert_eq!(
+            parse_channel_list(contents),
+            vec![
+                "https://www.youtube.com/@one",
+                "https://www.youtube.com/channel/UC123"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channel_dir_name() {
+        let handle_url = "https://www.youtube.com/@handle";
+        assert_eq!(
+            channel_dir_name(DirName::Handle, handle_url, MOCK_CHANNEL_ID, None),
+            "handle"
+        );
+        assert_eq!(
+            channel_dir_name(
+                DirName::Handle,
+                "https://www.youtube.com/user/name",
+                MOCK_CHANNEL_ID,
+                None
+            ),
+            MOCK_CHANNEL_ID
+        );
+        assert_eq!(
+            channel_dir_name(DirName::Id, handle_url, MOCK_CHANNEL_ID, None),
+            MOCK_CHANNEL_ID
+        );
+    }
+
+    #[test]
+    fn test_channel_dir_name_from_title() {
+        let url = "https://www.youtube.com/@handle";
+        assert_eq!(
+            channel_dir_name(DirName::Title, url, "UC_cooking", Some("My Cooking Show")),
+            "My Cooking Show (UC_cooking)"
+        );
+        assert_eq!(
+            channel_dir_name(DirName::Title, url, "UC_band", Some("AC/DC: Live?")),
+            "AC_DC_ Live_ (UC_band)"
+        );
+        // Channels with the same title still get separate directories.
+        assert_ne!(
+            channel_dir_name(DirName::Title, url, "UC_one", Some("Same")),
+            channel_dir_name(DirName::Title, url, "UC_two", Some("Same"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_title() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=snippet&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [{"id": MOCK_CHANNEL_ID, "snippet": {"title": "My Cooking Show"}}]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let title = get_channel_title(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(title, "My Cooking Show");
+    }
+
+    #[tokio::test]
+    async fn test_process_input_file_with_two_channels() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let input_file = temp_dir.path().join("channels.txt");
+        let mut server = mockito::Server::new_async().await;
+        let second_channel_id = "UC_second_channel";
+        let second_uploads_id = "UU_second_channel";
+
+        std::fs::write(
+            &input_file,
+            format!(
+                "http://any.url/@{}\nhttp://any.url/channel/{}\n",
+                MOCK_HANDLE, second_channel_id
+            ),
+        )
+        .unwrap();
+
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[(MOCK_CHANNEL_ID, MOCK_HANDLE)]).await;
+        let mut mocks = vec![
+            server
+                .mock(
+                    "GET",
+                    &*format!(
+                        "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                        MOCK_HANDLE, MOCK_API_KEY
+                    ),
+                )
+                .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+                .create_async()
+                .await,
+        ];
+        for (uploads_id, video_id) in [
+            (MOCK_UPLOADS_ID, MOCK_VIDEO_ID_1),
+            (second_uploads_id, MOCK_VIDEO_ID_2),
+        ] {
+            mocks.push(server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", uploads_id, MOCK_API_KEY))
+                .with_body(json!({"items": [{"contentDetails": {"videoId": video_id}}]}).to_string())
+                .create_async().await);
+            mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/maxresdefault.jpg", video_id))
+                    .with_body(video_id)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--input-file",
+            input_file.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(
+            (summary.downloaded, summary.failed, summary.failed_channels),
+            (2, 0, 0)
+        );
+        assert!(output_dir
+            .join(MOCK_HANDLE)
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_1))
+            .exists());
+        assert!(output_dir
+            .join(second_channel_id)
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_2))
+            .exists());
+    }
+
+    #[cfg(feature = "image-processing")]
+    async fn download_converted_fixture(format: OutputFormat) -> Vec<u8> {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(200)
+            .with_body(convert::tests::tiny_jpeg())
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let options = DownloadOptions {
+            format,
+            quality: 75,
+            ..Default::default()
+        };
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(matches!(
+            result.unwrap(),
+            DownloadOutcome::Downloaded { .. }
+        ));
+        assert!(!Path::new(output_dir)
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_1))
+            .exists());
+        let file_path =
+            Path::new(output_dir).join(format!("{}.{}", MOCK_VIDEO_ID_1, format.extension()));
+        fs::read(file_path).await.unwrap()
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_resize_saves_the_new_size_and_checks_the_served_one() {
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body(convert::tests::tiny_jpeg())
+            .create_async()
+            .await;
+        // The 4x3 fixture is wide enough, even though it's saved smaller.
+        let options = DownloadOptions {
+            min_width: Some(4),
+            resize: Some(Size {
+                width: 2,
+                height: 2,
+            }),
+            resize_mode: ResizeMode::Stretch,
+            ..Default::default()
+        };
+
+        let outcome = download_variant(
+            &test_client(),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &OutputLocation::new(temp_dir.path()),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let DownloadOutcome::Downloaded { path, .. } = outcome else {
+            panic!("unexpected outcome {:?}", outcome);
+        };
+        assert_eq!(convert::image_dimensions(&path).unwrap(), (2, 2));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_embed_metadata_into_downloaded_jpeg() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body(convert::tests::tiny_jpeg())
+            .create_async()
+            .await;
+        let thumbnail_url = format!("{}/thumbnail.jpg", server.url());
+        let titles = HashMap::from([(MOCK_VIDEO_ID_1.to_string(), "A title".to_string())]);
+        let options = DownloadOptions {
+            embed_titles: Some(Arc::new(titles)),
+            ..Default::default()
+        };
+
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &thumbnail_url,
+            &OutputLocation::new(temp_dir.path()),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let DownloadOutcome::Downloaded { path, .. } = outcome else {
+            panic!("unexpected outcome {:?}", outcome);
+        };
+        let contents = std::fs::read(path).unwrap();
+        let exif = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(&contents))
+            .unwrap();
+        let description = exif
+            .get_field(exif::Tag::ImageDescription, exif::In::PRIMARY)
+            .unwrap();
+        assert_eq!(description.display_value().to_string(), "\"A title\"");
+        assert!(contents
+            .windows(thumbnail_url.len())
+            .any(|window| window == thumbnail_url.as_bytes()));
+        assert!(image::load_from_memory(&contents).is_ok());
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_download_thumbnail_as_png() {
+        let contents = download_converted_fixture(OutputFormat::Png).await;
+        assert!(contents.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_download_thumbnail_as_webp() {
+        let contents = download_converted_fixture(OutputFormat::Webp).await;
+        assert_eq!(&contents[..4], b"RIFF");
+        assert_eq!(&contents[8..12], b"WEBP");
+    }
+
+    #[tokio::test]
+    async fn test_conditional_download_not_modified() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        fs::write(&file_path, b"cached_image_data").await.unwrap();
+
+        let cache = Arc::new(HttpCache::load(Path::new(output_dir)).await.unwrap());
+        cache.insert(
+            MOCK_VIDEO_ID_1,
+            CacheEntry {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            },
+        );
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .match_header("if-none-match", "\"v1\"")
+            .match_header("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT")
+            .with_status(304)
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let options = DownloadOptions {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(matches!(result.unwrap(), DownloadOutcome::Skipped { .. }));
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"cached_image_data");
+        assert_eq!(
+            cache.get(MOCK_VIDEO_ID_1).unwrap().etag.as_deref(),
+            Some("\"v1\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conditional_download_new_etag_rewrites_file() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        fs::write(&file_path, b"old_image_data").await.unwrap();
+
+        let cache = Arc::new(HttpCache::load(Path::new(output_dir)).await.unwrap());
+        cache.insert(
+            MOCK_VIDEO_ID_1,
+            CacheEntry {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+            },
+        );
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(200)
+            .with_header("etag", "\"v2\"")
+            .with_body("new_image_data")
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let options = DownloadOptions {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(matches!(
+            result.unwrap(),
+            DownloadOutcome::Downloaded { .. }
+        ));
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"new_image_data");
+
+        // The new validator is persisted for the next run.
+        cache.save().await.unwrap();
+        let reloaded = HttpCache::load(Path::new(output_dir)).await.unwrap();
+        assert_eq!(
+            reloaded.get(MOCK_VIDEO_ID_1).unwrap().etag.as_deref(),
+            Some("\"v2\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_thumbnail_respects_max_rate() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let image_bytes = vec![7u8; 10_000];
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(200)
+            .with_body(&image_bytes)
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let options = DownloadOptions {
+            rate_limiter: Some(Arc::new(RateLimiter::new(20_000))),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(matches!(
+            result.unwrap(),
+            DownloadOutcome::Downloaded { .. }
+        ));
+        // 10,000 bytes at 20,000 bytes/sec take at least half a second.
+        assert!(start.elapsed() >= std::time::Duration::from_millis(500));
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert_eq!(fs::read(file_path).await.unwrap(), image_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_file_after_run() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let metrics_file = temp_dir.path().join("youtube_images.prom");
+        let mut server = mockito::Server::new_async().await;
+        let _playlist_mock = server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}},
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let _thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--metrics-file",
+            metrics_file.to_str().unwrap(),
+        ]);
+        let started = std::time::Instant::now();
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+        run_metrics(&summary, started.elapsed())
+            .write(Path::new(args.metrics_file.as_deref().unwrap()))
+            .await
+            .unwrap();
+
+        let contents = fs::read_to_string(&metrics_file).await.unwrap();
+        let value = |name: &str| -> f64 {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("no {} in {}", name, contents))
+                .parse()
+                .unwrap()
+        };
+        assert!(contents.contains("# TYPE youtube_images_downloads gauge\n"));
+        assert_eq!(value("youtube_images_downloads"), 2.0);
+        assert_eq!(value("youtube_images_failures"), 0.0);
+        assert_eq!(value("youtube_images_downloaded_bytes"), 30.0);
+        let duration = value("youtube_images_duration_seconds");
+        assert!((0.0..60.0).contains(&duration));
+        // The channel, playlist and video details, and whatever other tests spent.
+        assert!(value("youtube_images_quota_units_estimated") >= 3.0);
+    }
+
+    /// Builds the client `main` would for the given extra arguments.
+    fn client_for(extra_args: &[&str]) -> Client {
+        let mut argv = vec!["youtube-images", "-c", "url"];
+        argv.extend_from_slice(extra_args);
+        build_client(&Args::parse_from(argv)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_redirect_is_followed() {
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let _redirect_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(302)
+            .with_header("location", &format!("{}/moved.jpg", server.url()))
+            .create_async()
+            .await;
+        let image_mock = server
+            .mock("GET", "/moved.jpg")
+            .with_body("real_image")
+            .create_async()
+            .await;
+
+        let outcome = download_variant(
+            &client_for(&[]),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &location,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        image_mock.assert_async().await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert_eq!(fs::read(file_path).await.unwrap(), b"real_image");
+    }
+
+    #[tokio::test]
+    async fn test_compress_writes_a_gzipped_thumbnail() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            compress: true,
+            ..Default::default()
+        };
+
+        let outcome = download_variant(
+            &test_client(),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &location,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let DownloadOutcome::Downloaded { path, .. } = outcome else {
+            panic!("unexpected outcome {:?}", outcome);
+        };
+        assert_eq!(
+            path,
+            temp_dir.path().join(format!("{}.jpg.gz", MOCK_VIDEO_ID_1))
+        );
+        let mut contents = Vec::new();
+        GzDecoder::new(std::fs::File::open(&path).unwrap())
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"fake_image_data");
+        let uncompressed = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert!(!uncompressed.exists());
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_api_base_url_flag_directs_every_api_call() {
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        // One body that serves as both the playlist listing and the video details.
+        let api_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/youtube/v3/".to_string()))
+            .match_header("authorization", "Bearer token")
+            .with_body(
+                json!({"items": [
+                    {"id": MOCK_VIDEO_ID_1, "contentDetails": {"videoId": MOCK_VIDEO_ID_1, "duration": "PT5M"}}
+                ]})
+                .to_string(),
+            )
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        let thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let server_url = server.url();
+        let channel_url = format!("https://www.youtube.com/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--api-base-url",
+            &server_url,
+            "--thumbnail-base-url",
+            &server_url,
+            "--access-token",
+            "token",
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        assert_eq!(
+            Args::parse_from(["youtube-images", "-c", "url"]).api_base_url,
+            API_BASE_URL
+        );
+
+        assert_eq!(run_main(args).await.unwrap(), 0);
+        api_mock.assert_async().await;
+        thumbnail_mock.assert_async().await;
+        assert!(temp_dir
+            .path()
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_1))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_retries_server_error_transparently() {
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let unavailable_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let image_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("real_image")
+            .expect(1)
+            .create_async()
+            .await;
+
+        // No backoff in the options: only the client's middleware retries.
+        let outcome = download_variant(
+            &client_for(&["--retries", "1", "--jitter", "full"]),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &location,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        unavailable_mock.assert_async().await;
+        image_mock.assert_async().await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert_eq!(fs::read(file_path).await.unwrap(), b"real_image");
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_redirect_loop_fails_cleanly() {
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        // The first request plus the 2 redirects --max-redirects allows.
+        let loop_mock = server
+            .mock("GET", "/loop.jpg")
+            .with_status(302)
+            .with_header("location", &format!("{}/loop.jpg", server.url()))
+            .expect(3)
+            .create_async()
+            .await;
+
+        let error = download_variant(
+            &client_for(&["--max-redirects", "2"]),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/loop.jpg", server.url()),
+            &location,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap_err();
+
+        loop_mock.assert_async().await;
+        assert!(!error.is_transient());
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert!(!file_path.exists());
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_redirect_to_other_host_is_a_miss() {
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        // The same server under another name, standing in for an unrelated site.
+        let other_host = server.url().replace("127.0.0.1", "localhost");
+        let _redirect_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(302)
+            .with_header("location", &format!("{}/login", other_host))
+            .create_async()
+            .await;
+        let _page_mock = server
+            .mock("GET", "/login")
+            .with_body("<html>Sign in</html>")
+            .create_async()
+            .await;
+
+        let outcome = download_variant(
+            &client_for(&[]),
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &format!("{}/thumbnail.jpg", server.url()),
+            &location,
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        assert!(!thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg).exists());
+    }
+
+    #[tokio::test]
+    async fn test_export_csv_without_downloading() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let export_file = temp_dir.path().join("videos.csv");
+        let mut server = mockito::Server::new_async().await;
+
+        let playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=snippet,contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{
+                "contentDetails": {"videoId": MOCK_VIDEO_ID_1, "videoPublishedAt": "2024-03-15T10:00:00Z"},
+                "snippet": {
+                    "title": "Hello, \"world\"",
+                    "publishedAt": "2024-03-16T10:00:00Z",
+                    "thumbnails": {
+                        "default": {"url": "https://i.ytimg.com/vi/video1/default.jpg"},
+                        "high": {"url": "https://i.ytimg.com/vi/video1/hqdefault.jpg"}
+                    }
+                }
+            }]}).to_string())
+            .create_async().await;
+        let videos_mock = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [{
+                    "id": MOCK_VIDEO_ID_1,
+                    "contentDetails": {"duration": "PT1H2M3S"},
+                    "statistics": {"viewCount": "123456"},
+                    "status": {"privacyStatus": "public"}
+                }]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--export",
+            "csv",
+            "--export-file",
+            export_file.to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        playlist_mock.assert_async().await;
+        videos_mock.assert_async().await;
+        thumbnail_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 0);
+        assert!(!output_dir.exists());
+        // The like count is hidden, so its column is left empty.
+        assert_eq!(
+            fs::read_to_string(&export_file).await.unwrap(),
+            "video_id,title,published_at,thumbnail_url,duration_seconds,view_count,like_count\n\
+             video1,\"Hello, \"\"world\"\"\",2024-03-15T10:00:00Z,https://i.ytimg.com/vi/video1/hqdefault.jpg,3723,123456,\n"
+        );
+    }
+
+    /// Exports a channel with the given videos and titles into `export_file` with
+    /// `--merge-export`.
+    async fn merge_export(export_file: &Path, videos: &[(&str, &str)]) {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let items: Vec<serde_json::Value> = videos
+            .iter()
+            .map(|(video_id, title)| {
+                json!({
+                    "contentDetails": {"videoId": video_id},
+                    "snippet": {"title": title, "publishedAt": "2024-03-16T10:00:00Z"}
+                })
+            })
+            .collect();
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=snippet,contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await;
+        let video_ids: Vec<&str> = videos.iter().map(|(video_id, _)| *video_id).collect();
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--export",
+            "json",
+            "--export-file",
+            export_file.to_str().unwrap(),
+            "--merge-export",
+        ]);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_merge_export_keeps_earlier_runs() {
+        let temp_dir = tempdir().unwrap();
+        let export_file = temp_dir.path().join("videos.json");
+
+        merge_export(&export_file, &[("video1", "First"), ("video2", "Second")]).await;
+        merge_export(&export_file, &[("video3", "Third"), ("video2", "Renamed")]).await;
+
+        let exported: Vec<serde_json::Value> =
+            serde_json::from_str(&fs::read_to_string(&export_file).await.unwrap()).unwrap();
+        let videos: Vec<(&str, &str)> = exported
+            .iter()
+            .map(|record| {
+                (
+                    record["video_id"].as_str().unwrap(),
+                    record["title"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            videos,
+            [
+                ("video3", "Third"),
+                ("video2", "Renamed"),
+                ("video1", "First")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_export_needs_json() {
+        let args = Args::parse_from([
+            "youtube-images",
+            "--channel-url",
+            "https://www.youtube.com/@handle",
+            "--export",
+            "csv",
+            "--merge-export",
+        ]);
+        let error = run_main(args).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--merge-export only works with --export json."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_metadata_writes_sidecar() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let mut server = mockito::Server::new_async().await;
+
+        let playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=snippet,contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{
+                "contentDetails": {"videoId": MOCK_VIDEO_ID_1, "videoPublishedAt": "2024-03-15T10:00:00Z"},
+                "snippet": {
+                    "title": "A title",
+                    "description": "A description",
+                    "publishedAt": "2024-03-16T10:00:00Z",
+                    "channelTitle": "A channel"
+                }
+            }]}).to_string())
+            .create_async().await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let _videos_mock = mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1]).await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--with-metadata",
+        ]);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        playlist_mock.assert_async().await;
+        let sidecar = metadata::sidecar_path(&OutputLocation::new(&output_dir), MOCK_VIDEO_ID_1);
+        let contents = fs::read_to_string(sidecar).await.unwrap();
+        let metadata: VideoMetadata = serde_json::from_str(&contents).unwrap();
+        assert_eq!(
+            metadata,
+            VideoMetadata {
+                video_id: MOCK_VIDEO_ID_1.to_string(),
+                title: "A title".to_string(),
+                description: "A description".to_string(),
+                published_at: "2024-03-15T10:00:00Z".to_string(),
+                channel_title: "A channel".to_string(),
+                thumbnail_url: format!("{}/vi/{}/maxresdefault.jpg", server.url(), MOCK_VIDEO_ID_1),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download_leaves_only_part_file() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        // Fail the body stream after the first chunk, so the connection breaks mid-download.
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_status(200)
+            .with_chunked_body(|writer| {
+                writer.write_all(b"only_part_of_the_image")?;
+                writer.flush()?;
+                Err(std::io::Error::other("connection lost"))
+            })
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let result = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(result.is_err());
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert!(!file_path.exists());
+        assert!(partial_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_retries_download_after_connection_is_lost() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let thumbnail_path = format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1);
+        let broken_mock = server
+            .mock("GET", &*thumbnail_path)
+            .with_chunked_body(|writer| {
+                writer.write_all(b"fake_image")?;
+                writer.flush()?;
+                Err(std::io::Error::other("connection lost"))
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let retry_mock = server
+            .mock("GET", &*thumbnail_path)
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            backoff: Some(Arc::new(Backoff::new(
+                1,
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+                Jitter::Full,
+                0,
+            ))),
+            ..DownloadOptions::default()
+        };
+        let outcome = try_download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await;
+
+        broken_mock.assert_async().await;
+        retry_mock.assert_async().await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert_eq!(
+            std::fs::read_to_string(file_path).unwrap(),
+            "fake_image_data"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_webp_saves_webp_when_every_jpeg_is_missing() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let jpeg_mock = server
+            .mock("GET", mockito::Matcher::Regex(r"^/vi/.*\.jpg$".to_string()))
+            .with_status(404)
+            .expect(Resolution::ALL.len())
+            .create_async()
+            .await;
+        let webp_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi_webp/{}/maxresdefault.webp", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_webp_data")
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            try_webp: true,
+            ..DownloadOptions::default()
+        };
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        jpeg_mock.assert_async().await;
+        webp_mock.assert_async().await;
+        let webp_path = Path::new(output_dir).join(format!("{}.webp", MOCK_VIDEO_ID_1));
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Downloaded {
+                resolution: Resolution::MaxRes,
+                bytes: 14,
+                path: webp_path.clone(),
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(webp_path).unwrap(),
+            "fake_webp_data"
+        );
+        assert!(!Path::new(output_dir)
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_1))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_successful_download_leaves_no_part_file() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert!(file_path.exists());
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_partial_download_is_resumed_with_range_request() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        let mut server = mockito::Server::new_async().await;
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let part_path = partial_path(&file_path);
+        std::fs::write(&part_path, "fake_image").unwrap();
+        save_resume_info(
+            &resume_info_path(&part_path),
+            &test_thumbnail_url,
+            Some("\"v1\""),
+        )
+        .await
+        .unwrap();
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .match_header("range", "bytes=10-")
+            .match_header("if-range", "\"v1\"")
+            .with_status(206)
+            .with_header("content-range", "bytes 10-14/15")
+            .with_header("etag", "\"v1\"")
+            .with_body("_data")
+            .create_async()
+            .await;
+
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "fake_image_data"
+        );
+        assert!(!partial_path(&file_path).exists());
+        assert!(!resume_info_path(&part_path).exists());
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { bytes: 15, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_partial_download_restarts_when_the_thumbnail_changed() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        let mut server = mockito::Server::new_async().await;
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let part_path = partial_path(&file_path);
+        std::fs::write(&part_path, "stale_imag").unwrap();
+        save_resume_info(
+            &resume_info_path(&part_path),
+            &test_thumbnail_url,
+            Some("\"v1\""),
+        )
+        .await
+        .unwrap();
+        // The server ignores If-Range and sends the rest of a newer version.
+        let ranged_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .match_header("range", "bytes=10-")
+            .with_status(206)
+            .with_header("content-range", "bytes 10-14/15")
+            .with_header("etag", "\"v2\"")
+            .with_body("_data")
+            .create_async()
+            .await;
+        let full_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .match_header("range", mockito::Matcher::Missing)
+            .with_header("etag", "\"v2\"")
+            .with_body("fresh_image_new")
+            .create_async()
+            .await;
+
+        download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        ranged_mock.assert_async().await;
+        full_mock.assert_async().await;
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "fresh_image_new"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_download_of_another_variant_is_not_resumed() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        let mut server = mockito::Server::new_async().await;
+        let part_path = partial_path(&file_path);
+        std::fs::write(&part_path, "maxres_ima").unwrap();
+        save_resume_info(
+            &resume_info_path(&part_path),
+            &format!("{}/maxresdefault.jpg", server.url()),
+            Some("\"v1\""),
+        )
+        .await
+        .unwrap();
+        let mock = server
+            .mock("GET", "/hqdefault.jpg")
+            .match_header("range", mockito::Matcher::Missing)
+            .with_body("hq_image_data")
+            .create_async()
+            .await;
+
+        download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::High,
+            &format!("{}/hqdefault.jpg", server.url()),
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "hq_image_data"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_download_restarts_when_range_is_ignored() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        std::fs::write(partial_path(&file_path), "fake_image").unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &test_thumbnail_url,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "fake_image_data"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_partial_files() {
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        std::fs::write(temp_dir.path().join("video1.jpg.part"), "partial").unwrap();
+        std::fs::write(temp_dir.path().join("video1.jpg.part.resume"), "url").unwrap();
+        std::fs::write(temp_dir.path().join("video2.jpg"), "complete").unwrap();
+        let nested = temp_dir.path().join("2024").join("03");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("video3.jpg.part"), "partial").unwrap();
+        std::fs::write(nested.join("video4.jpg"), "complete").unwrap();
+        // A symlinked directory is left alone, as it may lead out of the output directory.
+        let elsewhere = tempdir().unwrap();
+        std::fs::write(elsewhere.path().join("other.jpg.part"), "partial").unwrap();
+        std::os::unix::fs::symlink(elsewhere.path(), temp_dir.path().join("link")).unwrap();
+
+        assert_eq!(
+            remove_partial_files(Path::new(output_dir)).await.unwrap(),
+            2
+        );
+        assert!(!temp_dir.path().join("video1.jpg.part").exists());
+        assert!(!temp_dir.path().join("video1.jpg.part.resume").exists());
+        assert!(temp_dir.path().join("video2.jpg").exists());
+        assert!(!nested.join("video3.jpg.part").exists());
+        assert!(nested.join("video4.jpg").exists());
+        assert!(elsewhere.path().join("other.jpg.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dir_template_places_thumbnail_by_upload_date() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let mut server = mockito::Server::new_async().await;
+
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
+            .create_async().await;
+        let details_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/videos?part=snippet,contentDetails,statistics,status&id={}&key={}",
+                    MOCK_VIDEO_ID_1, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [{
+                    "id": MOCK_VIDEO_ID_1,
+                    "snippet": {"publishedAt": "2024-03-15T10:00:00Z", "channelTitle": "Chan"}
+                }]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--dir-template",
+            "{year}/{month}",
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        details_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+        assert!(output_dir
+            .join("2024")
+            .join("03")
+            .join(format!("{}.jpg", MOCK_VIDEO_ID_1))
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_private_video_is_excluded_from_downloads() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let mut server = mockito::Server::new_async().await;
+        let deleted_video_id = "video3";
+
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [
+                {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}},
+                {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}},
+                {"contentDetails": {"videoId": deleted_video_id}}
+            ]}).to_string())
+            .create_async().await;
+        // The deleted video is missing from the response altogether.
+        let _details_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/videos?part=snippet,contentDetails,statistics,status&id={},{},{}&key={}",
+                    MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, deleted_video_id, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": MOCK_VIDEO_ID_1, "status": {"privacyStatus": "public", "uploadStatus": "processed"}},
+                    {"id": MOCK_VIDEO_ID_2, "status": {"privacyStatus": "private", "uploadStatus": "processed"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let public_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+        let private_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_2),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let deleted_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", deleted_video_id),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("http://any.url/channel/{}", MOCK_CHANNEL_ID),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        public_mock.assert_async().await;
+        private_mock.assert_async().await;
+        deleted_mock.assert_async().await;
+        assert_eq!(
+            (summary.downloaded, summary.failed, summary.unavailable),
+            (1, 0, 2)
+        );
+        assert_eq!(summary.thumbnail_paths.len(), 1);
+    }
+
+    /// Runs a channel with a public video and an age-restricted one whose thumbnails are
+    /// all missing, checking whether the restricted thumbnail was requested.
+    async fn run_with_restricted_video(extra_args: &[&str], requested: bool) -> Summary {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [
+                {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}},
+                {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}
+            ]}).to_string())
+            .create_async().await;
+        let _details_mock = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [
+                    {"id": MOCK_VIDEO_ID_1, "contentDetails": {"duration": "PT5M"}},
+                    {"id": MOCK_VIDEO_ID_2, "contentDetails": {
+                        "duration": "PT5M",
+                        "contentRating": {"ytRating": "ytAgeRestricted"}
+                    }}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _public_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
+        let restricted_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!("^/vi/{}/", MOCK_VIDEO_ID_2)),
+            )
+            .with_status(404)
+            .expect_at_least(usize::from(requested))
+            .expect_at_most(if requested { usize::MAX } else { 0 })
+            .create_async()
+            .await;
+
+        let server_url = server.url();
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let mut argv = vec![
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server_url,
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ];
+        argv.extend_from_slice(extra_args);
+        let summary = run(
+            &client,
+            MOCK_CREDENTIALS,
+            &Args::parse_from(argv),
+            &server_url,
+        )
+        .await
+        .unwrap();
+        restricted_mock.assert_async().await;
+        summary
+    }
+
+    #[tokio::test]
+    async fn test_restricted_video_is_counted_as_restricted_not_failed() {
+        let summary = run_with_restricted_video(&[], true).await;
+
+        assert_eq!(
+            (summary.downloaded, summary.failed, summary.restricted),
+            (1, 0, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_restricted_skips_the_download() {
+        let summary = run_with_restricted_video(&["--skip-restricted"], false).await;
+
+        assert_eq!(
+            (summary.downloaded, summary.failed, summary.restricted),
+            (1, 0, 1)
+        );
+        assert_eq!(summary.thumbnail_paths.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_stores_identical_thumbnails_once() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mut mocks = Vec::new();
+        for video_id in [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2] {
+            mocks.push(
+                server
+                    .mock("GET", &*format!("/{}.jpg", video_id))
+                    .with_body("same_template_image")
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let options = DownloadOptions {
+            dedup: Some(Arc::new(DedupIndex::default())),
+            ..Default::default()
+        };
+        for video_id in [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2] {
+            let url = format!("{}/{}.jpg", server.url(), video_id);
+            let outcome = download_variant(
+                &client,
+                video_id,
+                Resolution::MaxRes,
+                &url,
+                &OutputLocation::new(output_dir),
+                &options,
+            )
+            .await
+            .unwrap();
+            assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        }
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        let original = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        let duplicate = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_2));
+        let real_files: Vec<_> = std::fs::read_dir(output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| !path.symlink_metadata().unwrap().is_symlink())
+            .collect();
+        assert_eq!(real_files, vec![original.clone()]);
+        #[cfg(unix)]
+        {
+            assert!(duplicate.symlink_metadata().unwrap().is_symlink());
+            assert_eq!(fs::read(&duplicate).await.unwrap(), b"same_template_image");
+        }
+        #[cfg(not(unix))]
+        assert!(!duplicate.exists());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_downloads_new_videos_on_later_checks() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let playlist_path = format!(
+            "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+            MOCK_UPLOADS_ID, MOCK_API_KEY
+        );
+
+        // mockito serves the first matching mock that still expects hits, so the first
+        // check sees one video and the second check sees a new upload as well.
+        let first_playlist_mock = server
+            .mock("GET", &*playlist_path)
+            .with_body(
+                json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let second_playlist_mock = server
+            .mock("GET", &*playlist_path)
+            .with_body(
+                json!({"items": [
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}},
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}
+                ]})
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let thumbnail_mock_1 = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .expect(1)
+            .create_async()
+            .await;
+        let thumbnail_mock_2 = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_2),
+            )
+            .with_body("image2")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            output_dir,
+            "--watch",
+            "5",
+        ]);
+        watch(&client, MOCK_CREDENTIALS, &args, &server.url(), Some(2)).await;
+
+        first_playlist_mock.assert_async().await;
+        second_playlist_mock.assert_async().await;
+        thumbnail_mock_1.assert_async().await;
+        thumbnail_mock_2.assert_async().await;
+        let state = DownloadState::load(&Path::new(output_dir).join(state::STATE_FILE))
+            .await
+            .unwrap();
+        assert!(state.contains(MOCK_VIDEO_ID_1));
+        assert!(state.contains(MOCK_VIDEO_ID_2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_continues_after_failed_check() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let playlist_path = format!(
+            "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+            MOCK_UPLOADS_ID, MOCK_API_KEY
+        );
+        let failing_mock = server
+            .mock("GET", &*playlist_path)
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let playlist_mock = server
+            .mock("GET", &*playlist_path)
+            .with_body(
+                json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1]).await;
+        let thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--watch",
+            "1",
+        ]);
+        watch(&client, MOCK_CREDENTIALS, &args, &server.url(), Some(2)).await;
+
+        failing_mock.assert_async().await;
+        playlist_mock.assert_async().await;
+        thumbnail_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_state_file_skips_already_downloaded_videos() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let state_file = temp_dir.path().join("state.json");
+        let state = DownloadState::load(&state_file).await.unwrap();
+        state.mark_downloaded(MOCK_VIDEO_ID_1);
+        state.save(false).await.unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}},
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let skipped_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let new_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_2),
+            )
+            .with_body("image2")
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--state-file",
+            state_file.to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        assert_eq!(summary.downloaded, 1);
+        skipped_mock.assert_async().await;
+        new_mock.assert_async().await;
+        let state = DownloadState::load(&state_file).await.unwrap();
+        assert!(state.contains(MOCK_VIDEO_ID_2));
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_if_different_skips_download_when_sizes_match() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        fs::write(&file_path, b"complete_image").await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+
+        let head_mock = server
+            .mock("HEAD", "/thumbnail.jpg")
+            .with_header("content-length", "14")
+            .create_async()
+            .await;
+        let get_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            overwrite: Overwrite::IfDifferent,
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(outcome, DownloadOutcome::Skipped { .. }));
+        head_mock.assert_async().await;
+        get_mock.assert_async().await;
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"complete_image");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_if_different_downloads_when_sizes_differ() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        fs::write(&file_path, b"stale").await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+
+        let head_mock = server
+            .mock("HEAD", "/thumbnail.jpg")
+            .with_header("content-length", "14")
+            .create_async()
+            .await;
+        let get_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("complete_image")
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            overwrite: Overwrite::IfDifferent,
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { bytes: 14, .. }
+        ));
+        head_mock.assert_async().await;
+        get_mock.assert_async().await;
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"complete_image");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_if_different_downloads_when_head_is_unsupported() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        fs::write(&file_path, b"complete_image").await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+
+        let _head_mock = server
+            .mock("HEAD", "/thumbnail.jpg")
+            .with_status(405)
+            .create_async()
+            .await;
+        let get_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("complete_image")
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            overwrite: Overwrite::IfDifferent,
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        get_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_download_best_thumbnail_falls_back_to_smaller_resolution() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mut missing_mocks = Vec::new();
+        for file_name in ["maxresdefault.jpg", "sddefault.jpg"] {
+            missing_mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/{}", MOCK_VIDEO_ID_1, file_name))
+                    .with_status(404)
+                    .create_async()
+                    .await,
+            );
+        }
+        let high_mock = server
+            .mock("GET", &*format!("/vi/{}/hqdefault.jpg", MOCK_VIDEO_ID_1))
+            .with_body("hq_image")
+            .create_async()
+            .await;
+        let medium_mock = server
+            .mock("GET", &*format!("/vi/{}/mqdefault.jpg", MOCK_VIDEO_ID_1))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Downloaded {
+                resolution: Resolution::High,
+                bytes: 8,
+                path: thumbnail_path(
+                    &OutputLocation::new(output_dir),
+                    MOCK_VIDEO_ID_1,
+                    OutputFormat::Jpg
+                ),
+            }
+        );
+        for mock in missing_mocks {
+            mock.assert_async().await;
+        }
+        high_mock.assert_async().await;
+        medium_mock.assert_async().await;
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert_eq!(fs::read(file_path).await.unwrap(), b"hq_image");
+    }
+
+    #[cfg(feature = "image-processing")]
+    /// Encodes an image like YouTube's gray 120x90 "no thumbnail" placeholder.
+    fn placeholder_jpeg() -> Vec<u8> {
+        let image = image::RgbImage::from_pixel(120, 90, image::Rgb([204, 204, 204]));
+        let mut output = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut output, image::ImageFormat::Jpeg)
+            .unwrap();
+        output.into_inner()
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_placeholder_thumbnails_fall_through_to_next_resolution() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let placeholder_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("/(maxres|sd)default.jpg$".to_string()),
+            )
+            .with_body(placeholder_jpeg())
+            .expect(2)
+            .create_async()
+            .await;
+        let high_mock = server
+            .mock("GET", &*format!("/vi/{}/hqdefault.jpg", MOCK_VIDEO_ID_1))
+            .with_body("hq_image")
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            placeholders: vec![placeholder::DEFAULT_PLACEHOLDER.parse().unwrap()],
+            ..Default::default()
+        };
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        placeholder_mock.assert_async().await;
+        high_mock.assert_async().await;
+        assert_eq!(outcome.resolution(), Some(Resolution::High));
+        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
+        assert_eq!(fs::read(file_path).await.unwrap(), b"hq_image");
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_placeholder_thumbnail_is_not_kept() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let location = OutputLocation::new(output_dir);
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", mockito::Matcher::Any)
+            .with_body(placeholder_jpeg())
+            .create_async()
+            .await;
+        let url = format!("{}/default.jpg", server.url());
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+
+        // The 120x90 signature doesn't apply to default.jpg, which is always that size...
+        let options = DownloadOptions {
+            placeholders: vec![placeholder::DEFAULT_PLACEHOLDER.parse().unwrap()],
+            ..Default::default()
+        };
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::Default,
+            &url,
+            &location,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.resolution(), Some(Resolution::Default));
+        fs::remove_file(&file_path).await.unwrap();
+
+        // ...but a hash signature does.
+        let hash = Sha256::digest(placeholder_jpeg()).into();
+        let options = DownloadOptions {
+            placeholders: vec![PlaceholderSignature::Sha256(hash)],
+            ..Default::default()
+        };
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::Default,
+            &url,
+            &location,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        assert!(!file_path.exists());
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[test]
+    fn test_keep_placeholders_disables_detection() {
+        let args = Args::try_parse_from(["youtube-images", "-c", "url"]).unwrap();
+        assert_eq!(
+            DownloadOptions::from_args(&args).placeholders,
+            [PlaceholderSignature::Dimensions(120, 90)]
+        );
+        let args =
+            Args::try_parse_from(["youtube-images", "-c", "url", "--keep-placeholders"]).unwrap();
+        assert!(DownloadOptions::from_args(&args).placeholders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_download_thumbnail_at_exact_resolution() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let maxres_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let high_mock = server
+            .mock("GET", &*format!("/vi/{}/hqdefault.jpg", MOCK_VIDEO_ID_1))
+            .with_body("hq_image")
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            resolution: Some(Resolution::High),
+            ..Default::default()
+        };
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        maxres_mock.assert_async().await;
+        high_mock.assert_async().await;
+        assert_eq!(outcome.resolution(), Some(Resolution::High));
+    }
+
+    #[tokio::test]
+    async fn test_download_thumbnail_at_missing_resolution_does_not_fall_back() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let maxres_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_status(404)
+            .create_async()
+            .await;
+        let fallback_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("(sd|hq|mq)default.jpg$".to_string()),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            resolution: Some(Resolution::MaxRes),
+            ..Default::default()
+        };
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        maxres_mock.assert_async().await;
+        fallback_mock.assert_async().await;
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        assert!(!thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg
+        )
+        .exists());
+    }
+
+    /// Serves the maxres and hq variants of `MOCK_VIDEO_ID_1`, and a 404 for the others.
+    async fn mock_two_variants(server: &mut mockito::ServerGuard) -> Vec<mockito::Mock> {
+        let mut mocks = Vec::new();
+        for (file, body) in [("maxresdefault", "maxres_image"), ("hqdefault", "hq_image")] {
+            mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/{}.jpg", MOCK_VIDEO_ID_1, file))
+                    .with_body(body)
+                    .create_async()
+                    .await,
+            );
+        }
+        mocks.push(
+            server
+                .mock(
+                    "GET",
+                    mockito::Matcher::Regex("/(sd|mq)?default.jpg$".to_string()),
+                )
+                .with_status(404)
+                .expect(3)
+                .create_async()
+                .await,
+        );
+        mocks
+    }
+
+    #[tokio::test]
+    async fn test_all_resolutions_names_variants_after_the_template() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mocks = mock_two_variants(&mut server).await;
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "--all-resolutions",
+            "--variant-template",
+            "{id}_{res}",
+        ]);
+        let options = DownloadOptions::from_args(&args);
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(temp_dir.path().to_str().unwrap()),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        let maxres = temp_dir
+            .path()
+            .join(format!("{}_maxres.jpg", MOCK_VIDEO_ID_1));
+        let high = temp_dir.path().join(format!("{}_hq.jpg", MOCK_VIDEO_ID_1));
+        assert_eq!(std::fs::read(&maxres).unwrap(), b"maxres_image");
+        assert_eq!(std::fs::read(&high).unwrap(), b"hq_image");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Downloaded {
+                resolution: Resolution::MaxRes,
+                bytes: 20,
+                path: maxres,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_all_resolutions_saves_variants_in_a_directory_per_video() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mocks = mock_two_variants(&mut server).await;
+        let options = DownloadOptions {
+            all_resolutions: true,
+            ..Default::default()
+        };
+
+        download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(temp_dir.path().to_str().unwrap()),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        let video_dir = temp_dir.path().join(MOCK_VIDEO_ID_1);
+        assert!(video_dir.join("maxresdefault.jpg").exists());
+        assert!(video_dir.join("hqdefault.jpg").exists());
+        assert!(!video_dir.join("sddefault.jpg").exists());
+    }
+
+    #[test]
+    fn test_variant_template_flag() {
+        let parse = |extra: &[&str]| {
+            Args::try_parse_from(["youtube-images", "-c", "url"].iter().chain(extra))
+        };
+        assert!(parse(&["--all-resolutions", "--variant-template", "{id}-{res}"]).is_ok());
+        assert!(parse(&["--all-resolutions", "--variant-template", "{id}"]).is_err());
+        assert!(parse(&["--variant-template", "{id}-{res}"]).is_err());
+        assert!(parse(&["--all-resolutions", "--resolution", "hq"]).is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_base_url_flag() {
+        let args = Args::parse_from(["youtube-images", "-c", "url", "-o", "out"]);
+        assert_eq!(args.thumbnail_base_url, THUMBNAIL_BASE_URL);
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--thumbnail-base-url",
+            "https://i.ytimg.com",
+        ]);
+        assert_eq!(args.thumbnail_base_url, "https://i.ytimg.com");
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_sent_instead_of_api_key() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=contentDetails&id={}",
+                    MOCK_CHANNEL_ID
+                ),
+            )
+            .match_header("authorization", "Bearer test_access_token")
+            .with_body(
+                json!({"items": [{
+                    "id": MOCK_CHANNEL_ID,
+                    "contentDetails": {"relatedPlaylists": {"uploads": MOCK_UPLOADS_ID}}
+                }]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let playlist_id = get_uploads_playlist_id(
+            &client,
+            Credentials::AccessToken("test_access_token"),
+            MOCK_CHANNEL_ID,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(playlist_id.as_deref(), Some(MOCK_UPLOADS_ID));
+    }
+
+    #[test]
+    fn test_derive_uploads_playlist_id() {
+        assert_eq!(
+            derive_uploads_playlist_id("UCuAXFkgsw1L7xaCfnd5JJOw").as_deref(),
+            Some("UUuAXFkgsw1L7xaCfnd5JJOw")
+        );
+        assert_eq!(derive_uploads_playlist_id("UC"), None);
+        assert_eq!(derive_uploads_playlist_id("HCxyz"), None);
+    }
+
+    #[test]
+    fn test_adaptive_concurrency_flags() {
+        let parse = |extra: &[&str]| {
+            Args::try_parse_from(
+                [
+                    "youtube-images",
+                    "--channel-url",
+                    "https://www.youtube.com/@handle",
+                ]
+                .iter()
+                .chain(extra),
+            )
+        };
+        let args = parse(&["--adaptive-concurrency", "--max-concurrency", "8"]).unwrap();
+        let adaptive = DownloadOptions::from_args(&args).adaptive.unwrap();
+        assert_eq!(adaptive.limit(), 2);
+        assert!(parse(&["--adaptive-concurrency", "--concurrency", "4"]).is_err());
+        assert!(parse(&["--min-concurrency", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_channel_id() {
+        assert_eq!(
+            parse_channel_id("UCuAXFkgsw1L7xaCfnd5JJOw").as_deref(),
+            Ok("UCuAXFkgsw1L7xaCfnd5JJOw")
+        );
+        assert!(parse_channel_id("UCuAXFkgsw1L7xaCfnd5JJO").is_err());
+        assert!(parse_channel_id("UUuAXFkgsw1L7xaCfnd5JJOw").is_err());
+        assert!(parse_channel_id("UCuAXFkgsw1L7xaCfnd5JJ/w").is_err());
+        assert!(Args::try_parse_from(["youtube-images", "--channel-id", "@handle"]).is_err());
+        assert!(Args::try_parse_from([
+            "youtube-images",
+            "--channel-id",
+            "UCuAXFkgsw1L7xaCfnd5JJOw",
+            "--channel-url",
+            "https://www.youtube.com/@handle",
+        ])
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_downloads_without_the_data_api() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/youtube/v3/".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+        let _channel_mock = server
+            .mock("GET", "/@handle")
+            .with_body(source::tests::VIDEOS_PAGE)
+            .create_async()
+            .await;
+        let _videos_mock = server
+            .mock("GET", "/channel/UCuAXFkgsw1L7xaCfnd5JJOw/videos")
+            .with_body(source::tests::VIDEOS_PAGE)
+            .create_async()
+            .await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/vi/.*/maxresdefault.jpg$".to_string()),
+            )
+            .with_body("image")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--scrape",
+            "--watch-base-url",
+            &server.url(),
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &format!("{}/@handle", server.url()),
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        let summary = run(&client, Credentials::ApiKey(""), &args, &server.url())
+            .await
+            .unwrap();
+
+        api_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 2);
+        assert!(temp_dir.path().join("dQw4w9WgXcQ.jpg").exists());
+        assert!(temp_dir.path().join("9bZkp7q19f0.jpg").exists());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_rejects_flags_that_need_the_api() {
+        let args = Args::parse_from([
+            "youtube-images",
+            "--scrape",
+            "--channel-url",
+            "https://www.youtube.com/@handle",
+            "--skip-shorts",
+        ]);
+        let error = run_main(args).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--skip-shorts needs the YouTube Data API, which --scrape doesn't use."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_id_flag_skips_resolution() {
+        let channel_id = "UCuAXFkgsw1L7xaCfnd5JJOw";
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let resolution_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/youtube/v3/(search|channels)".to_string()),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId=UUuAXFkgsw1L7xaCfnd5JJOw&key={}&maxResults=50",
+                    MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1]).await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-id",
+            channel_id,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        resolution_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_uses_derived_playlist_without_channels_call() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let channel_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/youtube/v3/channels".to_string()),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId=UU_test_channel_id&key={}&maxResults=50",
+                    MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
+            .create_async()
+            .await;
+
+        let (playlist_id, items) = list_uploads(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_CHANNEL_ID,
+            "contentDetails",
+            50,
+            None,
+            &server.url(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        channel_mock.assert_async().await;
+        playlist_mock.assert_async().await;
+        assert_eq!(playlist_id, "UU_test_channel_id");
+        assert_eq!(items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_looks_up_playlist_when_derived_one_is_empty() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let playlist_path = |playlist_id: &str| {
+            format!(
+                "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                playlist_id, MOCK_API_KEY
+            )
+        };
+        let derived_mock = server
+            .mock("GET", &*playlist_path("UU_test_channel_id"))
+            .with_body(json!({"items": []}).to_string())
+            .create_async()
+            .await;
+        let channel_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=contentDetails&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [{"contentDetails": {"relatedPlaylists": {"uploads": "UU_other"}}}]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        let other_mock = server
+            .mock("GET", &*playlist_path("UU_other"))
+            .with_body(
+                json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string(),
+            )
+            .create_async()
+            .await;
+
+        let (playlist_id, items) = list_uploads(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_CHANNEL_ID,
+            "contentDetails",
+            50,
+            None,
+            &server.url(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        derived_mock.assert_async().await;
+        channel_mock.assert_async().await;
+        other_mock.assert_async().await;
+        assert_eq!(playlist_id, "UU_other");
+        assert_eq!(items[0].content_details.video_id, MOCK_VIDEO_ID_2);
+    }
+
+    #[tokio::test]
+    async fn test_get_uploads_playlist_id_for_channel_without_uploads() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=contentDetails&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"id": MOCK_CHANNEL_ID}]}).to_string())
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_uploads_playlist_id_for_missing_channel() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=contentDetails&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": []}).to_string())
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        mock.assert_async().await;
+        let error = result.unwrap_err();
+        assert!(
+            error.to_string().contains("not found"),
+            "unexpected error: {}",
+            error
+        );
+        assert_eq!(
+            error::exit_code(error.as_ref()),
+            error::EXIT_CHANNEL_NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded_response_is_typed() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_status(403)
+            .with_body(
+                json!({"error": {
+                    "code": 403,
+                    "message": "The request cannot be completed because you have exceeded your quota.",
+                    "errors": [{"reason": "quotaExceeded", "domain": "youtube.quota"}]
+                }})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        mock.assert_async().await;
+        let error = result.unwrap_err();
+        assert!(is_quota_exceeded(error.as_ref()));
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_QUOTA_EXCEEDED);
+    }
+
+    #[tokio::test]
+    async fn test_api_error_response_keeps_message() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_body(
+                json!({"error": {
+                    "code": 400,
+                    "message": "Invalid value for the id parameter.",
+                    "errors": [{"reason": "badRequest"}]
+                }})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YouTube Data API error (400): Invalid value for the id parameter."
+        );
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_FAILURE);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_api_key_response_is_typed() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_body(
+                json!({"error": {
+                    "code": 400,
+                    "message": "API key not valid. Please pass a valid API key.",
+                    "errors": [{
+                        "message": "API key not valid. Please pass a valid API key.",
+                        "domain": "global",
+                        "reason": "badRequest"
+                    }],
+                    "status": "INVALID_ARGUMENT",
+                    "details": [{"reason": "API_KEY_INVALID"}]
+                }})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DownloaderError>(),
+            Some(DownloaderError::InvalidApiKey(_))
+        ));
+        assert!(error.to_string().contains("YouTube Data API v3 is enabled"));
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_INVALID_INPUT);
+    }
+
+    #[tokio::test]
+    async fn test_key_invalid_reason_is_typed() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_status(400)
+            .with_body(
+                json!({"error": {
+                    "code": 400,
+                    "message": "Bad Request",
+                    "errors": [{"reason": "keyInvalid", "domain": "usageLimits"}]
+                }})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The YouTube Data API rejected the API key: Bad Request. Check YOUTUBE_API_KEY and \
+             that the YouTube Data API v3 is enabled in the key's Google Cloud project."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_api_response_quotes_the_body() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .with_body("<html>Service temporarily rearranged</html>")
+            .create_async()
+            .await;
+
+        let result =
+            get_uploads_playlist_id(&client, MOCK_CREDENTIALS, MOCK_CHANNEL_ID, &server.url())
+                .await;
+
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DownloaderError>(),
+            Some(DownloaderError::MalformedResponse { .. })
+        ));
+        assert!(
+            error
+                .to_string()
+                .contains("<html>Service temporarily rearranged</html>"),
+            "unexpected error: {}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_api_response_snippet_is_shortened() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let body = format!("{{\"items\": [\"{}", "x".repeat(500));
+        let _mock = server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(mockito::Matcher::Any)
+            .with_body(&body)
+            .create_async()
+            .await;
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        match result.unwrap_err().downcast_ref::<DownloaderError>() {
+            Some(DownloaderError::MalformedResponse { snippet, .. }) => {
+                assert_eq!(snippet.as_str(), &body[..MALFORMED_BODY_SNIPPET_CHARS]);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_channel_url_exit_code() {
+        let error = resolve_channel_id(
+            &test_client(),
+            MOCK_CREDENTIALS,
+            "not a url",
+            None,
+            "http://unused",
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_INVALID_INPUT);
+    }
+
+    #[tokio::test]
+    async fn test_process_channel_with_empty_uploads_playlist() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _channel_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=contentDetails&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [{"contentDetails": {"relatedPlaylists": {"uploads": MOCK_UPLOADS_ID}}}]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        let playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_status(404)
+            .with_body(json!({"error": {"code": 404, "message": "playlistNotFound"}}).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &[]).await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        playlist_mock.assert_async().await;
+        assert_eq!(summary, Summary::default());
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_min_width_discards_small_thumbnail() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        // tiny_jpeg is 4x3 pixels.
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body(convert::tests::tiny_jpeg())
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            min_width: Some(120),
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        let file_path = thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg,
+        );
+        assert!(!file_path.exists());
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[tokio::test]
+    async fn test_min_height_keeps_large_enough_thumbnail() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body(convert::tests::tiny_jpeg())
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            min_height: Some(3),
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        assert!(thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg
+        )
+        .exists());
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_url_uses_cached_id() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let search_mock = server
+            .mock("GET", "/youtube/v3/search")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        let cache = ChannelCache::load(output_dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        cache.insert(&channel_url, MOCK_CHANNEL_ID);
+
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            Some(&cache),
+            None,
+            &server.url(),
+        )
+        .await;
+
+        search_mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_id_from_url_refreshes_expired_cache_entry() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let search_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[(MOCK_CHANNEL_ID, MOCK_HANDLE)]).await;
+
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        std::fs::write(
+            temp_dir.path().join(channel_cache::CHANNEL_CACHE_FILE),
+            json!({ channel_url.clone(): {"channel_id": "UC_stale_id", "resolved_at": 0} })
+                .to_string(),
+        )
+        .unwrap();
+        let cache = ChannelCache::load(output_dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            Some(&cache),
+            None,
+            &server.url(),
+        )
+        .await;
+
+        search_mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+        let reloaded = ChannelCache::load(output_dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(reloaded.get(&channel_url).as_deref(), Some(MOCK_CHANNEL_ID));
+    }
+
+    /// Runs a channel of two videos, the first with a thumbnail and the second without,
+    /// reporting progress to `progress`. Returns the channel URL.
+    async fn run_with_progress(progress: ProgressCallback) -> String {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_1}},
+                    {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .create_async()
+            .await;
+        let _missing_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!("^/vi/{}/", MOCK_VIDEO_ID_2)),
+            )
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--json",
+        ]);
+        let options = DownloadOptions {
+            progress: Some(Arc::new(progress)),
+            ..DownloadOptions::from_args(&args)
+        };
+        run_with_options(&client, MOCK_CREDENTIALS, &args, &options, &server.url())
+            .await
+            .unwrap();
+        channel_url
+    }
+
+    #[tokio::test]
+    async fn test_json_progress_events() {
+        let buffer = progress::tests::SharedBuffer::default();
+        let channel_url =
+            run_with_progress(EventSink::new(Box::new(buffer.clone())).into_callback()).await;
+
+        let mut events: Vec<serde_json::Value> = buffer
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("every line is a JSON object"))
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            json!({"event": "resolved", "channel_url": channel_url, "channel_id": MOCK_CHANNEL_ID})
+        );
+        assert_eq!(events[3], json!({"event": "summary", "ok": 1, "failed": 1}));
+        // Downloads finish in any order.
+        let mut downloads: Vec<serde_json::Value> = events.drain(1..3).collect();
+        downloads.sort_by_key(|event| event["video_id"].as_str().unwrap().to_string());
+        assert_eq!(
+            downloads,
+            vec![
+                json!({"event": "download", "video_id": MOCK_VIDEO_ID_1, "status": "ok", "bytes": 6}),
+                json!({"event": "download", "video_id": MOCK_VIDEO_ID_2, "status": "failed"}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_progress_callback_receives_each_step() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback = {
+            let received = received.clone();
+            ProgressCallback::new(move |event| received.lock().unwrap().push(event))
+        };
+
+        let channel_url = run_with_progress(callback).await;
+
+        let mut events = received.lock().unwrap().clone();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            ProgressEvent::Resolved {
+                channel_url,
+                channel_id: MOCK_CHANNEL_ID.to_string(),
+            }
+        );
+        assert_eq!(events[3], ProgressEvent::Finished { ok: 1, failed: 1 });
+        // Downloads finish in any order.
+        let mut downloads: Vec<ProgressEvent> = events.drain(1..3).collect();
+        downloads.sort_by_key(|event| matches!(event, ProgressEvent::Failed { .. }));
+        assert_eq!(
+            downloads,
+            [
+                ProgressEvent::Downloaded {
+                    video_id: MOCK_VIDEO_ID_1.to_string(),
+                    bytes: 6,
+                },
+                ProgressEvent::Failed {
+                    video_id: MOCK_VIDEO_ID_2.to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_playlist_name_downloads_matching_playlist() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let playlists_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlists?part=snippet&channelId={}&key={}&maxResults=50",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": "PL_other", "snippet": {"title": "Livestreams"}},
+                    {"id": "PL_tutorials", "snippet": {"title": "Rust Tutorials"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let uploads_mock = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let playlist_items_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId=PL_tutorials&key={}&maxResults=50",
+                    MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1]).await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--playlist-name",
+            "rust tutorials",
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        playlists_mock.assert_async().await;
+        uploads_mock.assert_async().await;
+        playlist_items_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_find_playlist_by_name_without_match() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _playlists_mock = server
+            .mock("GET", "/youtube/v3/playlists")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [{"id": "PL_other", "snippet": {"title": "Livestreams"}}]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let result = find_playlist_by_name(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_CHANNEL_ID,
+            "Tutorials",
+            &server.url(),
+        )
+        .await;
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("no playlist named \"Tutorials\""),
+            "unexpected error: {}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksums_file_lists_downloaded_thumbnails() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let fixtures = [(MOCK_VIDEO_ID_1, "image1"), (MOCK_VIDEO_ID_2, "image2")];
+
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}, {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string())
+            .create_async().await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let mut thumbnail_mocks = Vec::new();
+        for (video_id, body) in fixtures {
+            thumbnail_mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/maxresdefault.jpg", video_id))
+                    .with_body(body)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--checksums",
+        ]);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        let expected: String = fixtures
+            .iter()
+            .map(|(video_id, body)| {
+                let hash: String = Sha256::digest(body.as_bytes())
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                format!("{}  {}.jpg\n", hash, video_id)
+            })
+            .collect();
+        let contents =
+            std::fs::read_to_string(temp_dir.path().join(checksums::CHECKSUMS_FILE)).unwrap();
+        assert_eq!(contents, expected);
+    }
+
+    #[tokio::test]
+    async fn test_stats_only_counts_every_upload() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _first_page = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"nextPageToken": "page2", "items": [
+                {"contentDetails": {"videoId": "video3", "videoPublishedAt": "2024-05-01T10:00:00Z"}},
+                {"contentDetails": {"videoId": "video2", "videoPublishedAt": "2023-01-20T10:00:00Z"}}
+            ]}).to_string())
+            .create_async().await;
+        let _second_page = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50&pageToken=page2", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [
+                {"contentDetails": {"videoId": "video1", "videoPublishedAt": "2021-07-04T10:00:00Z"}}
+            ]}).to_string())
+            .create_async().await;
+        let thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let mut output = Vec::new();
+        print_channel_stats(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            MAX_PAGE_SIZE,
+            None,
+            &mut output,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        thumbnail_mock.assert_async().await;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Channel: {} ({})\n\
+                 Videos: 3\n\
+                 Oldest upload: 2021-07-04T10:00:00Z\n\
+                 Newest upload: 2024-05-01T10:00:00Z\n\
+                 Estimated thumbnails: 3 (one per video)\n",
+                channel_url, MOCK_CHANNEL_ID
+            )
+        );
+        assert!(
+            Args::try_parse_from(["youtube-images", "-c", "url", "--stats-only"])
+                .unwrap()
+                .stats_only
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_missing_downloads_the_gaps() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        std::fs::write(
+            temp_dir.path().join(format!("{}.jpg", MOCK_VIDEO_ID_1)),
+            "existing",
+        )
+        .unwrap();
+
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}, {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string())
+            .create_async().await;
+        let _videos_mock =
+            mock_public_videos(&mut server, &[MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]).await;
+        let present_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+        let missing_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_2),
+            )
+            .with_body("image2")
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--only-missing",
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        present_mock.assert_async().await;
+        missing_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join(format!("{}.jpg", MOCK_VIDEO_ID_1)))
+                .unwrap(),
+            "existing"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_logs_carry_the_video_id() {
+        let buffer = progress::tests::SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        // The test runtime is single-threaded, so spawned tasks use this subscriber too.
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _maxres_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_status(404)
+            .create_async()
+            .await;
+        let _sd_mock = server
+            .mock("GET", &*format!("/vi/{}/sddefault.jpg", MOCK_VIDEO_ID_1))
+            .with_body("image1")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "-c",
+            "url",
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        download_videos(
+            &client,
+            &args,
+            vec![MOCK_VIDEO_ID_1.to_string()],
+            &output_location(&args),
+            &DownloadOptions::from_args(&args),
+            &HashMap::new(),
+            HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let logs = buffer.contents();
+        let span = format!("download{{video_id={}}}", MOCK_VIDEO_ID_1);
+        for message in ["thumbnail variant not available", "saved thumbnail"] {
+            let line = logs
+                .lines()
+                .find(|line| line.contains(message))
+                .unwrap_or_else(|| panic!("no \"{}\" event in:\n{}", message, logs));
+            assert!(line.contains(&span), "no video ID in: {}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summary_counts_received_bytes() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let fixtures = [
+            (MOCK_VIDEO_ID_1, "a".repeat(1500)),
+            (MOCK_VIDEO_ID_2, "b".repeat(700)),
+        ];
+        let mut mocks = Vec::new();
+        for (video_id, body) in &fixtures {
+            mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/maxresdefault.jpg", video_id))
+                    .with_body(body)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "-c",
+            "url",
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        let options = DownloadOptions::from_args(&args);
+        let summary = download_videos(
+            &client,
+            &args,
+            vec![MOCK_VIDEO_ID_1.to_string(), MOCK_VIDEO_ID_2.to_string()],
+            &output_location(&args),
+            &options,
+            &HashMap::new(),
+            HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(summary.downloaded, 2);
+        assert_eq!(summary.bytes, 2200);
+    }
+
+    #[tokio::test]
+    async fn test_list_playlists_prints_every_page() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let playlists_url = format!(
+            "/youtube/v3/playlists?part=snippet,contentDetails&channelId={}&key={}&maxResults=50",
+            MOCK_CHANNEL_ID, MOCK_API_KEY
+        );
+        let first_page = server
+            .mock("GET", &*playlists_url)
+            .with_body(
+                json!({"nextPageToken": "page2", "items": [
+                    {"id": "PL_tutorials", "snippet": {"title": "Rust Tutorials"}, "contentDetails": {"itemCount": 12}},
+                    {"id": "PL_live", "snippet": {"title": "Livestreams"}, "contentDetails": {"itemCount": 3}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("GET", &*format!("{}&pageToken=page2", playlists_url))
+            .with_body(
+                json!({"items": [
+                    {"id": "PL_shorts", "snippet": {"title": "Shorts"}, "contentDetails": {"itemCount": 40}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut output = Vec::new();
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        list_playlists(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            &mut output,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Rust Tutorials (PL_tutorials, 12 videos)\n\
+             Livestreams (PL_live, 3 videos)\n\
+             Shorts (PL_shorts, 40 videos)\n"
+        );
+    }
+
+    #[test]
+    fn test_list_playlists_subcommand_needs_no_output_dir() {
+        let args = Args::try_parse_from(["youtube-images", "list-playlists", "-c", "url"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::ListPlaylists { channel_url }) if channel_url == "url"
+        ));
+    }
+
+    #[cfg(not(feature = "image-processing"))]
+    #[test]
+    fn test_image_flags_need_image_processing() {
+        let check = |extra: &[&str]| {
+            let mut argv = vec!["youtube-images", "-c", "url"];
+            argv.extend_from_slice(extra);
+            check_image_processing(&Args::parse_from(argv))
+        };
+        assert!(check(&[]).is_ok());
+        assert!(check(&["--try-webp", "--dedup"]).is_ok());
+        assert_eq!(
+            check(&["--format", "png"]).unwrap_err().to_string(),
+            "--format png needs image processing, which this build doesn't include. \
+             Rebuild with --features image-processing."
+        );
+        assert!(check(&["--min-width", "640"]).is_err());
+        assert!(check(&["--contact-sheet", "sheet.png"]).is_err());
+        assert!(check(&["--embed-metadata"]).is_err());
+        assert!(check(&["--resize", "640x360"]).is_err());
+    }
+
+    #[cfg(not(feature = "s3"))]
+    #[tokio::test]
+    async fn test_output_s3_needs_the_s3_feature() {
+        let temp_dir = tempdir().unwrap();
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            temp_dir.path().to_str().unwrap(),
+            "--output-s3",
+            "s3://bucket/thumbs",
+        ]);
+        let error = DownloadOptions::load(&args).await.unwrap_err();
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_INVALID_INPUT);
+        assert!(error.to_string().contains("Rebuild with --features s3"));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_image_flags_work_with_image_processing() {
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "--format",
+            "png",
+            "--min-width",
+            "640",
+            "--contact-sheet",
+            "sheet.png",
+        ]);
+        assert!(check_image_processing(&args).is_ok());
+        let png = convert::convert_image(
+            convert::tests::tiny_jpeg(),
+            OutputFormat::Jpg,
+            OutputFormat::Png,
+            80,
+        )
+        .unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_subcommand_reports_the_corrupted_file() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        let checksums = Checksums::load(dir).await.unwrap();
+        for id in [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, "video3"] {
+            let path = dir.join(format!("{}.jpg", id));
+            std::fs::write(&path, id).unwrap();
+            checksums.record(&path, &Sha256::digest(id).into());
+        }
+        checksums.save(false).await.unwrap();
+        std::fs::write(dir.join(format!("{}.jpg", MOCK_VIDEO_ID_2)), "corrupted").unwrap();
+
+        let args =
+            Args::try_parse_from(["youtube-images", "verify", dir.to_str().unwrap()]).unwrap();
+        let Some(Command::Verify { dir }) = &args.command else {
+            panic!("expected the verify subcommand");
+        };
+        let mut output = Vec::new();
+        let code = verify_archive(Path::new(dir), &mut output).await.unwrap();
+
+        assert_eq!(code, error::EXIT_PARTIAL_FAILURE);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "{}.jpg: FAILED\nVerified 3 files: 1 failed, 0 missing.\n",
+                MOCK_VIDEO_ID_2
+            )
+        );
+    }
+
+    #[test]
+    fn test_output_dir_precedence() {
+        let args = Args::try_parse_from(["youtube-images", "-c", "url"]).unwrap();
+        assert_eq!(args.output_dir, None);
+        assert_eq!(args.output_dir(), DEFAULT_OUTPUT_DIR);
+
+        let env = || Some("/srv/thumbnails".to_string());
+        assert_eq!(
+            resolve_output_dir(Some("out".to_string()), env()).unwrap(),
+            "out"
+        );
+        assert_eq!(resolve_output_dir(None, env()).unwrap(), "/srv/thumbnails");
+        assert_eq!(resolve_output_dir(None, None).unwrap(), "thumbnails");
+        assert_eq!(
+            resolve_output_dir(None, Some(String::new()))
+                .unwrap_err()
+                .to_string(),
+            "YOUTUBE_IMAGE_OUTPUT_DIR is set but empty; unset it or pass --output-dir."
+        );
+        assert!(resolve_output_dir(Some(String::new()), env()).is_err());
+    }
+
+    #[test]
+    fn test_config_file_supplies_missing_flags() {
+        let temp_dir = tempdir().unwrap();
+        let config = temp_dir.path().join("config.toml");
+        std::fs::write(&config, "concurrency = 4\nresolution = \"sd\"\n").unwrap();
+        let argv = |extra: &[&str]| {
+            let mut argv: Vec<OsString> = ["youtube-images", "-c", "url", "--config"]
+                .into_iter()
+                .map(OsString::from)
+                .collect();
+            argv.push(config.clone().into_os_string());
+            argv.extend(extra.iter().map(OsString::from));
+            argv
+        };
+
+        let args = parse_args(argv(&[])).unwrap();
+        assert_eq!(args.concurrency, Some(4));
+        assert_eq!(args.resolution, Some(Resolution::Standard));
+
+        let args = parse_args(argv(&["--concurrency", "8"])).unwrap();
+        assert_eq!(args.concurrency, Some(8));
+        assert_eq!(args.resolution, Some(Resolution::Standard));
+
+        std::fs::write(&config, "placeholder = [\"1x1\", \"2x2\"]\n").unwrap();
+        let args = parse_args(argv(&[])).unwrap();
+        assert_eq!(args.placeholders.len(), 2);
+        let args = parse_args(argv(&["--placeholder", "3x3"])).unwrap();
+        assert_eq!(args.placeholders, [PlaceholderSignature::Dimensions(3, 3)]);
+
+        std::fs::write(&config, "threads = 4\n").unwrap();
+        let error = parse_args(argv(&[])).unwrap_err();
+        assert!(error.to_string().contains("unknown setting \"threads\""));
+    }
+
+    #[tokio::test]
+    async fn test_max_total_size_stops_dispatching_downloads() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, "video3"];
+        let items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+        let first_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .expect(1)
+            .create_async()
+            .await;
+        let rest_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!("^/vi/({}|video3)/", MOCK_VIDEO_ID_2)),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--concurrency",
+            "1",
+            "--max-total-size",
+            "5",
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        first_mock.assert_async().await;
+        rest_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(summary.over_budget, 2);
+        assert_eq!(summary.failed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_total_size_holds_without_a_concurrency_limit() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, "video3"];
+        let items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/vi/.*/maxresdefault.jpg$".to_string()),
+            )
+            .with_body("image")
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--max-total-size",
+            "5",
+        ]);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+
+        // Every download starts at once, but only the first to finish fits the budget.
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(summary.over_budget, 2);
+        assert_eq!(summary.failed, 0);
+        let saved = video_ids
+            .iter()
+            .filter(|id| temp_dir.path().join(format!("{}.jpg", id)).exists())
+            .count();
+        assert_eq!(saved, 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_duration_stops_dispatching_and_saves_state() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let state_file = temp_dir.path().join("state.json");
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, "video3"];
+        let items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+        let slow_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_chunked_body(|writer| {
+                std::thread::sleep(Duration::from_millis(400));
+                writer.write_all(b"image1")
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let rest_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!("^/vi/({}|video3)/", MOCK_VIDEO_ID_2)),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--concurrency",
+            "1",
+            "--state-file",
+            state_file.to_str().unwrap(),
+            "--max-duration",
+            "60",
+        ]);
+        // A deadline in milliseconds rather than --max-duration's whole seconds keeps the test fast.
+        let options = DownloadOptions {
+            deadline: Some(Instant::now() + Duration::from_millis(200)),
+            ..DownloadOptions::load(&args).await.unwrap()
+        };
+        let summary = run_with_options(&client, MOCK_CREDENTIALS, &args, &options, &server.url())
+            .await
+            .unwrap();
+
+        slow_mock.assert_async().await;
+        rest_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(summary.out_of_time, 2);
+        assert_eq!(summary.failed, 0);
+        let state = DownloadState::load(&state_file).await.unwrap();
+        assert!(state.contains(MOCK_VIDEO_ID_1));
+        assert!(!state.contains(MOCK_VIDEO_ID_2));
+    }
+
+    #[tokio::test]
+    async fn test_max_duration_holds_without_a_concurrency_limit() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = [MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2, "video3"];
+        let items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await;
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+        let first_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("image1")
+            .expect(1)
+            .create_async()
+            .await;
+        // The other videos have no maxres variant, and only say so after the deadline.
+        let _slow_missing_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!(
+                    "^/vi/({}|video3)/maxresdefault.jpg$",
+                    MOCK_VIDEO_ID_2
+                )),
+            )
+            .with_status(404)
+            .with_body_from_request(|_| {
+                std::thread::sleep(Duration::from_millis(300));
+                b"not found".to_vec()
+            })
+            .create_async()
+            .await;
+        let fallback_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex(format!(
+                    "^/vi/({}|video3)/sddefault.jpg$",
+                    MOCK_VIDEO_ID_2
+                )),
+            )
+            .expect(0)
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--max-duration",
+            "60",
+        ]);
+        let options = DownloadOptions {
+            deadline: Some(Instant::now() + Duration::from_millis(200)),
+            ..DownloadOptions::load(&args).await.unwrap()
+        };
+        let summary = run_with_options(&client, MOCK_CREDENTIALS, &args, &options, &server.url())
+            .await
+            .unwrap();
+
+        // Every download starts at once, but the fallbacks would be requested too late.
+        first_mock.assert_async().await;
+        fallback_mock.assert_async().await;
+        assert_eq!(summary.downloaded, 1);
+        assert_eq!(summary.out_of_time, 2);
+        assert_eq!(summary.failed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_url_skips_search_results_with_other_handles() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let search_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
+                    MOCK_HANDLE, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": {"channelId": "UC_similar_channel"}},
+                    {"id": {"channelId": MOCK_CHANNEL_ID}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let handles_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/channels?part=snippet&id=UC_similar_channel,{}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"items": [
+                    {"id": MOCK_CHANNEL_ID, "snippet": {"customUrl": "@TestHandle"}},
+                    {"id": "UC_similar_channel", "snippet": {"customUrl": "@testhandle2"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        search_mock.assert_async().await;
+        handles_mock.assert_async().await;
+        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+    }
+
+    #[tokio::test]
+    async fn test_handle_url_without_matching_search_result() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let _search_mock = server
+            .mock("GET", "/youtube/v3/search")
+            .match_query(mockito::Matcher::Any)
+            .with_body(json!({"items": [{"id": {"channelId": "UC_similar_channel"}}]}).to_string())
+            .create_async()
+            .await;
+        let _handles_mock =
+            mock_channel_handles(&mut server, &[("UC_similar_channel", "testhandle2")]).await;
+
+        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
+        let result = get_channel_id_from_url(
+            &client,
+            MOCK_CREDENTIALS,
+            &channel_url,
+            None,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_always_replaces_existing_file() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg,
+        );
+        fs::write(&file_path, b"old_image").await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let head_mock = server
+            .mock("HEAD", mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let get_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("new_image")
+            .create_async()
+            .await;
+
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &DownloadOptions::default(),
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        head_mock.assert_async().await;
+        get_mock.assert_async().await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"new_image");
+    }
+
+    /// Runs a `--no-overwrite-newer` download over an existing file last modified at
+    /// `local_mtime`, with YouTube's copy last modified on 21 October 2015, and returns
+    /// the outcome and the file's contents afterwards.
+    async fn download_over_file_modified_at(
+        local_mtime: std::time::SystemTime,
+    ) -> (DownloadOutcome, Vec<u8>) {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        std::fs::write(&file_path, b"curated_image").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(local_mtime)
+            .unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", mockito::Matcher::Any)
+            .with_header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+            .with_body("youtube_image")
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            no_overwrite_newer: true,
+            ..Default::default()
+        };
+
+        let outcome =
+            download_thumbnail(&client, MOCK_VIDEO_ID_1, &location, &options, &server.url())
+                .await
+                .unwrap();
+        (outcome, std::fs::read(&file_path).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_no_overwrite_newer_keeps_newer_local_file() {
+        let (outcome, contents) =
+            download_over_file_modified_at(std::time::SystemTime::now()).await;
+        assert!(matches!(outcome, DownloadOutcome::Skipped { .. }));
+        assert_eq!(contents, b"curated_image");
+    }
+
+    #[tokio::test]
+    async fn test_no_overwrite_newer_replaces_older_local_file() {
+        let year_2000 = std::time::UNIX_EPOCH + Duration::from_secs(946_684_800);
+        let (outcome, contents) = download_over_file_modified_at(year_2000).await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        assert_eq!(contents, b"youtube_image");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_never_keeps_existing_file() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg,
+        );
+        fs::write(&file_path, b"old_image").await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mut mocks = Vec::new();
+        for method in ["HEAD", "GET"] {
+            mocks.push(
+                server
+                    .mock(method, mockito::Matcher::Any)
+                    .expect(0)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let options = DownloadOptions {
+            overwrite: Overwrite::Never,
+            ..Default::default()
+        };
+        let outcome = download_thumbnail(
+            &client,
+            MOCK_VIDEO_ID_1,
+            &OutputLocation::new(output_dir),
+            &options,
+            &server.url(),
+        )
+        .await
+        .unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Skipped {
+                resolution: None,
+                path: file_path.clone(),
+            }
+        );
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"old_image");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_if_different_compares_etags() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let file_path = thumbnail_path(
+            &OutputLocation::new(output_dir),
+            MOCK_VIDEO_ID_1,
+            OutputFormat::Jpg,
+        );
+        fs::write(&file_path, b"old_image").await.unwrap();
+        let cache = HttpCache::load(Path::new(output_dir)).await.unwrap();
+        cache.insert(
+            MOCK_VIDEO_ID_1,
+            CacheEntry {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+            },
+        );
+        let mut server = mockito::Server::new_async().await;
+        // Same size as the file on disk, but the ETag shows the image has changed.
+        let head_mock = server
+            .mock("HEAD", "/thumbnail.jpg")
+            .with_header("etag", "\"v2\"")
+            .with_header("content-length", "9")
+            .create_async()
+            .await;
+        let get_mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_header("etag", "\"v2\"")
+            .with_body("new_image")
+            .create_async()
+            .await;
+
+        let options = DownloadOptions {
+            overwrite: Overwrite::IfDifferent,
+            cache: Some(Arc::new(cache)),
+            ..Default::default()
+        };
+        let url = format!("{}/thumbnail.jpg", server.url());
+        let outcome = download_variant(
+            &client,
+            MOCK_VIDEO_ID_1,
+            Resolution::MaxRes,
+            &url,
+            &OutputLocation::new(output_dir),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        head_mock.assert_async().await;
+        get_mock.assert_async().await;
+        assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        assert_eq!(fs::read(&file_path).await.unwrap(), b"new_image");
+    }
+
+    #[tokio::test]
+    async fn test_get_all_video_ids_stops_on_repeated_page_token() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        let first_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(
+                json!({"nextPageToken": "stuck", "items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        let stuck_mock = server
+            .mock(
+                "GET",
+                &*format!(
+                    "/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50&pageToken=stuck",
+                    MOCK_UPLOADS_ID, MOCK_API_KEY
+                ),
+            )
+            .with_body(json!({"nextPageToken": "stuck", "items": []}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        first_mock.assert_async().await;
+        stuck_mock.assert_async().await;
+        assert_eq!(result.unwrap(), vec![MOCK_VIDEO_ID_1]);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_video_ids_stops_after_consecutive_empty_pages() {
+        let client = test_client();
+        let mut server = mockito::Server::new_async().await;
+        // Every page is empty but points to a new page, forever.
+        let mock = server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(|request| {
+                let page = request
+                    .path_and_query()
+                    .split("pageToken=page")
+                    .nth(1)
+                    .and_then(|page| page.parse::<usize>().ok())
+                    .unwrap_or(0);
+                json!({"nextPageToken": format!("page{}", page + 1), "items": []})
+                    .to_string()
+                    .into()
+            })
+            .expect(MAX_EMPTY_PAGES)
+            .create_async()
+            .await;
+
+        let result = get_all_video_ids(
+            &client,
+            MOCK_CREDENTIALS,
+            MOCK_UPLOADS_ID,
+            MAX_PAGE_SIZE,
+            None,
+            &server.url(),
+        )
+        .await;
+
+        mock.assert_async().await;
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_header() {
+        let mut server = mockito::Server::new_async().await;
+        let default_mock = server
+            .mock("GET", "/default")
+            .match_header(
+                "user-agent",
+                &*format!("youtube-image-downloader/{}", env!("CARGO_PKG_VERSION")),
+            )
+            .create_async()
+            .await;
+        let custom_mock = server
+            .mock("GET", "/custom")
+            .match_header("user-agent", "Mozilla/5.0 (X11; Linux x86_64)")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from(["youtube-images", "-c", "url", "-o", "out"]);
+        let client = build_client(&args).unwrap();
+        client
+            .get(format!("{}/default", server.url()))
+            .send()
+            .await
+            .unwrap();
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--user-agent",
+            "Mozilla/5.0 (X11; Linux x86_64)",
+        ]);
+        let client = build_client(&args).unwrap();
+        client
+            .get(format!("{}/custom", server.url()))
+            .send()
+            .await
+            .unwrap();
+
+        default_mock.assert_async().await;
+        custom_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_are_sent() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/headers")
+            .match_header("x-cdn-token", "secret")
+            .match_header("proxy-authorization", "Basic dXNlcjpwYXNz")
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--header",
+            "X-CDN-Token: secret",
+            "--header",
+            "Proxy-Authorization:Basic dXNlcjpwYXNz",
+        ]);
+        let client = build_client(&args).unwrap();
+        client
+            .get(format!("{}/headers", server.url()))
+            .send()
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_invalid_header_is_rejected() {
+        for (header, message) in [
+            ("X-Token secret", "isn't in the form \"Name: Value\""),
+            ("Bad Name: value", "\"Bad Name\" isn't a valid header name"),
+            ("X-Token: line\nbreak", "isn't a valid header value"),
+        ] {
+            let error = Args::try_parse_from(["youtube-images", "-c", "url", "--header", header])
+                .unwrap_err();
+            assert!(
+                error.to_string().contains(message),
+                "unexpected error: {}",
+                error
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_with_pool_size() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("GET", "/pooled").expect(3).create_async().await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--pool-size",
+            "2",
+        ]);
+        assert_eq!(args.pool_size, 2);
+        let client = build_client(&args).unwrap();
+        for _ in 0..3 {
+            client
+                .get(format!("{}/pooled", server.url()))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        mock.assert_async().await;
+        assert!(Args::try_parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--pool-size",
+            "0"
+        ])
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_with_tcp_keepalive_downloads_thumbnail() {
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().to_str().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/thumbnail.jpg")
+            .with_body("fake_image_data")
+            .expect(2)
+            .create_async()
+            .await;
+
+        for keepalive in ["30", "0"] {
+            let args = Args::parse_from([
+                "youtube-images",
+                "-c",
+                "url",
+                "-o",
+                output_dir,
+                "--tcp-keepalive",
+                keepalive,
+            ]);
+            let client = build_client(&args).unwrap();
+            let outcome = download_variant(
+                &client,
+                MOCK_VIDEO_ID_1,
+                Resolution::MaxRes,
+                &format!("{}/thumbnail.jpg", server.url()),
+                &OutputLocation::new(output_dir),
+                &DownloadOptions::default(),
+            )
+            .await
+            .unwrap();
+            assert!(matches!(outcome, DownloadOutcome::Downloaded { .. }));
+        }
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_with_video_ids_file_skips_the_api() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("thumbnails");
+        let ids_file = temp_dir.path().join("ids.txt");
+        let video_ids = ["dQw4w9WgXcQ", "9bZkp7q19f0"];
+        std::fs::write(&ids_file, format!("# saved\n{}\n", video_ids.join("\n"))).unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let mut mocks = Vec::new();
+        for video_id in video_ids {
+            mocks.push(
+                server
+                    .mock("GET", &*format!("/vi/{}/maxresdefault.jpg", video_id))
+                    .with_body("image")
+                    .create_async()
+                    .await,
+            );
+        }
+        let api_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/youtube/".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--video-ids-file",
+            ids_file.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ]);
+        let summary = run(&client, Credentials::ApiKey(""), &args, &server.url())
+            .await
+            .unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        api_mock.assert_async().await;
+        assert_eq!((summary.downloaded, summary.failed), (2, 0));
+        for video_id in video_ids {
+            assert!(output_dir.join(format!("{}.jpg", video_id)).exists());
+        }
+    }
 
-    if let Some(item) = response.items.into_iter().next() {
-        if let Some(details) = item.content_details {
-            return Ok(details.related_playlists.uploads);
-        }
+    #[test]
+    fn test_video_ids_file_needs_no_channel_url() {
+        assert!(Args::try_parse_from([
+            "youtube-images",
+            "--video-ids-file",
+            "ids.txt",
+            "--output-dir",
+            "out"
+        ])
+        .is_ok());
+        assert!(Args::try_parse_from([
+            "youtube-images",
+            "--video-ids-file",
+            "ids.txt",
+            "--channel-url",
+            "https://www.youtube.com/@handle",
+            "--output-dir",
+            "out"
+        ])
+        .is_err());
     }
-    Err("Could not find uploads playlist for the channel.".into())
-}
 
-/// Fetches all video IDs from a given playlist.
-async fn get_all_video_ids(
-    client: &Client,
-    api_key: &str,
-    playlist_id: &str,
-) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut video_ids = Vec::new();
-    let mut page_token: Option<String> = None;
+    #[tokio::test]
+    async fn test_retry_404_later_retries_missing_maxres_at_the_end() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("thumbnails");
+        let ids_file = temp_dir.path().join("ids.txt");
+        let video_id = "dQw4w9WgXcQ";
+        std::fs::write(&ids_file, video_id).unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let maxres_path = format!("/vi/{}/maxresdefault.jpg", video_id);
+        let not_yet_mock = server
+            .mock("GET", &*maxres_path)
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let retry_mock = server
+            .mock("GET", &*maxres_path)
+            .with_body("maxres_image")
+            .expect(1)
+            .create_async()
+            .await;
+        let fallback_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("(sd|hq|mq)default.jpg$".to_string()),
+            )
+            .expect(0)
+            .create_async()
+            .await;
 
-    loop {
-        let mut url = format!(
-            "https://www.googleapis.com/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50",
-            playlist_id, api_key
-        );
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--video-ids-file",
+            ids_file.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--retry-404-later",
+            "0",
+        ]);
+        let summary = run(&client, Credentials::ApiKey(""), &args, &server.url())
+            .await
+            .unwrap();
 
-        if let Some(token) = &page_token {
-            url.push_str(&format!("&pageToken={}", token));
-        }
+        not_yet_mock.assert_async().await;
+        retry_mock.assert_async().await;
+        fallback_mock.assert_async().await;
+        assert_eq!((summary.downloaded, summary.failed), (1, 0));
+        let file_path = output_dir.join(format!("{}.jpg", video_id));
+        assert_eq!(fs::read(file_path).await.unwrap(), b"maxres_image");
+    }
 
-        let response: PlaylistItemListResponse = client.get(&url).send().await?.json().await?;
+    #[tokio::test]
+    async fn test_retry_404_later_falls_back_on_the_retry() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("thumbnails");
+        let ids_file = temp_dir.path().join("ids.txt");
+        let video_id = "dQw4w9WgXcQ";
+        std::fs::write(&ids_file, video_id).unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let maxres_mock = server
+            .mock("GET", &*format!("/vi/{}/maxresdefault.jpg", video_id))
+            .with_status(404)
+            .expect(2)
+            .create_async()
+            .await;
+        let standard_mock = server
+            .mock("GET", &*format!("/vi/{}/sddefault.jpg", video_id))
+            .with_body("sd_image")
+            .expect(1)
+            .create_async()
+            .await;
 
-        for item in response.items {
-            video_ids.push(item.content_details.video_id);
-        }
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--video-ids-file",
+            ids_file.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--retry-404-later",
+            "0",
+        ]);
+        let summary = run(&client, Credentials::ApiKey(""), &args, &server.url())
+            .await
+            .unwrap();
 
-        page_token = response.next_page_token;
-        if page_token.is_none() {
-            break;
-        }
+        maxres_mock.assert_async().await;
+        standard_mock.assert_async().await;
+        assert_eq!((summary.downloaded, summary.failed), (1, 0));
     }
 
-    Ok(video_ids)
-}
+    /// Runs a download of a channel with three titled videos, passing `extra_args`, and
+    /// returns the IDs of the videos whose thumbnails were saved.
+    async fn download_titled_videos(extra_args: &[&str]) -> Vec<String> {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let mut server = mockito::Server::new_async().await;
+        let videos = [
+            ("video1", "Game Devlog #1"),
+            ("video2", "Cooking pasta"),
+            ("video3", "Game devlog #2 - menus"),
+        ];
 
-/// Downloads a single video thumbnail at its highest resolution.
-async fn download_thumbnail(
-    client: &Client,
-    video_id: &str,
-    output_dir: &str,
-) -> Result<(), Box<dyn Error>> {
-    // maxresdefault provides the highest possible resolution.
-    let thumbnail_url = format!("https://img.youtube.com/vi/{}/maxresdefault.jpg", video_id);
-    let response = client.get(&thumbnail_url).send().await?;
-
-    if response.status().is_success() {
-        let file_path = Path::new(output_dir).join(format!("{}.jpg", video_id));
-        let mut file = File::create(&file_path).await?;
-        let bytes = response.bytes().await?;
-        file.write_all(&bytes).await?;
-        println!("Downloaded thumbnail for video ID: {}", video_id);
-    } else {
-        // If maxresdefault.jpg doesn't exist, YouTube returns a 404.
-        // We could add a fallback to 'hqdefault.jpg' here if needed.
-        eprintln!(
-            "Failed to download max-res thumbnail for video ID {}. It might not exist. Status: {}",
-            video_id,
-            response.status()
-        );
-    }
+        let playlist_items: Vec<_> = videos
+            .iter()
+            .map(|(id, _)| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({ "items": playlist_items }).to_string())
+            .create_async().await;
+        let details: Vec<_> = videos
+            .iter()
+            .map(|(id, title)| {
+                json!({"id": id, "snippet": {"title": title, "publishedAt": "2024-03-15T10:00:00Z"}})
+            })
+            .collect();
+        let _details_mock = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body(json!({ "items": details }).to_string())
+            .create_async()
+            .await;
+        let _thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
 
-    Ok(())
-}
+        let server_url = server.url();
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let mut argv = vec![
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server_url,
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ];
+        argv.extend_from_slice(extra_args);
+        let args = Args::parse_from(argv);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+        videos
+            .iter()
+            .map(|(id, _)| id.to_string())
+            .filter(|id| output_dir.join(format!("{}.jpg", id)).exists())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_resolutions_report_records_each_fallback() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let report = temp_dir.path().join("resolutions.csv");
+        let mut server = mockito::Server::new_async().await;
+        let video_ids = ["video1", "video2", "video3"];
+        let playlist_items: Vec<_> = video_ids
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({ "items": playlist_items }).to_string())
+            .create_async().await;
+        let _videos_mock = mock_public_videos(&mut server, &video_ids).await;
+        let _maxres_mock = server
+            .mock("GET", "/vi/video1/maxresdefault.jpg")
+            .with_body("maxres_image")
+            .create_async()
+            .await;
+        let _hq_mock = server
+            .mock("GET", "/vi/video2/hqdefault.jpg")
+            .with_body("hq")
+            .create_async()
+            .await;
+        let _missing_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/vi/(video2/(maxres|sd)default|video3/)".to_string()),
+            )
+            .with_status(404)
+            .create_async()
+            .await;
 
-    let api_key =
-        env::var("YOUTUBE_API_KEY").map_err(|_| "YOUTUBE_API_KEY environment variable not set.")?;
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().join("out").to_str().unwrap(),
+            "--resolutions-report",
+            report.to_str().unwrap(),
+        ]);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
 
-    let client = Client::new();
+        // The fixtures aren't real images, so their dimensions are unknown.
+        assert_eq!(
+            std::fs::read_to_string(&report).unwrap(),
+            "video_id,chosen_resolution,width,height,bytes\n\
+             video1,maxres,,,12\n\
+             video2,hq,,,2\n\
+             video3,,,,\n"
+        );
+    }
 
-    // Create the output directory if it doesn't exist
-    fs::create_dir_all(&args.output_dir).await?;
+    #[tokio::test]
+    async fn test_prune_moves_removed_videos_to_trash() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let output_dir = temp_dir.path();
+        let current = ["keptVideo01", "keptVideo02"];
+        for name in ["keptVideo01.jpg", "goneVideo01.jpg", "cover.jpg"] {
+            std::fs::write(output_dir.join(name), "old_image_data").unwrap();
+        }
+        let mut server = mockito::Server::new_async().await;
+        let playlist_items: Vec<_> = current
+            .iter()
+            .map(|id| json!({"contentDetails": {"videoId": id}}))
+            .collect();
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({ "items": playlist_items }).to_string())
+            .create_async().await;
+        let _videos_mock = mock_public_videos(&mut server, &current).await;
+        let _thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .with_body("fake_image_data")
+            .create_async()
+            .await;
 
-    println!("Resolving channel URL: {}", args.channel_url);
-    let channel_id = get_channel_id_from_url(&client, &api_key, &args.channel_url).await?;
-    println!("Resolved to channel ID: {}", channel_id);
+        let server_url = server.url();
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server_url,
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--prune",
+        ]);
+        run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
 
-    println!("Fetching uploads playlist ID for channel...");
-    let uploads_playlist_id = get_uploads_playlist_id(&client, &api_key, &channel_id).await?;
-    println!("Found uploads playlist ID: {}", uploads_playlist_id);
+        let trash = output_dir.join(prune::TRASH_DIR);
+        assert!(!output_dir.join("goneVideo01.jpg").exists());
+        assert_eq!(
+            std::fs::read(trash.join("goneVideo01.jpg")).unwrap(),
+            b"old_image_data"
+        );
+        for id in current {
+            assert!(output_dir.join(format!("{}.jpg", id)).exists());
+        }
+        assert!(output_dir.join("cover.jpg").exists());
+        assert_eq!(std::fs::read_dir(&trash).unwrap().count(), 1);
+    }
 
-    println!("Fetching all video IDs from the playlist...");
-    let video_ids = get_all_video_ids(&client, &api_key, &uploads_playlist_id).await?;
-    println!("Found {} videos in the channel.", video_ids.len());
+    #[tokio::test]
+    async fn test_title_contains_filters_videos() {
+        assert_eq!(
+            download_titled_videos(&["--title-contains", "DEVLOG"]).await,
+            ["video1", "video3"]
+        );
+    }
 
-    let mut download_tasks = Vec::new();
+    #[tokio::test]
+    async fn test_title_regex_filters_videos() {
+        assert_eq!(
+            download_titled_videos(&["--title-regex", r"#\d+$"]).await,
+            ["video1"]
+        );
+        assert_eq!(
+            download_titled_videos(&["--title-regex", "^Cooking"]).await,
+            ["video2"]
+        );
+    }
 
-    for video_id in &video_ids {
-        let client = client.clone();
-        let output_dir = args.output_dir.clone();
-        let video_id = video_id.clone();
+    #[tokio::test]
+    async fn test_limit_keeps_newest_videos() {
+        assert_eq!(
+            download_titled_videos(&["--limit", "2"]).await,
+            ["video1", "video2"]
+        );
+    }
 
-        let task = tokio::spawn(async move {
-            if let Err(e) = download_thumbnail(&client, &video_id, &output_dir).await {
-                eprintln!("Error downloading thumbnail for {}: {}", video_id, e);
-            }
-        });
-        download_tasks.push(task);
+    #[tokio::test]
+    async fn test_limit_with_oldest_order_keeps_oldest_videos() {
+        assert_eq!(
+            download_titled_videos(&["--order", "oldest", "--limit", "2"]).await,
+            ["video2", "video3"]
+        );
     }
 
-    // Wait for all the download tasks to complete.
-    for task in download_tasks {
-        task.await?;
+    #[test]
+    fn test_select_videos_in_requested_order() {
+        let video_ids: Vec<String> = ["video3", "video2", "video1"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let select = |order: &str| {
+            let args = Args::parse_from(["youtube-images", "-c", "url", "--order", order]);
+            select_videos(video_ids.clone(), &HashMap::new(), &args).unwrap()
+        };
+        assert_eq!(select("newest"), ["video3", "video2", "video1"]);
+        assert_eq!(select("oldest"), ["video1", "video2", "video3"]);
     }
 
-    println!("\nDownload process finished!");
-    Ok(())
-}
+    #[test]
+    fn test_sort_videos_by_each_key() {
+        let details: HashMap<String, VideoDetails> = [
+            json!({"id": "video1", "snippet": {"title": "banana", "publishedAt": "2024-01-01T00:00:00Z"},
+                   "contentDetails": {"duration": "PT5M"}, "statistics": {"viewCount": "300"}}),
+            json!({"id": "video2", "snippet": {"title": "Apple", "publishedAt": "2024-03-01T00:00:00Z"},
+                   "contentDetails": {"duration": "PT1H"}, "statistics": {}}),
+            json!({"id": "video3", "snippet": {"title": "cherry", "publishedAt": "2023-06-01T00:00:00Z"},
+                   "contentDetails": {"duration": "PT5M"}, "statistics": {"viewCount": "9000"}}),
+            json!({"id": "video4", "snippet": {"title": "apple", "publishedAt": "2024-02-01T00:00:00Z"},
+                   "contentDetails": {"duration": "PT30S"}, "statistics": {"viewCount": "300"}}),
+        ]
+        .into_iter()
+        .map(|item| {
+            let details: VideoDetails = serde_json::from_value(item).unwrap();
+            (details.id.clone(), details)
+        })
+        .collect();
+        let sorted = |key| {
+            let mut video_ids: Vec<String> = ["video1", "video2", "video3", "video4", "video5"]
+                .iter()
+                .map(|id| id.to_string())
+                .collect();
+            sort_videos(&mut video_ids, &details, key);
+            video_ids
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-    use tempfile::tempdir;
+        assert_eq!(
+            sorted(SortBy::Date),
+            ["video2", "video4", "video1", "video3", "video5"]
+        );
+        // Hidden views and missing details go last, ties keep their order.
+        assert_eq!(
+            sorted(SortBy::Views),
+            ["video3", "video1", "video4", "video2", "video5"]
+        );
+        assert_eq!(
+            sorted(SortBy::Duration),
+            ["video2", "video1", "video3", "video4", "video5"]
+        );
+        assert_eq!(
+            sorted(SortBy::Title),
+            ["video2", "video4", "video1", "video3", "video5"]
+        );
+    }
 
-    const MOCK_API_KEY: &str = "test_api_key";
-    const MOCK_CHANNEL_ID: &str = "UC_test_channel_id";
-    const MOCK_USERNAME: &str = "testuser";
-    const MOCK_HANDLE: &str = "testhandle";
-    const MOCK_UPLOADS_ID: &str = "UU_test_uploads_id";
-    const MOCK_VIDEO_ID_1: &str = "video1";
-    const MOCK_VIDEO_ID_2: &str = "video2";
+    #[test]
+    fn test_check_video_count_tolerates_small_differences() {
+        assert!(check_video_count(95, 100, true).is_ok());
+        assert!(check_video_count(120, 100, true).is_ok());
+        assert!(check_video_count(50, 100, false).is_ok());
+        assert!(matches!(
+            check_video_count(50, 100, true),
+            Err(DownloaderError::IncompleteListing {
+                collected: 50,
+                reported: 100
+            })
+        ));
+    }
 
     #[tokio::test]
-    async fn test_get_channel_id_from_handle_url() {
-        let client = Client::new();
+    async fn test_verify_count_fails_strictly_on_incomplete_listing() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
         let mut server = mockito::Server::new_async().await;
-        let mock = server
+        let statistics_mock = server
             .mock(
                 "GET",
                 &*format!(
-                    "/youtube/v3/search?part=id&q={}&type=channel&key={}",
-                    MOCK_HANDLE, MOCK_API_KEY
+                    "/youtube/v3/channels?part=statistics&id={}&key={}",
+                    MOCK_CHANNEL_ID, MOCK_API_KEY
                 ),
             )
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(json!({"items": [{"id": {"channelId": MOCK_CHANNEL_ID}}]}).to_string())
+            .with_body(json!({"items": [{"statistics": {"videoCount": "250"}}]}).to_string())
+            .create_async()
+            .await;
+        let _playlist_mock = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
+            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}, {"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string())
+            .create_async().await;
+        let thumbnail_mock = server
+            .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+            .expect(0)
             .create_async()
             .await;
 
-        // Pass the mock server's URL to the function
-        let channel_url = format!("http://any.url/@{}", MOCK_HANDLE);
-        let result =
-            get_channel_id_from_url(&client, MOCK_API_KEY, &channel_url, &server.url()).await;
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let args = Args::parse_from([
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server.url(),
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            temp_dir.path().to_str().unwrap(),
+            "--verify-count",
+            "--strict",
+        ]);
+        let error = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap_err();
 
-        mock.assert_async().await;
-        assert_eq!(result.unwrap(), MOCK_CHANNEL_ID);
+        statistics_mock.assert_async().await;
+        thumbnail_mock.assert_async().await;
+        assert_eq!(
+            error.to_string(),
+            "Only 2 videos were listed, but the channel reports 250; the list is probably incomplete"
+        );
+        assert_eq!(error::exit_code(error.as_ref()), error::EXIT_FAILURE);
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_get_uploads_playlist_id() {
-        let client = Client::new();
-        let mut server = mockito::Server::new_async().await;
-        let mock = server.mock("GET", &*format!("/youtube/v3/channels?part=contentDetails&id={}&key={}", MOCK_CHANNEL_ID, MOCK_API_KEY))
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(json!({"items": [{"contentDetails": {"relatedPlaylists": {"uploads": MOCK_UPLOADS_ID}}}]}).to_string())
-            .create_async().await;
+    async fn test_on_download_runs_for_each_download() {
+        let markers = tempdir().unwrap();
+        let command = format!(
+            "touch '{}'/\"$YOUTUBE_IMAGES_VIDEO_ID\".marker",
+            markers.path().display()
+        );
 
-        let result =
-            get_uploads_playlist_id(&client, MOCK_API_KEY, MOCK_CHANNEL_ID, &server.url()).await;
+        let downloaded =
+            download_titled_videos(&["--on-download", &command, "--concurrency", "2"]).await;
 
-        mock.assert_async().await;
-        assert_eq!(result.unwrap(), MOCK_UPLOADS_ID);
+        assert_eq!(downloaded, ["video1", "video2", "video3"]);
+        let mut marked: Vec<String> = std::fs::read_dir(markers.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        marked.sort();
+        assert_eq!(marked, ["video1.marker", "video2.marker", "video3.marker"]);
     }
 
     #[tokio::test]
-    async fn test_get_all_video_ids_with_pagination() {
-        let client = Client::new();
-        let next_page_token = "nextPageToken123";
-        let mut server = mockito::Server::new_async().await;
+    async fn test_archive_only_writes_a_zip_instead_of_loose_files() {
+        let temp_dir = tempdir().unwrap();
+        let archive_file = temp_dir.path().join("thumbnails.zip");
 
-        let mock1 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50", MOCK_UPLOADS_ID, MOCK_API_KEY))
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(json!({"nextPageToken": next_page_token, "items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_1}}]}).to_string())
-            .create_async().await;
+        let loose_files = download_titled_videos(&[
+            "--archive",
+            "zip",
+            "--archive-file",
+            archive_file.to_str().unwrap(),
+            "--archive-only",
+        ])
+        .await;
 
-        let mock2 = server.mock("GET", &*format!("/youtube/v3/playlistItems?part=contentDetails&playlistId={}&key={}&maxResults=50&pageToken={}", MOCK_UPLOADS_ID, MOCK_API_KEY, next_page_token))
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(json!({"items": [{"contentDetails": {"videoId": MOCK_VIDEO_ID_2}}]}).to_string())
-            .create_async().await;
+        assert!(loose_files.is_empty());
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive_file).unwrap()).unwrap();
+        let mut names: Vec<String> = zip
+            .file_names()
+            .map(|name| name.unwrap().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["video1.jpg", "video2.jpg", "video3.jpg"]);
+        for name in ["video1.jpg", "video2.jpg", "video3.jpg"] {
+            let mut contents = String::new();
+            std::io::Read::read_to_string(&mut zip.by_name(name).unwrap(), &mut contents).unwrap();
+            assert_eq!(contents, "fake_image_data");
+        }
+    }
 
-        let result = get_all_video_ids(&client, MOCK_API_KEY, MOCK_UPLOADS_ID, &server.url()).await;
+    #[tokio::test]
+    async fn test_sort_by_applies_before_limit() {
+        assert_eq!(
+            download_titled_videos(&["--sort-by", "title", "--limit", "1"]).await,
+            ["video2"]
+        );
+    }
 
-        mock1.assert_async().await;
-        mock2.assert_async().await;
-        assert_eq!(result.unwrap(), vec![MOCK_VIDEO_ID_1, MOCK_VIDEO_ID_2]);
+    #[test]
+    fn test_select_videos_range_after_ordering() {
+        let video_ids: Vec<String> = (0..600).rev().map(|n| format!("video{}", n)).collect();
+        let select = |extra: &[&str]| {
+            let mut argv = vec!["youtube-images", "-c", "url", "--order", "oldest"];
+            argv.extend(extra);
+            select_videos(video_ids.clone(), &HashMap::new(), &Args::parse_from(argv))
+        };
+        let expected = |range: std::ops::Range<usize>| -> Vec<String> {
+            range.map(|n| format!("video{}", n)).collect()
+        };
+
+        assert_eq!(
+            select(&["--range", "100..200"]).unwrap(),
+            expected(100..200)
+        );
+        assert_eq!(select(&["--range", "500.."]).unwrap(), expected(500..600));
+        assert_eq!(select(&["--range", "..50"]).unwrap(), expected(0..50));
+        assert_eq!(
+            select(&["--range", "590..610", "--clamp"]).unwrap(),
+            expected(590..600)
+        );
+        assert!(matches!(
+            select(&["--range", "590..610"]),
+            Err(DownloaderError::InvalidInput(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_download_thumbnail_success() {
-        let client = Client::new();
+    async fn test_range_limits_downloads() {
+        assert_eq!(
+            download_titled_videos(&["--range", "1.."]).await,
+            ["video2", "video3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_with_seed_picks_the_same_sample() {
+        let args = ["--shuffle", "--seed", "7", "--limit", "2"];
+        let first = download_titled_videos(&args).await;
+        assert_eq!(first.len(), 2);
+        assert_eq!(download_titled_videos(&args).await, first);
+    }
+
+    #[test]
+    fn test_invalid_title_regex_is_rejected_at_startup() {
+        let error = Args::try_parse_from([
+            "youtube-images",
+            "-c",
+            "url",
+            "-o",
+            "out",
+            "--title-regex",
+            "(unclosed",
+        ])
+        .unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+        assert!(error.to_string().contains("--title-regex"));
+    }
+
+    /// Downloads one video with `--dir-template {year}/{month} --with-metadata`, plus
+    /// `extra_args`, and returns the paths of all files written, relative to the output
+    /// directory.
+    async fn download_with_dir_template(extra_args: &[&str]) -> Vec<PathBuf> {
+        let client = test_client();
         let temp_dir = tempdir().unwrap();
-        let output_dir = temp_dir.path().to_str().unwrap();
-        let image_bytes = b"fake_image_data";
+        let output_dir = temp_dir.path().join("out");
         let mut server = mockito::Server::new_async().await;
 
-        // Mock a simple path on the server
-        let mock = server
-            .mock("GET", "/thumbnail.jpg")
-            .with_status(200)
-            .with_body(image_bytes)
+        let _playlist_mock = server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [{
+                    "contentDetails": {"videoId": MOCK_VIDEO_ID_1},
+                    "snippet": {"title": "A title", "publishedAt": "2024-03-15T10:00:00Z"}
+                }]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _details_mock = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!({"items": [{
+                    "id": MOCK_VIDEO_ID_1,
+                    "snippet": {"publishedAt": "2024-03-15T10:00:00Z"}
+                }]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let _thumbnail_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_body("fake_image_data")
             .create_async()
             .await;
 
-        // Construct the full URL to the mock server's path
-        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let server_url = server.url();
+        let channel_url = format!("http://any.url/channel/{}", MOCK_CHANNEL_ID);
+        let mut argv = vec![
+            "youtube-images",
+            "--thumbnail-base-url",
+            &server_url,
+            "--channel-url",
+            &channel_url,
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--dir-template",
+            "{year}/{month}",
+            "--with-metadata",
+            "--no-cache",
+        ];
+        argv.extend_from_slice(extra_args);
+        let args = Args::parse_from(argv);
+        let summary = run(&client, MOCK_CREDENTIALS, &args, &server.url())
+            .await
+            .unwrap();
+        assert_eq!(summary.downloaded, 1);
 
-        let result =
-            download_thumbnail(&client, MOCK_VIDEO_ID_1, &test_thumbnail_url, output_dir).await;
+        let mut files = Vec::new();
+        let mut dirs = vec![output_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    files.push(path.strip_prefix(&output_dir).unwrap().to_path_buf());
+                }
+            }
+        }
+        files.sort();
+        files
+    }
 
-        mock.assert_async().await; // This will now pass!
-        assert!(result.is_ok());
+    #[tokio::test]
+    async fn test_dir_template_nests_files_by_default() {
+        let month_dir = Path::new("2024").join("03");
+        assert_eq!(
+            download_with_dir_template(&[]).await,
+            [
+                month_dir.join(format!("{}.jpg", MOCK_VIDEO_ID_1)),
+                month_dir.join(format!("{}.json", MOCK_VIDEO_ID_1)),
+            ]
+        );
+    }
 
-        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
-        assert!(file_path.exists());
-        let contents = fs::read(file_path).await.unwrap();
-        assert_eq!(contents, image_bytes);
+    #[tokio::test]
+    async fn test_flatten_turns_dir_template_into_file_name_prefix() {
+        assert_eq!(
+            download_with_dir_template(&["--flatten"]).await,
+            [
+                PathBuf::from(format!("2024_03_{}.jpg", MOCK_VIDEO_ID_1)),
+                PathBuf::from(format!("2024_03_{}.json", MOCK_VIDEO_ID_1)),
+            ]
+        );
+        assert_eq!(
+            download_with_dir_template(&["--flatten", "--no-flatten"]).await,
+            download_with_dir_template(&[]).await
+        );
     }
 
     #[tokio::test]
-    async fn test_download_thumbnail_failure() {
-        let client = Client::new();
+    async fn test_per_download_timeout_cancels_slow_download() {
+        let client = test_client();
         let temp_dir = tempdir().unwrap();
         let output_dir = temp_dir.path().to_str().unwrap();
+        let location = OutputLocation::new(output_dir);
         let mut server = mockito::Server::new_async().await;
+        let _slow_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_chunked_body(|writer| {
+                writer.write_all(b"first_chunk")?;
+                writer.flush()?;
+                std::thread::sleep(Duration::from_millis(800));
+                writer.write_all(b"rest_of_the_image")
+            })
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            download_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
 
-        let mock = server
-            .mock("GET", "/thumbnail.jpg")
+        let started = std::time::Instant::now();
+        let outcome =
+            try_download_thumbnail(&client, MOCK_VIDEO_ID_1, &location, &options, &server.url())
+                .await;
+
+        assert!(started.elapsed() < Duration::from_millis(600));
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        let file_path = thumbnail_path(&location, MOCK_VIDEO_ID_1, OutputFormat::Jpg);
+        assert!(!file_path.exists());
+        assert!(!partial_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_per_download_timeout_cleans_up_the_webp_fallback() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path().to_str().unwrap());
+        let mut server = mockito::Server::new_async().await;
+        let _jpg_mock = server
+            .mock("GET", mockito::Matcher::Regex(r"\.jpg$".to_string()))
             .with_status(404)
             .create_async()
             .await;
+        let _slow_webp_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi_webp/{}/maxresdefault.webp", MOCK_VIDEO_ID_1),
+            )
+            .with_header(ETAG, "\"webp\"")
+            .with_chunked_body(|writer| {
+                writer.write_all(b"first_chunk")?;
+                writer.flush()?;
+                std::thread::sleep(Duration::from_millis(2000));
+                writer.write_all(b"rest_of_the_image")
+            })
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            try_webp: true,
+            download_timeout: Some(Duration::from_millis(1000)),
+            ..Default::default()
+        };
 
-        let test_thumbnail_url = format!("{}{}", server.url(), "/thumbnail.jpg");
+        let outcome =
+            try_download_thumbnail(&client, MOCK_VIDEO_ID_1, &location, &options, &server.url())
+                .await;
 
-        let result =
-            download_thumbnail(&client, MOCK_VIDEO_ID_1, &test_thumbnail_url, output_dir).await;
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        let leftovers: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert!(leftovers.is_empty(), "left behind {:?}", leftovers);
+    }
 
-        mock.assert_async().await; // This will now pass!
-        assert!(result.is_ok());
+    #[tokio::test]
+    async fn test_per_download_timeout_cleans_up_a_variant_directory() {
+        let client = test_client();
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path().to_str().unwrap());
+        let mut server = mockito::Server::new_async().await;
+        let _slow_mock = server
+            .mock(
+                "GET",
+                &*format!("/vi/{}/maxresdefault.jpg", MOCK_VIDEO_ID_1),
+            )
+            .with_header(ETAG, "\"maxres\"")
+            .with_chunked_body(|writer| {
+                writer.write_all(b"first_chunk")?;
+                writer.flush()?;
+                std::thread::sleep(Duration::from_millis(800));
+                writer.write_all(b"rest_of_the_image")
+            })
+            .create_async()
+            .await;
+        let options = DownloadOptions {
+            all_resolutions: true,
+            download_timeout: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
 
-        let file_path = Path::new(output_dir).join(format!("{}.jpg", MOCK_VIDEO_ID_1));
-        assert!(!file_path.exists());
+        let outcome =
+            try_download_thumbnail(&client, MOCK_VIDEO_ID_1, &location, &options, &server.url())
+                .await;
+
+        assert_eq!(outcome, DownloadOutcome::NotFound);
+        let video_dir = temp_dir.path().join(MOCK_VIDEO_ID_1);
+        let leftovers: Vec<_> = std::fs::read_dir(&video_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert!(leftovers.is_empty(), "left behind {:?}", leftovers);
     }
 }
@@ -0,0 +1,94 @@
+use crate::compress;
+use crate::paths::{self, OutputLocation};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// The contents of a `{video_id}.json` sidecar written next to a thumbnail.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VideoMetadata {
+    pub video_id: String,
+    pub title: String,
+    pub description: String,
+    pub published_at: String,
+    pub channel_title: String,
+    pub thumbnail_url: String,
+}
+
+/// Returns the path of a video's sidecar file.
+pub fn sidecar_path(location: &OutputLocation, video_id: &str) -> PathBuf {
+    paths::output_path_for(video_id, "json", location)
+}
+
+/// Writes the sidecar for a video, gzipped as `{video_id}.json.gz` if `gzip` is set. The
+/// JSON is written to a temporary file first and renamed into place, so readers never see
+/// a half-written sidecar.
+pub async fn write_sidecar(
+    location: &OutputLocation,
+    metadata: &VideoMetadata,
+    gzip: bool,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let mut path = sidecar_path(location, &metadata.video_id);
+    let mut contents = serde_json::to_string_pretty(metadata)?.into_bytes();
+    if gzip {
+        path = compress::gz_path(&path);
+        contents = compress::gzip(&contents)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    fs::write(&temp_path, contents).await?;
+    fs::rename(&temp_path, &path).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_sidecar_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let metadata = VideoMetadata {
+            video_id: "video1".to_string(),
+            title: "A title".to_string(),
+            description: "Line one\nLine two".to_string(),
+            published_at: "2024-03-15T10:00:00Z".to_string(),
+            channel_title: "A channel".to_string(),
+            thumbnail_url: "https://img.youtube.com/vi/video1/maxresdefault.jpg".to_string(),
+        };
+
+        let path = write_sidecar(&location, &metadata, false).await.unwrap();
+
+        assert_eq!(path, temp_dir.path().join("video1.json"));
+        assert!(!path.with_extension("json.tmp").exists());
+        let contents = fs::read_to_string(&path).await.unwrap();
+        let read_back: VideoMetadata = serde_json::from_str(&contents).unwrap();
+        assert_eq!(read_back, metadata);
+    }
+
+    #[tokio::test]
+    async fn test_gzipped_sidecar() {
+        use flate2::read::GzDecoder;
+
+        let temp_dir = tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let metadata = VideoMetadata {
+            video_id: "video1".to_string(),
+            title: "A title".to_string(),
+            description: String::new(),
+            published_at: "2024-03-15T10:00:00Z".to_string(),
+            channel_title: "A channel".to_string(),
+            thumbnail_url: String::new(),
+        };
+
+        let path = write_sidecar(&location, &metadata, true).await.unwrap();
+
+        assert_eq!(path, temp_dir.path().join("video1.json.gz"));
+        let file = std::fs::File::open(&path).unwrap();
+        let read_back: VideoMetadata = serde_json::from_reader(GzDecoder::new(file)).unwrap();
+        assert_eq!(read_back, metadata);
+    }
+}
@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+use tokio::fs;
+
+/// The figures of a finished run written by `--metrics-file`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    pub downloaded: usize,
+    pub failed: usize,
+    pub bytes: u64,
+    pub duration: Duration,
+    pub quota_units: u64,
+}
+
+impl RunMetrics {
+    /// Renders the metrics in the Prometheus text exposition format. They describe a
+    /// single run, so they're all gauges.
+    pub fn render(&self) -> String {
+        let metrics: [(&str, &str, String); 5] = [
+            (
+                "youtube_images_downloads",
+                "Thumbnails downloaded by the last run.",
+                self.downloaded.to_string(),
+            ),
+            (
+                "youtube_images_failures",
+                "Thumbnails that failed to download in the last run.",
+                self.failed.to_string(),
+            ),
+            (
+                "youtube_images_downloaded_bytes",
+                "Bytes received from the thumbnail server in the last run.",
+                self.bytes.to_string(),
+            ),
+            (
+                "youtube_images_duration_seconds",
+                "How long the last run took.",
+                format!("{:.3}", self.duration.as_secs_f64()),
+            ),
+            (
+                "youtube_images_quota_units_estimated",
+                "Estimated YouTube Data API quota units used by the last run.",
+                self.quota_units.to_string(),
+            ),
+        ];
+        let mut output = String::new();
+        for (name, help, value) in metrics {
+            // Writing to a String can't fail.
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} gauge", name);
+            let _ = writeln!(output, "{} {}", name, value);
+        }
+        output
+    }
+
+    /// Writes the metrics to `path` via a temporary file, so a collector never reads a
+    /// half-written file.
+    pub async fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, self.render()).await?;
+        fs::rename(&temp_path, path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = RunMetrics {
+            downloaded: 3,
+            failed: 1,
+            bytes: 2048,
+            duration: Duration::from_millis(1500),
+            quota_units: 102,
+        };
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            "# HELP youtube_images_downloads Thumbnails downloaded by the last run.\n\
+             # TYPE youtube_images_downloads gauge\n\
+             youtube_images_downloads 3\n"
+        ));
+        assert!(rendered.contains("youtube_images_failures 1\n"));
+        assert!(rendered.contains("youtube_images_downloaded_bytes 2048\n"));
+        assert!(rendered.contains("youtube_images_duration_seconds 1.500\n"));
+        assert!(rendered.contains("youtube_images_quota_units_estimated 102\n"));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::backoff::Backoff;
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{ClientWithMiddleware, Middleware, Next};
+use reqwest_retry::{RetryDecision, RetryPolicy};
+use std::time::{Instant, SystemTime};
+
+/// The HTTP client every API and thumbnail request goes through. `build_client` gives it
+/// the middleware that logs requests and retries failed ones.
+pub type Client = ClientWithMiddleware;
+
+/// Logs every request sent, retries included, with its status and how long it took. Only
+/// the host and path are logged, so an API key in the query string stays out of the logs.
+#[derive(Debug)]
+pub struct RequestLogger;
+
+#[async_trait::async_trait]
+impl Middleware for RequestLogger {
+    async fn handle(
+        &self,
+        request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let method = request.method().clone();
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let path = request.url().path().to_string();
+        let started = Instant::now();
+        let result = next.run(request, extensions).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(response) => tracing::debug!(
+                %method,
+                host,
+                path,
+                status = response.status().as_u16(),
+                elapsed_ms,
+                "HTTP request"
+            ),
+            Err(e) => {
+                tracing::debug!(%method, host, path, error = %e, elapsed_ms, "HTTP request failed")
+            }
+        }
+        result
+    }
+}
+
+/// Lets `RetryTransientMiddleware` wait as `--retries` and `--jitter` ask before retrying a
+/// request that failed to connect, timed out or got a 429 or 5xx answer.
+impl RetryPolicy for Backoff {
+    fn should_retry(&self, _request_start_time: SystemTime, n_past_retries: u32) -> RetryDecision {
+        if n_past_retries < self.retries() {
+            RetryDecision::Retry {
+                execute_after: SystemTime::now() + self.delay(n_past_retries),
+            }
+        } else {
+            RetryDecision::DoNotRetry
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::error::DownloaderError;
+use crate::thumbnail::Resolution;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Name of the file written and removed again to check the output directory is writable.
+const WRITE_TEST_FILE: &str = ".write_test";
+
+/// Creates `dir` if needed and checks a file can be written to it, so an unusable output
+/// directory is reported before any API quota is spent.
+pub async fn ensure_writable_dir(dir: &Path) -> Result<(), DownloaderError> {
+    let unwritable = |e: std::io::Error| {
+        DownloaderError::InvalidInput(format!(
+            "Output directory {} is not writable: {}",
+            dir.display(),
+            e
+        ))
+    };
+    if fs::metadata(dir)
+        .await
+        .is_ok_and(|metadata| !metadata.is_dir())
+    {
+        return Err(DownloaderError::InvalidInput(format!(
+            "Output directory {} is a file, not a directory.",
+            dir.display()
+        )));
+    }
+    fs::create_dir_all(dir).await.map_err(unwritable)?;
+    let test_file = dir.join(WRITE_TEST_FILE);
+    fs::write(&test_file, b"").await.map_err(unwritable)?;
+    fs::remove_file(&test_file).await.map_err(unwritable)?;
+    Ok(())
+}
+
+/// Syncs a directory's entries to disk, so a file renamed into it survives a power loss.
+/// Only Unix can open a directory for this; elsewhere it does nothing.
+pub async fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    fs::File::open(dir).await?.sync_all().await?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Replaces `path` with `contents` by writing a temporary file next to it and renaming it
+/// into place, so readers never see a half-written file. With `fsync`, the temporary file
+/// and then the rename are synced to disk, for `--fsync`.
+pub async fn replace_file(path: &Path, contents: impl AsRef<[u8]>, fsync: bool) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(contents.as_ref()).await?;
+    if fsync {
+        file.sync_all().await?;
+    }
+    drop(file);
+    fs::rename(&temp_path, path).await?;
+    if fsync {
+        sync_dir(path.parent().unwrap_or(Path::new("."))).await?;
+    }
+    Ok(())
+}
+
+/// Replaces characters that aren't safe in a file or directory name on common
+/// filesystems, and neutralises names like `..` that would escape the output directory.
+pub fn sanitize_path_component(component: &str) -> String {
+    let sanitized: String = component
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim().trim_end_matches('.');
+    if sanitized.is_empty() || sanitized.chars().all(|c| c == '.') {
+        "_".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// Expands a `--dir-template` such as `{year}/{month}` for a single video.
+/// `published_at` is an RFC 3339 timestamp as returned by the API; date placeholders
+/// become `unknown` if it's missing or malformed. Every component is sanitized.
+pub fn render_dir_template(template: &str, published_at: Option<&str>, channel: &str) -> PathBuf {
+    let date_part = |range: std::ops::Range<usize>| {
+        published_at
+            .and_then(|date| date.get(range))
+            .filter(|part| part.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or("unknown")
+    };
+    let (year, month, day) = (date_part(0..4), date_part(5..7), date_part(8..10));
+
+    template
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .map(|component| {
+            let rendered = component
+                .replace("{year}", year)
+                .replace("{month}", month)
+                .replace("{day}", day)
+                .replace("{channel}", channel);
+            sanitize_path_component(&rendered)
+        })
+        .collect()
+}
+
+/// Where a video's files are saved. Subdirectories from features like `--dir-template`
+/// and per-channel directories are added with `join`; a flat location turns them into a
+/// file name prefix instead, so every file stays in one directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLocation {
+    dir: PathBuf,
+    prefix: Vec<String>,
+    flatten: bool,
+}
+
+impl OutputLocation {
+    /// A location that nests subdirectories below `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        OutputLocation {
+            dir: dir.into(),
+            prefix: Vec::new(),
+            flatten: false,
+        }
+    }
+
+    /// A location that keeps every file directly in `dir`, for `--flatten`.
+    pub fn flat(dir: impl Into<PathBuf>) -> Self {
+        OutputLocation {
+            flatten: true,
+            ..OutputLocation::new(dir)
+        }
+    }
+
+    /// The directory files at this location are written to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The location for files organized into `subdirs` below this one.
+    pub fn join(&self, subdirs: impl AsRef<Path>) -> Self {
+        let mut location = self.clone();
+        if self.flatten {
+            location.prefix.extend(
+                subdirs
+                    .as_ref()
+                    .iter()
+                    .map(|component| component.to_string_lossy().into_owned()),
+            );
+        } else {
+            location.dir.push(subdirs);
+        }
+        location
+    }
+
+    /// The video ID of a file this location would name `{id}.{extension}`, the inverse of
+    /// `output_path_for`. Returns `None` for files that belong elsewhere.
+    pub fn video_id_of<'a>(&self, file_name: &'a str, extension: &str) -> Option<&'a str> {
+        let mut rest = file_name.strip_suffix(extension)?.strip_suffix('.')?;
+        for component in &self.prefix {
+            rest = rest.strip_prefix(component.as_str())?.strip_prefix('_')?;
+        }
+        Some(rest)
+    }
+}
+
+/// Builds the path of a video's file with the given extension, e.g. its thumbnail or
+/// metadata sidecar. Every file a download writes is named through here, so nested and
+/// flat layouts stay consistent: `dir/2024/03/{id}.jpg` or `dir/2024_03_{id}.jpg`.
+pub fn output_path_for(video_id: &str, extension: &str, location: &OutputLocation) -> PathBuf {
+    let mut name = String::new();
+    for component in &location.prefix {
+        name.push_str(component);
+        name.push('_');
+    }
+    name.push_str(video_id);
+    name.push('.');
+    name.push_str(extension);
+    location.dir.join(name)
+}
+
+/// Builds the path of one variant of a video's thumbnail for `--all-resolutions`. With a
+/// `--variant-template` the file is named after it, `{id}` and `{res}` filled in; otherwise
+/// it keeps the CDN's name, like `maxresdefault`, in a directory per video, which a flat
+/// location turns into an `{id}_` prefix.
+pub fn variant_path_for(
+    video_id: &str,
+    resolution: Resolution,
+    extension: &str,
+    location: &OutputLocation,
+    template: Option<&str>,
+) -> PathBuf {
+    match template {
+        Some(template) => {
+            let name = template
+                .replace("{id}", video_id)
+                .replace("{res}", resolution.short_name());
+            output_path_for(&sanitize_path_component(&name), extension, location)
+        }
+        None => output_path_for(resolution.file_stem(), extension, &location.join(video_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_year_month_template() {
+        assert_eq!(
+            render_dir_template("{year}/{month}", Some("2024-03-15T10:00:00Z"), "Chan"),
+            Path::new("2024").join("03")
+        );
+    }
+
+    #[test]
+    fn test_render_all_placeholders() {
+        assert_eq!(
+            render_dir_template(
+                "{channel}/{year}-{month}-{day}",
+                Some("2024-03-15T10:00:00Z"),
+                "My Channel"
+            ),
+            Path::new("My Channel").join("2024-03-15")
+        );
+    }
+
+    #[test]
+    fn test_render_missing_date() {
+        assert_eq!(
+            render_dir_template("{year}/{month}", None, "Chan"),
+            Path::new("unknown").join("unknown")
+        );
+        assert_eq!(
+            render_dir_template("{year}", Some("garbage"), "Chan"),
+            Path::new("unknown")
+        );
+    }
+
+    #[test]
+    fn test_render_sanitizes_placeholder_values() {
+        assert_eq!(
+            render_dir_template("{channel}", None, "AC/DC: Live?"),
+            Path::new("AC_DC_ Live_")
+        );
+        assert_eq!(render_dir_template("{channel}", None, ".."), Path::new("_"));
+        assert_eq!(
+            render_dir_template("../{year}", None, ""),
+            Path::new("_").join("unknown")
+        );
+    }
+
+    #[test]
+    fn test_nested_and_flat_output_paths() {
+        let subdirs = render_dir_template("{year}/{month}", Some("2024-03-15T10:00:00Z"), "");
+        let nested = OutputLocation::new("out").join("chan").join(&subdirs);
+        let flat = OutputLocation::flat("out").join("chan").join(&subdirs);
+
+        assert_eq!(
+            output_path_for("abc", "jpg", &nested),
+            Path::new("out")
+                .join("chan")
+                .join("2024")
+                .join("03")
+                .join("abc.jpg")
+        );
+        assert_eq!(
+            nested.dir(),
+            Path::new("out").join("chan").join("2024").join("03")
+        );
+        assert_eq!(
+            output_path_for("abc", "jpg", &flat),
+            Path::new("out").join("chan_2024_03_abc.jpg")
+        );
+        assert_eq!(flat.dir(), Path::new("out"));
+        assert_eq!(flat.video_id_of("chan_2024_03_abc.jpg", "jpg"), Some("abc"));
+        assert_eq!(flat.video_id_of("other_2024_03_abc.jpg", "jpg"), None);
+        assert_eq!(nested.video_id_of("abc.jpg", "jpg"), Some("abc"));
+
+        let variant = |location, template| {
+            variant_path_for("abc", Resolution::High, "jpg", location, template)
+        };
+        assert_eq!(
+            variant(&nested, None),
+            nested.dir().join("abc").join("hqdefault.jpg")
+        );
+        assert_eq!(
+            variant(&flat, None),
+            Path::new("out").join("chan_2024_03_abc_hqdefault.jpg")
+        );
+        assert_eq!(
+            variant(&flat, Some("{id}-{res}")),
+            Path::new("out").join("chan_2024_03_abc-hq.jpg")
+        );
+        assert_eq!(nested.video_id_of("abc.json", "jpg"), None);
+        assert_eq!(
+            output_path_for("abc", "json", &OutputLocation::flat("out")),
+            Path::new("out").join("abc.json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_file_with_fsync() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("state.json");
+        std::fs::write(&path, "old").unwrap();
+
+        replace_file(&path, "new", true).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert!(!temp_dir.path().join("state.json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_ensure_writable_dir_creates_the_directory() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("a").join("b");
+
+        ensure_writable_dir(&dir).await.unwrap();
+
+        assert!(dir.is_dir());
+        assert!(!dir.join(WRITE_TEST_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn test_ensure_writable_dir_rejects_a_file() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("thumbnails");
+        std::fs::write(&file, "not a directory").unwrap();
+
+        let error = ensure_writable_dir(&file).await.unwrap_err();
+
+        assert!(error.to_string().contains("is a file, not a directory"));
+    }
+
+    // /proc can't be written to even by root, unlike a directory with its permissions removed.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_ensure_writable_dir_rejects_a_read_only_location() {
+        let error = ensure_writable_dir(Path::new("/proc/youtube-images"))
+            .await
+            .unwrap_err();
+
+        assert!(
+            error.to_string().contains("is not writable"),
+            "unexpected error: {}",
+            error
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// A channel offered to the user when a URL could refer to several.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelCandidate {
+    pub channel_id: String,
+    pub title: String,
+    pub subscriber_count: Option<u64>,
+}
+
+impl fmt::Display for ChannelCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", self.title)?;
+        match self.subscriber_count {
+            Some(count) => write!(f, "{} subscribers", count)?,
+            None => write!(f, "subscriber count hidden")?,
+        }
+        write!(f, ", {})", self.channel_id)
+    }
+}
+
+/// Chooses between candidate channels for `--interactive`.
+pub trait ChannelPicker: fmt::Debug + Send + Sync {
+    /// Returns the index of the chosen candidate, or `None` if the user gave up.
+    fn pick(&self, candidates: &[ChannelCandidate]) -> io::Result<Option<usize>>;
+}
+
+/// Asks on the terminal, listing the candidates on stderr and reading the answer from stdin.
+#[derive(Debug)]
+pub struct TerminalPicker;
+
+impl ChannelPicker for TerminalPicker {
+    fn pick(&self, candidates: &[ChannelCandidate]) -> io::Result<Option<usize>> {
+        prompt(candidates, &mut io::stdin().lock(), &mut io::stderr())
+    }
+}
+
+/// Lists the candidates on `output` and reads a choice from `input`: a number from the
+/// list, nothing for the first candidate, or `q` to give up. Invalid answers are asked
+/// again; the end of the input gives up.
+pub fn prompt(
+    candidates: &[ChannelCandidate],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<usize>> {
+    writeln!(output, "Several channels match:")?;
+    for (number, candidate) in candidates.iter().enumerate() {
+        writeln!(output, "  {}) {}", number + 1, candidate)?;
+    }
+    loop {
+        write!(
+            output,
+            "Choose a channel [1-{}, default 1, q to quit]: ",
+            candidates.len()
+        )?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim() {
+            "" => return Ok(Some(0)),
+            "q" | "Q" => return Ok(None),
+            answer => match answer.parse::<usize>() {
+                Ok(number) if (1..=candidates.len()).contains(&number) => {
+                    return Ok(Some(number - 1))
+                }
+                _ => writeln!(output, "Please enter a number from the list.")?,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<ChannelCandidate> {
+        vec![
+            ChannelCandidate {
+                channel_id: "UC_one".to_string(),
+                title: "One".to_string(),
+                subscriber_count: Some(1200),
+            },
+            ChannelCandidate {
+                channel_id: "UC_two".to_string(),
+                title: "Two".to_string(),
+                subscriber_count: None,
+            },
+        ]
+    }
+
+    fn answer(input: &str) -> (Option<usize>, String) {
+        let mut output = Vec::new();
+        let choice = prompt(&candidates(), &mut input.as_bytes(), &mut output).unwrap();
+        (choice, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_prompt_lists_candidates() {
+        let (choice, output) = answer("2\n");
+        assert_eq!(choice, Some(1));
+        assert!(output.contains("  1) One (1200 subscribers, UC_one)"));
+        assert!(output.contains("  2) Two (subscriber count hidden, UC_two)"));
+    }
+
+    #[test]
+    fn test_prompt_defaults_to_first_candidate() {
+        assert_eq!(answer("\n").0, Some(0));
+    }
+
+    #[test]
+    fn test_prompt_asks_again_after_invalid_answer() {
+        let (choice, output) = answer("7\nabc\n2\n");
+        assert_eq!(choice, Some(1));
+        assert_eq!(output.matches("Please enter a number").count(), 2);
+    }
+
+    #[test]
+    fn test_prompt_can_be_abandoned() {
+        assert_eq!(answer("q\n").0, None);
+        assert_eq!(answer("").0, None);
+    }
+}
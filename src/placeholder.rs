@@ -0,0 +1,112 @@
+use crate::dedup::ContentHash;
+use crate::thumbnail::Resolution;
+use std::fmt;
+use std::str::FromStr;
+
+/// The signature YouTube's "no thumbnail" image is recognized by unless `--placeholder`
+/// says otherwise: the small gray 120x90 picture served with a 200 in place of a real
+/// thumbnail, e.g. as the `hqdefault.jpg` of a removed video.
+pub const DEFAULT_PLACEHOLDER: &str = "120x90";
+
+/// A way to recognize a placeholder image served in place of a real thumbnail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderSignature {
+    /// An image with exactly this content.
+    Sha256(ContentHash),
+    /// An image of this size served for a variant that's nominally a different size. The
+    /// 120x90 `default.jpg` therefore only matches by hash.
+    Dimensions(u32, u32),
+}
+
+impl PlaceholderSignature {
+    /// Checks whether an image received for `resolution`, with the given SHA-256 hash and
+    /// dimensions (if they could be read), matches this signature.
+    pub fn matches(
+        &self,
+        resolution: Resolution,
+        hash: &ContentHash,
+        dimensions: Option<(u32, u32)>,
+    ) -> bool {
+        match *self {
+            PlaceholderSignature::Sha256(placeholder) => placeholder == *hash,
+            PlaceholderSignature::Dimensions(width, height) => {
+                dimensions == Some((width, height)) && resolution.dimensions() != (width, height)
+            }
+        }
+    }
+}
+
+impl FromStr for PlaceholderSignature {
+    type Err = String;
+
+    /// Parses `sha256:<64 hex digits>` or `<width>x<height>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = s.strip_prefix("sha256:") {
+            let mut hash = [0u8; 32];
+            if hex.len() != 64 || !hex.is_ascii() {
+                return Err(format!("\"{}\" is not a SHA-256 hash", hex));
+            }
+            for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+                let pair = std::str::from_utf8(pair).unwrap_or_default();
+                *byte = u8::from_str_radix(pair, 16)
+                    .map_err(|_| format!("\"{}\" is not a SHA-256 hash", hex))?;
+            }
+            return Ok(PlaceholderSignature::Sha256(hash));
+        }
+        let invalid = || {
+            format!(
+                "\"{}\" is neither sha256:<hash> nor <width>x<height>, e.g. 120x90",
+                s
+            )
+        };
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        Ok(PlaceholderSignature::Dimensions(
+            width.parse().map_err(|_| invalid())?,
+            height.parse().map_err(|_| invalid())?,
+        ))
+    }
+}
+
+impl fmt::Display for PlaceholderSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaceholderSignature::Sha256(hash) => {
+                write!(f, "sha256:")?;
+                hash.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+            PlaceholderSignature::Dimensions(width, height) => write!(f, "{}x{}", width, height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_parse_signatures() {
+        assert_eq!(
+            "120x90".parse(),
+            Ok(PlaceholderSignature::Dimensions(120, 90))
+        );
+        let hash: ContentHash = Sha256::digest(b"placeholder").into();
+        let signature = PlaceholderSignature::Sha256(hash);
+        assert_eq!(signature.to_string().parse(), Ok(signature));
+        assert!("120".parse::<PlaceholderSignature>().is_err());
+        assert!("sha256:abc".parse::<PlaceholderSignature>().is_err());
+        assert!(format!("sha256:{}", "zz".repeat(32))
+            .parse::<PlaceholderSignature>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_dimensions_ignore_variants_of_that_size() {
+        let signature = PlaceholderSignature::Dimensions(120, 90);
+        let hash = [0; 32];
+        assert!(signature.matches(Resolution::High, &hash, Some((120, 90))));
+        assert!(!signature.matches(Resolution::High, &hash, Some((480, 360))));
+        assert!(!signature.matches(Resolution::High, &hash, None));
+        assert!(!signature.matches(Resolution::Default, &hash, Some((120, 90))));
+    }
+}
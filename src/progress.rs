@@ -0,0 +1,383 @@
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static HUMAN_OUTPUT: AtomicBool = AtomicBool::new(true);
+static QUIET: AtomicBool = AtomicBool::new(false);
+/// Whether messages on stdout and stderr are colored; off until `init_color` decides.
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Turns the human-readable progress messages printed by `status!` on or off.
+pub fn set_human_output(enabled: bool) {
+    HUMAN_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn human_output() -> bool {
+    HUMAN_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Silences everything but errors for `--quiet`: `notice!` messages, and with
+/// `set_human_output(false)` the `status!` ones.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints a message about something the run worked around, such as a retry, to stderr,
+/// unless `--quiet` is given.
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if !$crate::progress::quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a human-readable progress message to stdout, unless stdout is reserved for
+/// `--json` events.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::progress::human_output() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Whether to color a stream: only a terminal, and never with `--no-color` or a non-empty
+/// `NO_COLOR` (see https://no-color.org).
+pub fn color_enabled(no_color: bool, no_color_env: Option<&OsStr>, is_terminal: bool) -> bool {
+    !no_color && no_color_env.is_none_or(OsStr::is_empty) && is_terminal
+}
+
+/// Decides whether `success`, `skipped` and `failure` color their messages, separately for
+/// stdout and stderr so piping one of them doesn't put escape codes into the file.
+pub fn init_color(no_color: bool) {
+    let env = std::env::var_os("NO_COLOR");
+    let enabled = |is_terminal| color_enabled(no_color, env.as_deref(), is_terminal);
+    COLOR_STDOUT.store(enabled(io::stdout().is_terminal()), Ordering::Relaxed);
+    COLOR_STDERR.store(enabled(io::stderr().is_terminal()), Ordering::Relaxed);
+}
+
+/// A message about a thumbnail that was saved, for stdout: green on a terminal.
+pub fn success(message: impl Display) -> String {
+    if COLOR_STDOUT.load(Ordering::Relaxed) {
+        message.green().to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+/// A message about a video that was skipped, for stdout: yellow on a terminal.
+pub fn skipped(message: impl Display) -> String {
+    if COLOR_STDOUT.load(Ordering::Relaxed) {
+        message.yellow().to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+/// A message about a failure, for stderr: red on a terminal.
+pub fn failure(message: impl Display) -> String {
+    if COLOR_STDERR.load(Ordering::Relaxed) {
+        message.red().to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+/// Formats a byte count with a decimal unit, e.g. `287 MB`, keeping one decimal place for
+/// values under 10.
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", value.round())
+    } else if value < 10.0 {
+        format!("{:.1} {}", value, UNITS[unit])
+    } else {
+        format!("{:.0} {}", value, UNITS[unit])
+    }
+}
+
+/// Describes how much was downloaded and how fast, e.g.
+/// `412 thumbnails (287 MB) in 48s (6.0 MB/s)`.
+pub fn transfer_report(thumbnails: usize, bytes: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let elapsed = if seconds < 10.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        format!("{:.0}s", seconds)
+    };
+    let mut report = format!(
+        "{} thumbnails ({}) in {}",
+        thumbnails,
+        format_bytes(bytes as f64),
+        elapsed
+    );
+    if seconds > 0.0 {
+        report.push_str(&format!(" ({}/s)", format_bytes(bytes as f64 / seconds)));
+    }
+    report
+}
+
+/// How a thumbnail download ended.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Ok,
+    /// The thumbnail on disk was already up to date.
+    Skipped,
+    Failed,
+}
+
+/// A machine-readable progress event, written as one JSON object per line.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Resolved {
+        channel_url: String,
+        channel_id: String,
+    },
+    Download {
+        video_id: String,
+        status: DownloadStatus,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<u64>,
+    },
+    Summary {
+        ok: usize,
+        failed: usize,
+    },
+}
+
+/// A step of a run, reported to a `ProgressCallback` as it happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// The channel URL was resolved to a channel ID.
+    Resolved {
+        channel_url: String,
+        channel_id: String,
+    },
+    /// A thumbnail was saved.
+    Downloaded { video_id: String, bytes: u64 },
+    /// The thumbnail on disk was already up to date.
+    Skipped { video_id: String },
+    /// The video got no thumbnail.
+    Failed { video_id: String },
+    /// Every video of the channel was handled.
+    Finished { ok: usize, failed: usize },
+}
+
+impl From<ProgressEvent> for Event {
+    fn from(event: ProgressEvent) -> Self {
+        let download = |video_id, status, bytes| Event::Download {
+            video_id,
+            status,
+            bytes,
+        };
+        match event {
+            ProgressEvent::Resolved {
+                channel_url,
+                channel_id,
+            } => Event::Resolved {
+                channel_url,
+                channel_id,
+            },
+            ProgressEvent::Downloaded { video_id, bytes } => {
+                download(video_id, DownloadStatus::Ok, Some(bytes))
+            }
+            ProgressEvent::Skipped { video_id } => {
+                download(video_id, DownloadStatus::Skipped, None)
+            }
+            ProgressEvent::Failed { video_id } => download(video_id, DownloadStatus::Failed, None),
+            ProgressEvent::Finished { ok, failed } => Event::Summary { ok, failed },
+        }
+    }
+}
+
+/// Receives the progress events of a run, from any download task, so a caller can show
+/// progress its own way. `--json` writes them to stdout with `EventSink::into_callback`.
+pub struct ProgressCallback(Box<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressCallback").finish_non_exhaustive()
+    }
+}
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        ProgressCallback(Box::new(callback))
+    }
+
+    pub fn report(&self, event: ProgressEvent) {
+        (self.0)(event)
+    }
+}
+
+/// Writes progress events as JSON lines, shared between download tasks.
+pub struct EventSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSink").finish_non_exhaustive()
+    }
+}
+
+impl EventSink {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        EventSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn stdout() -> Self {
+        EventSink::new(Box::new(io::stdout()))
+    }
+
+    /// Writes a single event. A broken pipe shouldn't abort downloads, so write errors
+    /// are reported and otherwise ignored.
+    pub fn emit(&self, event: &Event) {
+        let mut writer = self.writer.lock().unwrap();
+        let result = serde_json::to_writer(&mut *writer, event)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writer.flush());
+        if let Err(e) = result {
+            eprintln!("Error writing progress event: {}", e);
+        }
+    }
+
+    /// A callback writing every progress event it receives as a JSON line.
+    pub fn into_callback(self) -> ProgressCallback {
+        ProgressCallback::new(move |event| self.emit(&event.into()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_color_only_on_a_terminal_without_opt_out() {
+        assert!(color_enabled(false, None, true));
+        assert!(color_enabled(false, Some(OsStr::new("")), true));
+        assert!(!color_enabled(false, None, false));
+        assert!(!color_enabled(true, None, true));
+        assert!(!color_enabled(false, Some(OsStr::new("1")), true));
+    }
+
+    #[test]
+    fn test_no_escape_codes_when_not_a_terminal() {
+        // Test output is captured, so neither stream is a terminal.
+        init_color(false);
+        for message in [success("saved"), skipped("kept"), failure("failed")] {
+            assert!(!message.contains('\x1b'), "{:?}", message);
+        }
+        assert_eq!(success(format_args!("video {}", 1)), "video 1");
+    }
+
+    /// An in-memory writer that can be inspected after being handed to an `EventSink`.
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuffer(pub(crate) Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(6_000_000.0), "6.0 MB");
+        assert_eq!(format_bytes(287_400_000.0), "287 MB");
+        assert_eq!(format_bytes(1_500.0), "1.5 kB");
+    }
+
+    #[test]
+    fn test_transfer_report() {
+        assert_eq!(
+            transfer_report(412, 287_000_000, Duration::from_secs(48)),
+            "412 thumbnails (287 MB) in 48s (6.0 MB/s)"
+        );
+        assert_eq!(
+            transfer_report(0, 0, Duration::ZERO),
+            "0 thumbnails (0 B) in 0.0s"
+        );
+    }
+
+    #[test]
+    fn test_events_are_written_as_json_lines() {
+        let buffer = SharedBuffer::default();
+        let sink = EventSink::new(Box::new(buffer.clone()));
+
+        sink.emit(&Event::Download {
+            video_id: "video1".to_string(),
+            status: DownloadStatus::Ok,
+            bytes: Some(42),
+        });
+        sink.emit(&Event::Download {
+            video_id: "video2".to_string(),
+            status: DownloadStatus::Failed,
+            bytes: None,
+        });
+        sink.emit(&Event::Summary { ok: 1, failed: 1 });
+
+        let lines: Vec<serde_json::Value> = buffer
+            .contents()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({"event": "download", "video_id": "video1", "status": "ok", "bytes": 42}),
+                serde_json::json!({"event": "download", "video_id": "video2", "status": "failed"}),
+                serde_json::json!({"event": "summary", "ok": 1, "failed": 1}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_callback_writes_progress_events_as_json() {
+        let buffer = SharedBuffer::default();
+        let callback = EventSink::new(Box::new(buffer.clone())).into_callback();
+
+        callback.report(ProgressEvent::Skipped {
+            video_id: "video1".to_string(),
+        });
+
+        assert_eq!(
+            buffer.contents(),
+            "{\"event\":\"download\",\"video_id\":\"video1\",\"status\":\"skipped\"}\n"
+        );
+    }
+}
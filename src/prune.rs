@@ -0,0 +1,79 @@
+use crate::paths::OutputLocation;
+use crate::video_ids::is_valid_video_id;
+use std::collections::HashSet;
+use std::io;
+use tokio::fs;
+
+/// Subdirectory of a channel's output directory that `--prune` moves thumbnails into.
+pub const TRASH_DIR: &str = ".trash";
+
+/// Removes the thumbnails at `location` whose video isn't in `current` any more, moving
+/// them to `.trash` unless `hard` is set. Only files named like a thumbnail of this
+/// location, `{id}.{extension}` with a valid video ID, are considered. Returns how many
+/// were pruned.
+pub async fn prune(
+    location: &OutputLocation,
+    extension: &str,
+    current: &HashSet<&str>,
+    hard: bool,
+) -> io::Result<usize> {
+    let mut entries = match fs::read_dir(location.dir()).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let trash = location.dir().join(TRASH_DIR);
+    let mut pruned = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let orphaned = location
+            .video_id_of(file_name, extension)
+            .is_some_and(|id| is_valid_video_id(id) && !current.contains(id));
+        if !orphaned || !entry.file_type().await?.is_file() {
+            continue;
+        }
+        if hard {
+            fs::remove_file(entry.path()).await?;
+        } else {
+            fs::create_dir_all(&trash).await?;
+            fs::rename(entry.path(), trash.join(file_name)).await?;
+        }
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_prune_hard_deletes_only_orphans() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        for name in [
+            "keptVideo01.jpg",
+            "goneVideo01.jpg",
+            "goneVideo01.json",
+            "notes.jpg",
+        ] {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+        let current = HashSet::from(["keptVideo01"]);
+
+        let pruned = prune(&OutputLocation::new(dir), "jpg", &current, true)
+            .await
+            .unwrap();
+
+        assert_eq!(pruned, 1);
+        assert!(!dir.join("goneVideo01.jpg").exists());
+        assert!(!dir.join(TRASH_DIR).exists());
+        for name in ["keptVideo01.jpg", "goneVideo01.json", "notes.jpg"] {
+            assert!(dir.join(name).exists(), "{} was pruned", name);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The quota units a search request costs.
+pub const SEARCH_COST: u64 = 100;
+/// The quota units every other request this tool makes costs, e.g. listing a page of
+/// channels, playlist items or videos.
+pub const LIST_COST: u64 = 1;
+/// The quota units a Google Cloud project gets per day by default.
+pub const DAILY_QUOTA: u64 = 10_000;
+
+/// Quota units spent by the Data API requests of this process so far.
+static UNITS_USED: AtomicU64 = AtomicU64::new(0);
+
+/// The quota units the Data API charges for a request to `url`: 100 for a search and 1
+/// for every other call this tool makes.
+pub fn cost(url: &str) -> u64 {
+    if url.contains("/youtube/v3/search?") {
+        SEARCH_COST
+    } else {
+        LIST_COST
+    }
+}
+
+/// Counts the cost of a request about to be sent to `url`.
+pub fn record(url: &str) {
+    UNITS_USED.fetch_add(cost(url), Ordering::Relaxed);
+}
+
+/// An estimate of the quota units used so far. Requests that fail are counted too, as
+/// most still cost quota.
+pub fn units_used() -> u64 {
+    UNITS_USED.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_costs_more() {
+        assert_eq!(
+            cost("https://www.googleapis.com/youtube/v3/search?part=id&q=x"),
+            100
+        );
+        assert_eq!(
+            cost("https://www.googleapis.com/youtube/v3/channels?part=id&id=x"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_record_adds_up() {
+        let before = units_used();
+        record("https://www.googleapis.com/youtube/v3/videos?part=snippet&id=x");
+        // Other tests may record requests concurrently.
+        assert!(units_used() > before);
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// A token-bucket limiter shared by all download tasks to cap aggregate throughput.
+/// The bucket starts empty and holds at most one second's worth of bytes.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available bytes. Goes negative when a chunk is taken on credit.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec: bytes_per_sec as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until `bytes` may be consumed without exceeding the configured rate.
+    pub async fn acquire(&self, bytes: usize) {
+        // Holding the lock while sleeping queues other tasks behind us, so
+        // waiters are served in order and the debt is never double-counted.
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.bytes_per_sec).min(self.bytes_per_sec);
+        bucket.last_refill = now;
+
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens < 0.0 {
+            let wait = Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec);
+            tokio::time::sleep(wait).await;
+            bucket.tokens = 0.0;
+            bucket.last_refill = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_debt() {
+        let limiter = RateLimiter::new(10_000);
+        let start = std::time::Instant::now();
+        limiter.acquire(2_000).await;
+        limiter.acquire(2_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::thumbnail::Resolution;
+use clap::ValueEnum;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+
+/// What a video's download ended with, for the `--resolutions-report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolutionRow {
+    pub video_id: String,
+    /// The variant that was saved or kept; `None` if there was none or it isn't known.
+    pub resolution: Option<Resolution>,
+    /// The saved image's size, if it could be read.
+    pub dimensions: Option<(u32, u32)>,
+    /// The saved file's size.
+    pub bytes: Option<u64>,
+}
+
+/// Collects the variant every video ended up with during a run, and writes them as a CSV
+/// once it's over.
+#[derive(Debug)]
+pub struct ResolutionReport {
+    path: PathBuf,
+    rows: Mutex<Vec<ResolutionRow>>,
+}
+
+impl ResolutionReport {
+    pub fn new(path: &Path) -> Self {
+        ResolutionReport {
+            path: path.to_path_buf(),
+            rows: Mutex::default(),
+        }
+    }
+
+    /// The path the report is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, row: ResolutionRow) {
+        self.rows.lock().unwrap().push(row);
+    }
+
+    /// Renders the rows, ordered by video ID, with a header row. Unknown values are left
+    /// empty.
+    pub fn to_csv(&self) -> String {
+        let mut rows = self.rows.lock().unwrap().clone();
+        rows.sort_by(|a, b| a.video_id.cmp(&b.video_id));
+        let mut csv = String::from("video_id,chosen_resolution,width,height,bytes\n");
+        let number = |number: Option<u64>| number.map(|n| n.to_string()).unwrap_or_default();
+        for row in rows {
+            let resolution = row
+                .resolution
+                .and_then(|resolution| resolution.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default();
+            let (width, height) = row.dimensions.unzip();
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                row.video_id,
+                resolution,
+                number(width.map(u64::from)),
+                number(height.map(u64::from)),
+                number(row.bytes)
+            ));
+        }
+        csv
+    }
+
+    /// Writes the report, creating its directory if needed.
+    pub async fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&self.path, self.to_csv()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_orders_rows_and_leaves_unknowns_empty() {
+        let report = ResolutionReport::new(Path::new("report.csv"));
+        report.record(ResolutionRow {
+            video_id: "video2".to_string(),
+            resolution: None,
+            dimensions: None,
+            bytes: None,
+        });
+        report.record(ResolutionRow {
+            video_id: "video1".to_string(),
+            resolution: Some(Resolution::Standard),
+            dimensions: Some((640, 480)),
+            bytes: Some(12345),
+        });
+
+        assert_eq!(
+            report.to_csv(),
+            "video_id,chosen_resolution,width,height,bytes\n\
+             video1,sd,640,480,12345\n\
+             video2,,,,\n"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+/// A small seedable pseudo-random generator (SplitMix64). Implemented here rather than
+/// taken from a crate so that a given seed yields the same order in every version.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`. `bound` must not be zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // Reject the top partial range so every value is equally likely.
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// A seed taken from the clock, for when the user didn't give one.
+pub fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Randomly permutes `items` (Fisher-Yates), the same way for the same seed.
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut rng = SplitMix64::new(seed);
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video_ids() -> Vec<String> {
+        (1..=8).map(|n| format!("video{}", n)).collect()
+    }
+
+    #[test]
+    fn test_fixed_seed_gives_fixed_order() {
+        let mut ids = video_ids();
+        shuffle(&mut ids, 42);
+        assert_eq!(
+            ids,
+            ["video4", "video2", "video7", "video3", "video5", "video1", "video8", "video6"]
+        );
+    }
+
+    #[test]
+    fn test_shuffle_is_a_permutation() {
+        for seed in 0..20 {
+            let mut ids = video_ids();
+            shuffle(&mut ids, seed);
+            ids.sort();
+            assert_eq!(ids, video_ids());
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_give_different_orders() {
+        let mut first = video_ids();
+        let mut second = video_ids();
+        shuffle(&mut first, 1);
+        shuffle(&mut second, 2);
+        assert_ne!(first, second);
+    }
+}
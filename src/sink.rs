@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A place downloaded thumbnails are delivered to once they're saved in the output
+/// directory, such as an object store for `--output-s3`.
+#[async_trait::async_trait]
+pub trait Sink: fmt::Debug + Send + Sync {
+    /// Stores the thumbnail saved at `path`.
+    async fn put(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// A bucket and key prefix given as `s3://bucket/prefix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Location {
+    pub bucket: String,
+    /// Prepended to every key; empty or ending in `/`.
+    pub prefix: String,
+}
+
+impl FromStr for S3Location {
+    type Err = String;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let rest = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| format!("\"{}\" isn't an s3://bucket/prefix URL", url))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("\"{}\" doesn't name a bucket", url));
+        }
+        let prefix = prefix.trim_matches('/');
+        Ok(S3Location {
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", prefix)
+            },
+        })
+    }
+}
+
+#[cfg(feature = "s3")]
+pub use s3_sink::S3Sink;
+
+#[cfg(feature = "s3")]
+mod s3_sink {
+    use super::*;
+    use crate::archive;
+    use s3::creds::Credentials;
+    use s3::{Bucket, Region};
+    use std::path::PathBuf;
+
+    /// Uploads thumbnails to an S3-compatible bucket, keyed by the prefix followed by their
+    /// path relative to the output directory.
+    #[derive(Debug)]
+    pub struct S3Sink {
+        bucket: Box<Bucket>,
+        prefix: String,
+        output_dir: PathBuf,
+    }
+
+    impl S3Sink {
+        pub fn new(
+            location: &S3Location,
+            region: Region,
+            credentials: Credentials,
+            output_dir: &Path,
+        ) -> Result<Self, Box<dyn Error>> {
+            let mut bucket = Bucket::new(&location.bucket, region.clone(), credentials)?;
+            // Custom endpoints such as MinIO usually don't have a DNS name per bucket.
+            if matches!(region, Region::Custom { .. }) {
+                bucket = bucket.with_path_style();
+            }
+            Ok(S3Sink {
+                bucket,
+                prefix: location.prefix.clone(),
+                output_dir: output_dir.to_path_buf(),
+            })
+        }
+
+        /// Connects with the credentials in `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+        /// `AWS_SESSION_TOKEN` (or the shared credentials file), in the region from
+        /// `AWS_REGION` or `AWS_DEFAULT_REGION`. `AWS_ENDPOINT_URL` points it at another
+        /// S3-compatible service.
+        pub fn from_env(location: &S3Location, output_dir: &Path) -> Result<Self, Box<dyn Error>> {
+            let region = std::env::var("AWS_REGION")
+                .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|_| "us-east-1".to_string());
+            let region = match std::env::var("AWS_ENDPOINT_URL") {
+                Ok(endpoint) => Region::Custom { region, endpoint },
+                Err(_) => region.parse()?,
+            };
+            let credentials = Credentials::default()
+                .map_err(|e| format!("No AWS credentials for --output-s3: {}", e))?;
+            S3Sink::new(location, region, credentials, output_dir)
+        }
+
+        /// The key a thumbnail saved at `path` is stored under.
+        fn key(&self, path: &Path) -> String {
+            let relative = path.strip_prefix(&self.output_dir).unwrap_or(path);
+            format!("{}{}", self.prefix, archive::entry_name(relative))
+        }
+    }
+
+    /// The content type an object store should serve a thumbnail with.
+    pub(super) fn content_type(path: &Path) -> &'static str {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jpg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("webp") => "image/webp",
+            _ => "application/octet-stream",
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for S3Sink {
+        async fn put(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let contents = tokio::fs::read(path).await?;
+            self.bucket
+                .put_object_with_content_type(self.key(path), &contents, content_type(path))
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_location() {
+        let location = |url: &str| url.parse::<S3Location>();
+        assert_eq!(
+            location("s3://bucket/thumbs/2024/"),
+            Ok(S3Location {
+                bucket: "bucket".to_string(),
+                prefix: "thumbs/2024/".to_string(),
+            })
+        );
+        assert_eq!(location("s3://bucket").unwrap().prefix, "");
+        assert_eq!(location("s3://bucket/").unwrap().prefix, "");
+        assert!(location("https://bucket/thumbs").is_err());
+        assert!(location("s3:///thumbs").is_err());
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_content_type_follows_the_extension() {
+        use s3_sink::content_type;
+
+        assert_eq!(content_type(Path::new("a/abc.jpg")), "image/jpeg");
+        assert_eq!(content_type(Path::new("abc.webp")), "image/webp");
+        assert_eq!(content_type(Path::new("abc")), "application/octet-stream");
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_s3_sink_puts_the_thumbnail_under_its_key() {
+        use s3::creds::Credentials;
+        use s3::Region;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = temp_dir.path();
+        std::fs::create_dir_all(output_dir.join("2024")).unwrap();
+        let thumbnail = output_dir.join("2024").join("abc.jpg");
+        std::fs::write(&thumbnail, "image bytes").unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let put_mock = server
+            .mock("PUT", "/bucket/thumbs/2024/abc.jpg")
+            .match_header("content-type", "image/jpeg")
+            .match_header(
+                "authorization",
+                mockito::Matcher::Regex("^AWS4-HMAC-SHA256 ".into()),
+            )
+            .match_body("image bytes")
+            .with_header("ETag", "\"etag\"")
+            .create_async()
+            .await;
+
+        let sink = S3Sink::new(
+            &"s3://bucket/thumbs".parse().unwrap(),
+            Region::Custom {
+                region: "us-east-1".to_string(),
+                endpoint: server.url(),
+            },
+            Credentials::new(Some("access"), Some("secret"), None, None, None).unwrap(),
+            output_dir,
+        )
+        .unwrap();
+        sink.put(&thumbnail).await.unwrap();
+
+        put_mock.assert_async().await;
+    }
+}
@@ -0,0 +1,186 @@
+use crate::middleware::Client;
+use crate::video_ids::is_valid_video_id;
+use crate::{PlaylistItem, VideoContentDetails};
+use regex::Regex;
+use serde_json::Value;
+use std::error::Error;
+use std::sync::LazyLock;
+
+/// Matches the `ytInitialData` object a YouTube page assigns in a script.
+static INITIAL_DATA_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?s)ytInitialData"?\]?\s*=\s*(\{.*?\});\s*</script>"#).unwrap());
+
+/// A channel's uploads, newest first, or `None` if it has no uploads at all.
+pub type Uploads = Option<Vec<PlaylistItem>>;
+
+/// Where the videos of a channel are listed from: the Data API, or the channel's pages for
+/// runs without an API key.
+#[async_trait::async_trait]
+pub trait ChannelSource: Send + Sync {
+    /// Finds the ID of the channel at `channel_url`.
+    async fn resolve(&self, channel_url: &str) -> Result<String, Box<dyn Error>>;
+
+    /// Lists the channel's uploads.
+    async fn list_uploads(&self, channel_id: &str) -> Result<Uploads, Box<dyn Error>>;
+}
+
+/// Lists channels by reading the `ytInitialData` embedded in their pages, for `--scrape`.
+/// No API key is needed, but only the videos on the first page of the channel's Videos tab
+/// are found, about 30, and it breaks whenever YouTube changes its pages.
+pub struct ScrapingSource<'a> {
+    client: &'a Client,
+    /// Where channel pages are read from, like `https://www.youtube.com`.
+    base_url: &'a str,
+}
+
+impl<'a> ScrapingSource<'a> {
+    pub fn new(client: &'a Client, base_url: &'a str) -> Self {
+        ScrapingSource { client, base_url }
+    }
+
+    async fn initial_data(&self, url: &str) -> Result<Value, Box<dyn Error>> {
+        let page = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(extract_initial_data(&page).ok_or_else(|| format!("no ytInitialData on {}", url))?)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChannelSource for ScrapingSource<'_> {
+    async fn resolve(&self, channel_url: &str) -> Result<String, Box<dyn Error>> {
+        let channel_url = crate::normalize_channel_url(channel_url)?;
+        let url = reqwest::Url::parse(&channel_url)?;
+        if let Some(channel_id) = url.path().strip_prefix("/channel/") {
+            return Ok(channel_id.to_string());
+        }
+        let initial_data = self.initial_data(&channel_url).await?;
+        Ok(channel_id(&initial_data)
+            .ok_or_else(|| format!("couldn't find the channel ID on {}", channel_url))?)
+    }
+
+    async fn list_uploads(&self, channel_id: &str) -> Result<Uploads, Box<dyn Error>> {
+        let url = format!("{}/channel/{}/videos", self.base_url, channel_id);
+        let initial_data = self.initial_data(&url).await?;
+        let items = video_ids(&initial_data)
+            .into_iter()
+            .map(|video_id| PlaylistItem {
+                content_details: VideoContentDetails {
+                    video_id,
+                    video_published_at: None,
+                },
+                snippet: None,
+            })
+            .collect();
+        Ok(Some(items))
+    }
+}
+
+/// Extracts the `ytInitialData` object a YouTube page embeds in a script, if it has one.
+pub fn extract_initial_data(html: &str) -> Option<Value> {
+    let json = INITIAL_DATA_PATTERN.captures(html)?.get(1)?.as_str();
+    serde_json::from_str(json).ok()
+}
+
+/// The ID of the channel a channel page's `ytInitialData` describes.
+pub fn channel_id(initial_data: &Value) -> Option<String> {
+    initial_data
+        .pointer("/metadata/channelMetadataRenderer/externalId")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+/// The IDs of the videos listed in a page's `ytInitialData`, in page order and without
+/// repeats.
+pub fn video_ids(initial_data: &Value) -> Vec<String> {
+    let mut video_ids = Vec::new();
+    collect_video_ids(initial_data, &mut video_ids);
+    video_ids
+}
+
+fn collect_video_ids(value: &Value, video_ids: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let video_id = value.get("videoId").and_then(Value::as_str);
+                if let (true, Some(video_id)) = (key == "videoRenderer", video_id) {
+                    if is_valid_video_id(video_id) && !video_ids.iter().any(|id| id == video_id) {
+                        video_ids.push(video_id.to_string());
+                    }
+                } else {
+                    collect_video_ids(value, video_ids);
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_video_ids(value, video_ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The Videos tab of a channel, cut down to what's read from it.
+    pub(crate) const VIDEOS_PAGE: &str = r#"<html><body><script nonce="abc">var ytInitialData = {"contents":{"twoColumnBrowseResultsRenderer":{"tabs":[{"tabRenderer":{"title":"Home","selected":false}},{"tabRenderer":{"title":"Videos","selected":true,"content":{"richGridRenderer":{"contents":[{"richItemRenderer":{"content":{"videoRenderer":{"videoId":"dQw4w9WgXcQ","title":{"runs":[{"text":"First; </script> not the end"}]},"navigationEndpoint":{"watchEndpoint":{"videoId":"dQw4w9WgXcQ"}}}}}},{"richItemRenderer":{"content":{"videoRenderer":{"videoId":"9bZkp7q19f0","title":{"runs":[{"text":"Second"}]}}}}},{"richItemRenderer":{"content":{"videoRenderer":{"videoId":"dQw4w9WgXcQ"}}}},{"continuationItemRenderer":{"continuationEndpoint":{"continuationCommand":{"token":"4qmFsgKl"}}}}]}}}}]}},"metadata":{"channelMetadataRenderer":{"title":"A channel","externalId":"UCuAXFkgsw1L7xaCfnd5JJOw","vanityChannelUrl":"http://www.youtube.com/@handle"}}};</script><script>var other = {};</script></body></html>"#;
+
+    #[test]
+    fn test_video_ids_from_initial_data() {
+        let initial_data = extract_initial_data(VIDEOS_PAGE).unwrap();
+
+        assert_eq!(video_ids(&initial_data), ["dQw4w9WgXcQ", "9bZkp7q19f0"]);
+        assert_eq!(
+            channel_id(&initial_data).as_deref(),
+            Some("UCuAXFkgsw1L7xaCfnd5JJOw")
+        );
+    }
+
+    #[test]
+    fn test_pages_without_initial_data() {
+        assert_eq!(extract_initial_data("<html></html>"), None);
+        let initial_data = serde_json::json!({"contents": {}});
+        assert!(video_ids(&initial_data).is_empty());
+        assert_eq!(channel_id(&initial_data), None);
+    }
+
+    #[tokio::test]
+    async fn test_scraping_source_resolves_and_lists_a_handle() {
+        let mut server = mockito::Server::new_async().await;
+        let _channel_mock = server
+            .mock("GET", "/@handle")
+            .with_body(VIDEOS_PAGE)
+            .create_async()
+            .await;
+        let videos_mock = server
+            .mock("GET", "/channel/UCuAXFkgsw1L7xaCfnd5JJOw/videos")
+            .with_body(VIDEOS_PAGE)
+            .create_async()
+            .await;
+        let client = reqwest::Client::new().into();
+        let base_url = server.url();
+        let source = ScrapingSource::new(&client, &base_url);
+
+        let channel_id = source
+            .resolve(&format!("{}/@handle/videos?si=abc", base_url))
+            .await
+            .unwrap();
+        let items = source.list_uploads(&channel_id).await.unwrap().unwrap();
+
+        videos_mock.assert_async().await;
+        assert_eq!(channel_id, "UCuAXFkgsw1L7xaCfnd5JJOw");
+        let video_ids: Vec<&str> = items
+            .iter()
+            .map(|item| item.content_details.video_id.as_str())
+            .collect();
+        assert_eq!(video_ids, ["dQw4w9WgXcQ", "9bZkp7q19f0"]);
+    }
+}
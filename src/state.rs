@@ -0,0 +1,81 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+
+/// Name of the state file used in the output directory when `--watch` is given
+/// without an explicit `--state-file`.
+pub const STATE_FILE: &str = ".download_state.json";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct StateFile {
+    downloaded: BTreeSet<String>,
+}
+
+/// The set of video IDs whose thumbnails have already been downloaded, persisted
+/// between runs so only new videos are fetched.
+#[derive(Debug)]
+pub struct DownloadState {
+    path: PathBuf,
+    downloaded: Mutex<BTreeSet<String>>,
+}
+
+impl DownloadState {
+    /// Loads the state file, starting empty if it doesn't exist yet.
+    pub async fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let state = match fs::read_to_string(path).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StateFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(DownloadState {
+            path: path.to_path_buf(),
+            downloaded: Mutex::new(state.downloaded),
+        })
+    }
+
+    pub fn contains(&self, video_id: &str) -> bool {
+        self.downloaded.lock().unwrap().contains(video_id)
+    }
+
+    pub fn mark_downloaded(&self, video_id: &str) {
+        self.downloaded.lock().unwrap().insert(video_id.to_string());
+    }
+
+    /// Writes the state back to disk, via a temporary file so a crash can't corrupt it.
+    /// With `fsync`, it's synced to disk before this returns.
+    pub async fn save(&self, fsync: bool) -> Result<(), Box<dyn Error>> {
+        let state = StateFile {
+            downloaded: self.downloaded.lock().unwrap().clone(),
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        paths::replace_file(&self.path, serde_json::to_string_pretty(&state)?, fsync).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_state_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("nested").join(STATE_FILE);
+
+        let state = DownloadState::load(&path).await.unwrap();
+        assert!(!state.contains("video1"));
+        state.mark_downloaded("video1");
+        state.save(false).await.unwrap();
+
+        let reloaded = DownloadState::load(&path).await.unwrap();
+        assert!(reloaded.contains("video1"));
+        assert!(!reloaded.contains("video2"));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::middleware::Client;
+use crate::paths::{self, OutputLocation};
+use regex::Regex;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use tokio::fs;
+
+/// Matches the storyboard spec in a watch page's player response, as a JSON string.
+static SPEC_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""playerStoryboardSpecRenderer":\{"spec":("(?:[^"\\]|\\.)*")"#).unwrap()
+});
+
+/// The most frames a storyboard level is believed to tile across or down a sheet. YouTube
+/// uses 10, so a spec far beyond it is broken or made up.
+const MAX_GRID_SIDE: u32 = 100;
+
+/// The most frames a storyboard level is believed to have, which bounds how many sheets are
+/// fetched for it.
+const MAX_FRAMES: u32 = 100_000;
+
+/// One resolution of a video's storyboard: frames of `width`×`height` pixels, tiled
+/// `columns`×`rows` to a sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoryboardLevel {
+    pub width: u32,
+    pub height: u32,
+    /// How many frames the level has across all its sheets.
+    pub frames: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// Stands in for `$N` in the URL template; `$M` in it is the sheet number.
+    name: String,
+    signature: String,
+}
+
+impl StoryboardLevel {
+    /// How many sheets the frames are spread over.
+    pub fn sheets(&self) -> u32 {
+        self.frames
+            .div_ceil(self.columns.saturating_mul(self.rows).max(1))
+    }
+}
+
+/// The storyboard spec of a video: a URL template and the levels it can be filled in with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Storyboard {
+    url_template: String,
+    pub levels: Vec<StoryboardLevel>,
+}
+
+impl Storyboard {
+    /// Parses a spec such as `https://i.ytimg.com/sb/ID/storyboard3_L$L/$N.jpg?sqp=...|
+    /// 48#27#100#10#10#0#default#rs$...|80#45#100#10#10#2000#M$M#rs$...`, whose fields after
+    /// the template are, per level: frame width and height, frame count, columns, rows, the
+    /// interval between frames, the sheet name and the signature. The spec comes from a scraped
+    /// page, so levels with no frames, or implausibly many, are rejected.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split('|');
+        let url_template = parts.next().unwrap_or_default().to_string();
+        if !url_template.starts_with("http") {
+            return Err(format!("no URL template in storyboard spec \"{}\"", spec));
+        }
+        let levels = parts
+            .map(|level| {
+                let fields: Vec<&str> = level.split('#').collect();
+                let [width, height, frames, columns, rows, _interval, name, signature] = fields[..]
+                else {
+                    return Err(format!("malformed storyboard level \"{}\"", level));
+                };
+                let number = |field: &str| {
+                    field
+                        .parse::<u32>()
+                        .map_err(|_| format!("malformed storyboard level \"{}\"", level))
+                };
+                let parsed = StoryboardLevel {
+                    width: number(width)?,
+                    height: number(height)?,
+                    frames: number(frames)?,
+                    columns: number(columns)?,
+                    rows: number(rows)?,
+                    name: name.to_string(),
+                    signature: signature.to_string(),
+                };
+                let grid = 1..=MAX_GRID_SIDE;
+                if !grid.contains(&parsed.columns)
+                    || !grid.contains(&parsed.rows)
+                    || !(1..=MAX_FRAMES).contains(&parsed.frames)
+                {
+                    return Err(format!("implausible storyboard level \"{}\"", level));
+                }
+                Ok(parsed)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if levels.is_empty() {
+            return Err("the storyboard spec has no levels".to_string());
+        }
+        Ok(Storyboard {
+            url_template,
+            levels,
+        })
+    }
+
+    /// The level with the largest frames.
+    pub fn largest_level(&self) -> usize {
+        (0..self.levels.len())
+            .max_by_key(|&index| {
+                let level = &self.levels[index];
+                u64::from(level.width) * u64::from(level.height)
+            })
+            .unwrap_or_default()
+    }
+
+    /// The URLs of every sheet of the level at `index`.
+    pub fn sheet_urls(&self, index: usize) -> Vec<String> {
+        let level = &self.levels[index];
+        let url = self
+            .url_template
+            .replace("$L", &index.to_string())
+            .replace("$N", &level.name);
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let url = format!("{}{}sigh={}", url, separator, level.signature);
+        (0..level.sheets())
+            .map(|sheet| url.replace("$M", &sheet.to_string()))
+            .collect()
+    }
+}
+
+/// Extracts the storyboard spec from the player response embedded in a watch page, if it
+/// has one.
+pub fn extract_spec(html: &str) -> Option<String> {
+    let quoted = SPEC_PATTERN.captures(html)?.get(1)?.as_str();
+    serde_json::from_str(quoted).ok()
+}
+
+/// The path a storyboard sheet is saved at: `{id}.storyboard{n}.jpg` next to the thumbnail.
+pub fn sheet_path(location: &OutputLocation, video_id: &str, sheet: usize) -> PathBuf {
+    paths::output_path_for(video_id, &format!("storyboard{}.jpg", sheet), location)
+}
+
+/// Downloads every sheet of the largest storyboard level of a video, for `--storyboards`.
+/// The spec is read from the video's watch page, as the Data API doesn't offer it, so this
+/// breaks whenever YouTube changes the page. Returns the paths of the saved sheets.
+pub async fn download(
+    client: &Client,
+    video_id: &str,
+    watch_base_url: &str,
+    location: &OutputLocation,
+) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    let page = client
+        .get(format!("{}/watch?v={}", watch_base_url, video_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let spec = extract_spec(&page).ok_or("the watch page has no storyboard spec")?;
+    let storyboard = Storyboard::parse(&spec)?;
+    let mut saved = Vec::new();
+    for (sheet, url) in storyboard
+        .sheet_urls(storyboard.largest_level())
+        .into_iter()
+        .enumerate()
+    {
+        let bytes = client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let path = sheet_path(location, video_id, sheet);
+        fs::write(&path, &bytes).await?;
+        saved.push(path);
+    }
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "https://i.ytimg.com/sb/abc/storyboard3_L$L/$N.jpg?sqp=xyz\
+                        |48#27#100#10#10#0#default#rs$one\
+                        |160#90#30#5#5#2000#M$M#rs$two";
+
+    #[test]
+    fn test_parse_spec_and_sheet_urls() {
+        let storyboard = Storyboard::parse(SPEC).unwrap();
+
+        assert_eq!(storyboard.levels.len(), 2);
+        assert_eq!(storyboard.largest_level(), 1);
+        assert_eq!(storyboard.levels[1].sheets(), 2);
+        assert_eq!(
+            storyboard.sheet_urls(0),
+            ["https://i.ytimg.com/sb/abc/storyboard3_L0/default.jpg?sqp=xyz&sigh=rs$one"]
+        );
+        assert_eq!(
+            storyboard.sheet_urls(1),
+            [
+                "https://i.ytimg.com/sb/abc/storyboard3_L1/M0.jpg?sqp=xyz&sigh=rs$two",
+                "https://i.ytimg.com/sb/abc/storyboard3_L1/M1.jpg?sqp=xyz&sigh=rs$two",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_specs() {
+        assert!(Storyboard::parse("").is_err());
+        assert!(Storyboard::parse("https://i.ytimg.com/sb/abc/$N.jpg").is_err());
+        assert!(Storyboard::parse("https://i.ytimg.com/sb/abc/$N.jpg|48#27#x").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_implausible_levels() {
+        for level in [
+            "48#27#100#0#10#0#default#rs$x",
+            "48#27#100#10#0#0#default#rs$x",
+            "48#27#0#10#10#0#default#rs$x",
+            "48#27#100#100000#100000#0#default#rs$x",
+            "48#27#4000000000#10#10#0#default#rs$x",
+        ] {
+            let spec = format!("https://i.ytimg.com/sb/abc/$N.jpg|{}", level);
+            assert!(Storyboard::parse(&spec).is_err(), "{}", level);
+        }
+    }
+
+    #[test]
+    fn test_largest_level_of_huge_frames_does_not_overflow() {
+        let spec = "https://i.ytimg.com/sb/abc/$N.jpg\
+                    |4000000000#4000000000#1#1#1#0#default#rs$x\
+                    |48#27#1#1#1#0#default#rs$y";
+
+        assert_eq!(Storyboard::parse(spec).unwrap().largest_level(), 0);
+    }
+
+    #[test]
+    fn test_extract_spec_unescapes_the_json_string() {
+        let html = r#"var ytInitialPlayerResponse = {"storyboards":{"playerStoryboardSpecRenderer":{"spec":"https://i.ytimg.com/sb/abc/$N.jpg?sqp=a\u0026b|48#27#1#1#1#0#default#rs$x","recommendedLevel":1}}};"#;
+
+        assert_eq!(
+            extract_spec(html).as_deref(),
+            Some("https://i.ytimg.com/sb/abc/$N.jpg?sqp=a&b|48#27#1#1#1#0#default#rs$x")
+        );
+        assert_eq!(extract_spec("<html></html>"), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_saves_the_sheets() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let location = OutputLocation::new(temp_dir.path());
+        let mut server = mockito::Server::new_async().await;
+        let spec = format!(
+            "{}/sb/abc/storyboard3_L$L/$N.jpg?sqp=xyz|160#90#4#2#2#2000#M$M#rs$sig",
+            server.url()
+        );
+        let page = format!(
+            r#"<script>{{"playerStoryboardSpecRenderer":{{"spec":{}}}}}</script>"#,
+            serde_json::to_string(&spec).unwrap()
+        );
+        let _page_mock = server
+            .mock("GET", "/watch?v=abc")
+            .with_body(page)
+            .create_async()
+            .await;
+        let sheet_mock = server
+            .mock("GET", "/sb/abc/storyboard3_L0/M0.jpg?sqp=xyz&sigh=rs$sig")
+            .with_body("sheet image")
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new().into();
+        let saved = download(&client, "abc", &server.url(), &location)
+            .await
+            .unwrap();
+
+        sheet_mock.assert_async().await;
+        assert_eq!(saved, [sheet_path(&location, "abc", 0)]);
+        assert_eq!(std::fs::read(&saved[0]).unwrap(), b"sheet image");
+    }
+}
@@ -0,0 +1,167 @@
+use clap::ValueEnum;
+
+/// A thumbnail variant served by the YouTube CDN.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// maxresdefault.jpg, up to 1280x720. Missing for many older videos.
+    #[value(name = "maxres")]
+    MaxRes,
+    /// sddefault.jpg, 640x480.
+    #[value(name = "sd")]
+    Standard,
+    /// hqdefault.jpg, 480x360. Available for every video.
+    #[value(name = "hq")]
+    High,
+    /// mqdefault.jpg, 320x180.
+    #[value(name = "mq")]
+    Medium,
+    /// default.jpg, 120x90.
+    #[value(name = "default")]
+    Default,
+}
+
+impl Resolution {
+    /// Every resolution from largest to smallest, the order downloads fall back through.
+    pub const ALL: [Resolution; 5] = [
+        Resolution::MaxRes,
+        Resolution::Standard,
+        Resolution::High,
+        Resolution::Medium,
+        Resolution::Default,
+    ];
+
+    /// The file name the CDN serves this variant under, without its extension.
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            Resolution::MaxRes => "maxresdefault",
+            Resolution::Standard => "sddefault",
+            Resolution::High => "hqdefault",
+            Resolution::Medium => "mqdefault",
+            Resolution::Default => "default",
+        }
+    }
+
+    /// The short name `--resolution` and `--variant-template`'s `{res}` use for this variant.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Resolution::MaxRes => "maxres",
+            Resolution::Standard => "sd",
+            Resolution::High => "hq",
+            Resolution::Medium => "mq",
+            Resolution::Default => "default",
+        }
+    }
+
+    /// The width and height this variant is normally served at.
+    pub fn dimensions(self) -> (u32, u32) {
+        match self {
+            Resolution::MaxRes => (1280, 720),
+            Resolution::Standard => (640, 480),
+            Resolution::High => (480, 360),
+            Resolution::Medium => (320, 180),
+            Resolution::Default => (120, 90),
+        }
+    }
+}
+
+/// Checks whether `host` serves YouTube thumbnails, so a redirect to it can be followed.
+pub fn is_image_host(host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    host == "img.youtube.com"
+        || ["ytimg.com", "ggpht.com"]
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// Builds the URL of a video's thumbnail at the given resolution.
+pub fn thumbnail_url(video_id: &str, resolution: Resolution, base_url: &str) -> String {
+    format!(
+        "{}/vi/{}/{}.jpg",
+        base_url,
+        video_id,
+        resolution.file_stem()
+    )
+}
+
+/// Builds the URL of the WebP version of a video's thumbnail, which exists for some live
+/// streams and premieres that have no JPEG.
+pub fn webp_thumbnail_url(video_id: &str, resolution: Resolution, base_url: &str) -> String {
+    format!(
+        "{}/vi_webp/{}/{}.webp",
+        base_url,
+        video_id,
+        resolution.file_stem()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_URL: &str = "https://img.youtube.com";
+
+    #[test]
+    fn test_thumbnail_url_for_each_resolution() {
+        let expected = [
+            (Resolution::MaxRes, "maxresdefault.jpg"),
+            (Resolution::Standard, "sddefault.jpg"),
+            (Resolution::High, "hqdefault.jpg"),
+            (Resolution::Medium, "mqdefault.jpg"),
+            (Resolution::Default, "default.jpg"),
+        ];
+        for (resolution, file_name) in expected {
+            assert_eq!(
+                thumbnail_url("abc123", resolution, BASE_URL),
+                format!("https://img.youtube.com/vi/abc123/{}", file_name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_short_names_match_the_resolution_flag() {
+        for resolution in Resolution::ALL {
+            let value = resolution.to_possible_value().unwrap();
+            assert_eq!(resolution.short_name(), value.get_name());
+        }
+    }
+
+    #[test]
+    fn test_webp_thumbnail_url() {
+        assert_eq!(
+            webp_thumbnail_url("abc123", Resolution::MaxRes, BASE_URL),
+            "https://img.youtube.com/vi_webp/abc123/maxresdefault.webp"
+        );
+    }
+
+    #[test]
+    fn test_image_hosts() {
+        assert!(is_image_host("i.ytimg.com"));
+        assert!(is_image_host("I9.YTIMG.COM"));
+        assert!(is_image_host("img.youtube.com"));
+        assert!(is_image_host("yt3.ggpht.com"));
+        assert!(!is_image_host("www.youtube.com"));
+        assert!(!is_image_host("notytimg.com"));
+        assert!(!is_image_host("ytimg.com.example.org"));
+    }
+
+    #[test]
+    fn test_resolutions_are_ordered_largest_first() {
+        assert_eq!(Resolution::ALL[0], Resolution::MaxRes);
+        assert_eq!(Resolution::ALL[4], Resolution::Default);
+    }
+
+    #[test]
+    fn test_resolution_value_names() {
+        let names: Vec<String> = Resolution::value_variants()
+            .iter()
+            .map(|resolution| {
+                resolution
+                    .to_possible_value()
+                    .unwrap()
+                    .get_name()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, ["maxres", "sd", "hq", "mq", "default"]);
+    }
+}
@@ -0,0 +1,59 @@
+use regex::Regex;
+
+/// Selects videos by title, for `--title-contains` and `--title-regex`.
+#[derive(Debug, Clone)]
+pub struct TitleFilter {
+    contains: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl TitleFilter {
+    /// Builds a filter from the flags, or `None` if neither was given.
+    pub fn new(contains: Option<&str>, regex: Option<&Regex>) -> Option<Self> {
+        if contains.is_none() && regex.is_none() {
+            return None;
+        }
+        Some(TitleFilter {
+            contains: contains.map(str::to_lowercase),
+            regex: regex.cloned(),
+        })
+    }
+
+    /// Whether `title` contains the substring (ignoring case) and matches the regex,
+    /// whichever of them are set.
+    pub fn matches(&self, title: &str) -> bool {
+        self.contains
+            .as_ref()
+            .is_none_or(|substring| title.to_lowercase().contains(substring))
+            && self
+                .regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_flags_means_no_filter() {
+        assert!(TitleFilter::new(None, None).is_none());
+    }
+
+    #[test]
+    fn test_contains_ignores_case() {
+        let filter = TitleFilter::new(Some("devlog"), None).unwrap();
+        assert!(filter.matches("My Game DevLog #3"));
+        assert!(!filter.matches("Cooking with friends"));
+    }
+
+    #[test]
+    fn test_contains_and_regex_must_both_match() {
+        let regex = Regex::new(r"#\d+$").unwrap();
+        let filter = TitleFilter::new(Some("devlog"), Some(&regex)).unwrap();
+        assert!(filter.matches("Devlog #12"));
+        assert!(!filter.matches("Devlog special"));
+        assert!(!filter.matches("Vlog #12"));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::DownloaderError;
+use std::collections::HashSet;
+
+/// Checks that `id` looks like a YouTube video ID: 11 characters from the URL-safe
+/// base64 alphabet.
+pub fn is_valid_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parses a `--video-ids-file`: one video ID per line, ignoring blank lines, lines starting
+/// with '#' and repeated IDs. Only the first comma-separated field of each line is used and a
+/// `video_id` header is skipped, so a CSV written by `--export csv` can be read as is.
+/// Every malformed ID is reported at once, before any request is made for the others.
+pub fn parse_video_ids(contents: &str) -> Result<Vec<String>, DownloaderError> {
+    let mut seen = HashSet::new();
+    let mut video_ids = Vec::new();
+    let mut invalid = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let id = line.split(',').next().unwrap_or_default().trim();
+        if index == 0 && id == "video_id" {
+            continue;
+        }
+        if !is_valid_video_id(id) {
+            invalid.push(format!("Line {}: \"{}\"", index + 1, id));
+        } else if seen.insert(id) {
+            video_ids.push(id.to_string());
+        }
+    }
+    match invalid.len() {
+        0 => Ok(video_ids),
+        1 => Err(DownloaderError::InvalidInput(format!(
+            "{} is not a valid YouTube video ID.",
+            invalid[0]
+        ))),
+        _ => Err(DownloaderError::InvalidInput(format!(
+            "These are not valid YouTube video IDs:\n{}",
+            invalid.join("\n")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_video_ids() {
+        assert!(is_valid_video_id("dQw4w9WgXcQ"));
+        assert!(is_valid_video_id("a-b_c-d_e-f"));
+        assert!(!is_valid_video_id("dQw4w9WgXc"));
+        assert!(!is_valid_video_id("dQw4w9WgXcQQ"));
+        assert!(!is_valid_video_id("dQw4w9WgX/Q"));
+        assert!(!is_valid_video_id(""));
+        assert!(!is_valid_video_id("dQw4w9WgX Q"));
+        assert!(!is_valid_video_id("dQw4w9WgX=Q"));
+        assert!(!is_valid_video_id("dQw4w9WgXé"));
+    }
+
+    #[test]
+    fn test_parse_video_ids() {
+        let contents = "# saved IDs\ndQw4w9WgXcQ\n\n  9bZkp7q19f0  \ndQw4w9WgXcQ\n";
+        assert_eq!(
+            parse_video_ids(contents).unwrap(),
+            vec!["dQw4w9WgXcQ", "9bZkp7q19f0"]
+        );
+    }
+
+    #[test]
+    fn test_parse_exported_csv() {
+        let contents = "video_id,title,published_at,thumbnail_url\n\
+                        dQw4w9WgXcQ,\"A, title\",2024-03-15T10:00:00Z,https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg\n";
+        assert_eq!(parse_video_ids(contents).unwrap(), vec!["dQw4w9WgXcQ"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_id() {
+        let error = parse_video_ids("dQw4w9WgXcQ\nnot-an-id\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: \"not-an-id\" is not a valid YouTube video ID."
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_every_invalid_id() {
+        let error = parse_video_ids("not-an-id\ndQw4w9WgXcQ\nwatch?v=dQw4w9WgXcQ\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "These are not valid YouTube video IDs:\n\
+             Line 1: \"not-an-id\"\n\
+             Line 3: \"watch?v=dQw4w9WgXcQ\""
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A slice of a channel's video list by position for `--range`, counting from zero like
+/// Rust ranges: `100..200`, `100..=199`, `500..` or `..50`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoRange {
+    start: usize,
+    /// Exclusive; `None` for an open-ended range.
+    end: Option<usize>,
+}
+
+impl FromStr for VideoRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let (start, end) = range
+            .split_once("..")
+            .ok_or_else(|| format!("\"{}\" isn't a range like 100..200, 500.. or ..50", range))?;
+        let number = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("\"{}\" isn't a valid position", n))
+        };
+        let start = if start.trim().is_empty() {
+            0
+        } else {
+            number(start)?
+        };
+        let end = match end.strip_prefix('=') {
+            Some(end) => Some(
+                number(end)?
+                    .checked_add(1)
+                    .ok_or("the range is too large")?,
+            ),
+            None if end.trim().is_empty() => None,
+            None => Some(number(end)?),
+        };
+        if end.is_some_and(|end| end < start) {
+            return Err(format!("the range {} ends before it starts", range));
+        }
+        Ok(VideoRange { start, end })
+    }
+}
+
+impl fmt::Display for VideoRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..", self.start)?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
+        }
+        Ok(())
+    }
+}
+
+impl VideoRange {
+    /// The positions this range selects from a list of `len` videos. A range reaching past
+    /// the end is an error, unless `clamp` cuts it down to the videos there are.
+    pub fn positions(self, len: usize, clamp: bool) -> Result<Range<usize>, String> {
+        let end = self.end.unwrap_or(len);
+        if clamp {
+            return Ok(self.start.min(len)..end.min(len));
+        }
+        if self.start > len || end > len {
+            return Err(format!(
+                "--range {} is out of bounds for {} videos; pass --clamp to download what's there",
+                self, len
+            ));
+        }
+        Ok(self.start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(range: &str, len: usize) -> Result<Range<usize>, String> {
+        range.parse::<VideoRange>().unwrap().positions(len, false)
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(positions("100..200", 600), Ok(100..200));
+        assert_eq!(positions("100..=199", 600), Ok(100..200));
+        assert_eq!(positions("500..", 600), Ok(500..600));
+        assert_eq!(positions("..50", 600), Ok(0..50));
+        assert_eq!(positions("..", 600), Ok(0..600));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_ranges() {
+        for range in ["100", "a..b", "200..100", "-1..5"] {
+            assert!(range.parse::<VideoRange>().is_err(), "{}", range);
+        }
+    }
+
+    #[test]
+    fn test_out_of_bounds_unless_clamped() {
+        assert_eq!(
+            positions("500..", 120),
+            Err(
+                "--range 500.. is out of bounds for 120 videos; pass --clamp to download \
+                 what's there"
+                    .to_string()
+            )
+        );
+        assert!(positions("100..200", 120).is_err());
+        let range: VideoRange = "100..200".parse().unwrap();
+        assert_eq!(range.positions(120, true), Ok(100..120));
+        let range: VideoRange = "500..".parse().unwrap();
+        assert_eq!(range.positions(120, true), Ok(120..120));
+    }
+}
@@ -0,0 +1,101 @@
+//! Runs the binary to check what `--quiet` leaves on stdout and stderr.
+
+use std::process::{Command, Output};
+
+const CHANNEL_ID: &str = "UCuAXFkgsw1L7xaCfnd5JJOw";
+const VIDEO_ID: &str = "dQw4w9WgXcQ";
+
+/// Downloads the one video of a channel with `extra` flags, serving `maxres_status` for
+/// its maxres thumbnail and `other_status` for every smaller one.
+fn run_quiet(maxres_status: usize, other_status: usize, extra: &[&str]) -> Output {
+    let mut server = mockito::Server::new();
+    let _playlist_mock = server
+        .mock("GET", "/youtube/v3/playlistItems")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            serde_json::json!({"items": [{"contentDetails": {"videoId": VIDEO_ID}}]}).to_string(),
+        )
+        .create();
+    let _videos_mock = server
+        .mock("GET", "/youtube/v3/videos")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            serde_json::json!({"items": [{
+                "id": VIDEO_ID,
+                "status": {"privacyStatus": "public", "uploadStatus": "processed"}
+            }]})
+            .to_string(),
+        )
+        .create();
+    let _maxres_mock = server
+        .mock(
+            "GET",
+            format!("/vi/{}/maxresdefault.jpg", VIDEO_ID).as_str(),
+        )
+        .with_status(maxres_status)
+        .with_body("image")
+        .create();
+    let _thumbnail_mock = server
+        .mock("GET", mockito::Matcher::Regex("^/vi/".to_string()))
+        .with_status(other_status)
+        .with_body("image")
+        .create();
+    let output_dir = tempfile::tempdir().unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_youtube-images"))
+        .args([
+            "--channel-id",
+            CHANNEL_ID,
+            "--api-base-url",
+            &server.url(),
+            "--thumbnail-base-url",
+            &server.url(),
+            "--output-dir",
+            output_dir.path().to_str().unwrap(),
+            "--quiet",
+        ])
+        .args(extra)
+        .env("YOUTUBE_API_KEY", "test_api_key")
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_quiet_run_prints_nothing() {
+    let output = run_quiet(200, 200, &[]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn test_quiet_run_falls_back_silently() {
+    let output = run_quiet(404, 200, &[]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn test_quiet_run_still_reports_errors() {
+    let output = run_quiet(404, 404, &[]);
+
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains(&format!("No thumbnail available for video ID {}", VIDEO_ID)));
+}
+
+#[test]
+fn test_quiet_json_prints_only_the_summary() {
+    let output = run_quiet(200, 200, &["--json"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"event\":\"summary\",\"ok\":1,\"failed\":0}\n"
+    );
+}